@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use mqttrs::*;
+
+fuzz_target!(|data: &[u8]| {
+    // Decoding must never panic, whatever the input.
+    let _ = decode_slice(data);
+    let _ = decode_slice_with_options(data, &DecodeOptions::strict());
+    let _ = v5::decode_slice(data);
+    let _ = clone_packet(data, &mut [0; 16]);
+
+    // Whatever decodes must encode again.
+    if let Ok(Some((len, packet))) = decode_slice_with_len(data) {
+        let mut buf = vec![0; packet.encoded_len()];
+        if encode_slice(&packet, &mut buf).is_ok() {
+            assert_eq!(Ok(Some((buf.len(), packet))), decode_slice_with_len(&buf));
+        }
+        assert!(len <= data.len());
+    }
+});
@@ -0,0 +1,23 @@
+//! Standalone MQTT 3.1.1 broker: `mqttrs-broker [ADDRESS]`, listening on `0.0.0.0:1883` by
+//! default.
+
+use mqttrs::broker::Broker;
+use std::process::exit;
+use tokio::net::TcpListener;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| "0.0.0.0:1883".into());
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("mqttrs-broker: cannot listen on {}: {}", addr, e);
+            exit(1);
+        }
+    };
+    eprintln!("mqttrs-broker: listening on {}", addr);
+    if let Err(e) = Broker::new().serve(listener).await {
+        eprintln!("mqttrs-broker: {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,506 @@
+//! Minimal MQTT 3.1.1 broker for [tokio], built on [MqttCodec] and [SubscriptionTree].
+//!
+//! It is meant for integration tests and small gateways. It handles `QoS` 0, 1 and 2, wildcard
+//! subscriptions, retained messages and Last Wills, but sessions aren't persisted: every
+//! connection starts with a clean session, and its subscriptions and in-flight messages are
+//! dropped when it is closed. There is no authentication. At most 64 `QoS` 1 and 2 messages are
+//! in flight to each client, further messages wait for their acknowledgement.
+//!
+//! Connections don't use [ServerSession]: its outgoing messages borrow their topic and payload,
+//! while the broker hands owned messages to each connection, and its queues have a fixed capacity,
+//! while a connection queues any number of messages beyond its in-flight window.
+//!
+//! The `mqttrs-broker` binary serves a [Broker] on the address given as argument, or
+//! `0.0.0.0:1883`.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), mqttrs::Error> {
+//! use mqttrs::broker::Broker;
+//! use tokio::net::TcpListener;
+//!
+//! let listener = TcpListener::bind("127.0.0.1:1883").await?;
+//! Broker::new().serve(listener).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [tokio]: https://tokio.rs/
+//! [MqttCodec]: ../struct.MqttCodec.html
+//! [SubscriptionTree]: ../struct.SubscriptionTree.html
+//! [Broker]: struct.Broker.html
+//! [ServerSession]: ../struct.ServerSession.html
+
+use crate::{topic::check_topic_name, *};
+use bytes::BytesMut;
+use core::{fmt, time::Duration};
+use std::{
+    boxed::Box,
+    collections::{HashMap, HashSet, VecDeque},
+    format,
+    io::ErrorKind,
+    string::String,
+    sync::{Arc, Mutex},
+    vec::Vec,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    net::TcpListener,
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Maximum number of messages sent to a client and waiting for `Puback` or `Pubcomp`, which also
+/// keeps free pids available.
+pub(crate) const MAX_IN_FLIGHT: usize = 64;
+
+/// MQTT broker, routing messages between the connections it serves.
+///
+/// `Broker` is a handle to the shared broker state, and can be cloned to serve several listeners
+/// or to [`publish()`] from the application. Retained messages are kept in a
+/// [MemoryRetainedStore], unless another [RetainedStore] is given to
+/// [`with_retained_store()`].
+///
+/// [`publish()`]: #method.publish
+/// [MemoryRetainedStore]: ../struct.MemoryRetainedStore.html
+/// [RetainedStore]: ../trait.RetainedStore.html
+/// [`with_retained_store()`]: #method.with_retained_store
+#[derive(Clone)]
+pub struct Broker {
+    state: Arc<Mutex<State>>,
+}
+
+/// State shared by all connections.
+struct State {
+    /// Subscriptions by client id. The capacity is unused with the `std` feature.
+    subscriptions: SubscriptionTree<String, 0>,
+    clients: HashMap<String, Client>,
+    retained: Box<dyn RetainedStore + Send>,
+    next_conn: u64,
+}
+
+/// A connected client.
+#[derive(Debug)]
+struct Client {
+    /// Identifies the connection, in case another one takes over the client id.
+    conn: u64,
+    messages: mpsc::UnboundedSender<Message>,
+}
+
+/// Message sent to a connection task.
+#[derive(Debug)]
+enum Message {
+    /// Deliver a message. The connection sets its pid.
+    Publish(OwnedPublish),
+    /// Another connection took over the client id.
+    Close,
+}
+
+impl Broker {
+    pub fn new() -> Self {
+        Self::with_retained_store(MemoryRetainedStore::new())
+    }
+
+    /// Create a broker keeping retained messages in `store`.
+    pub fn with_retained_store(store: impl RetainedStore + Send + 'static) -> Self {
+        let state = State {
+            subscriptions: SubscriptionTree::new(),
+            clients: HashMap::new(),
+            retained: Box::new(store),
+            next_conn: 0,
+        };
+        Broker {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Accept connections from `listener`, serving each of them in a new task.
+    ///
+    /// Only returns if accepting a connection fails.
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), Error> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(self.clone().serve_connection(stream));
+        }
+    }
+
+    /// Serve one client connection, until it is closed.
+    ///
+    /// Returns `Ok(())` if the client disconnected cleanly, otherwise the error that closed the
+    /// connection (after publishing the client's Last Will). A connection closed without a
+    /// `Disconnect` returns `Error::UnexpectedEof`, and a refused connection
+    /// `Error::ConnectionRefused`.
+    pub async fn serve_connection<S>(self, stream: S) -> Result<(), Error>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+        let mut conn = Connection {
+            broker: self,
+            reader,
+            writer,
+            codec: MqttCodec::default(),
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            conn: 0,
+            client_id: String::new(),
+            messages: messages_rx,
+            keep_alive: Duration::from_secs(0),
+            last_received: Instant::now(),
+            last_will: None,
+            outgoing: HashSet::new(),
+            queued: VecDeque::new(),
+            incoming: HashSet::new(),
+            next_pid: Pid::new(),
+        };
+
+        let connect = match conn.read_packet().await? {
+            OwnedPacket::Connect(connect) => connect,
+            packet => return Err(Error::UnexpectedPacket(packet.get_type())),
+        };
+        let code = match connect.as_borrowed().validate() {
+            Ok(()) if connect.protocol == Protocol::MQTT311 => ConnectReturnCode::Accepted,
+            Ok(()) => ConnectReturnCode::RefusedProtocolVersion,
+            Err(Error::InvalidClientId) => ConnectReturnCode::RefusedIdentifierRejected,
+            Err(e) => return Err(e),
+        };
+        if code != ConnectReturnCode::Accepted {
+            let connack = Connack {
+                session_present: false,
+                code,
+            };
+            conn.send(OwnedPacket::Connack(connack)).await?;
+            return Err(Error::ConnectionRefused(code));
+        }
+
+        let (id, client_id) = conn.broker.register(connect.client_id, messages_tx);
+        conn.conn = id;
+        conn.client_id = client_id;
+        conn.keep_alive = Duration::from_secs(connect.keep_alive.into());
+        conn.last_will = connect.last_will;
+        let connack = Connack {
+            session_present: false,
+            code: ConnectReturnCode::Accepted,
+        };
+        let res = match conn.send(OwnedPacket::Connack(connack)).await {
+            Ok(()) => conn.run().await,
+            Err(e) => Err(e),
+        };
+
+        conn.broker.unregister(&conn.client_id, conn.conn);
+        if let Some(will) = conn.last_will.take() {
+            conn.broker.route(&OwnedPublish {
+                dup: false,
+                qospid: qospid(will.qos),
+                retain: will.retain,
+                topic_name: will.topic,
+                payload: will.message,
+            });
+        }
+        res
+    }
+
+    /// Publish a message to the subscribers of `topic_name`, as if a client published it.
+    ///
+    /// Returns `Error::InvalidTopicName` if `topic_name` isn't valid.
+    pub fn publish(
+        &self,
+        topic_name: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<(), Error> {
+        check_topic_name(topic_name)?;
+        self.route(&OwnedPublish {
+            dup: false,
+            qospid: qospid(qos),
+            retain,
+            topic_name: topic_name.into(),
+            payload: payload.into(),
+        });
+        Ok(())
+    }
+
+    /// Register a new connection for `client_id`, closing the previous one if any ([MQTT 3.1]).
+    ///
+    /// Returns the connection number, and the client id, generated if `client_id` is empty.
+    ///
+    /// [MQTT 3.1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718028
+    fn register(
+        &self,
+        client_id: String,
+        messages: mpsc::UnboundedSender<Message>,
+    ) -> (u64, String) {
+        let mut state = self.state.lock().unwrap();
+        let conn = state.next_conn;
+        state.next_conn += 1;
+        let client_id = match client_id.is_empty() {
+            true => format!("mqttrs-{}", conn),
+            false => client_id,
+        };
+        if let Some(old) = state.clients.insert(client_id.clone(), Client { conn, messages }) {
+            let _ = old.messages.send(Message::Close);
+        }
+        // Sessions aren't persisted, drop the subscriptions of the previous connection.
+        state.subscriptions.remove_all(&client_id);
+        (conn, client_id)
+    }
+
+    /// Forget a closed connection, unless another one took over its client id.
+    fn unregister(&self, client_id: &str, conn: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.clients.get(client_id).map(|c| c.conn) == Some(conn) {
+            state.clients.remove(client_id);
+            state.subscriptions.remove_all(&client_id.into());
+        }
+    }
+
+    /// Subscribe `client_id`, returning the `Suback` return codes and the matching retained
+    /// messages.
+    fn subscribe(
+        &self,
+        subscribe: &Subscribe,
+        client_id: &str,
+    ) -> (Vec<SubscribeReturnCodes>, Vec<OwnedPublish>) {
+        let mut state = self.state.lock().unwrap();
+        let return_codes = state.subscriptions.subscribe(subscribe, client_id.into());
+        let mut retained = Vec::new();
+        for (topic, code) in subscribe.topics.iter().zip(return_codes.iter()) {
+            if let (SubscribeReturnCodes::Success(_), Ok(messages)) =
+                (code, state.retained.retained(topic))
+            {
+                retained.extend(messages);
+            }
+        }
+        (return_codes, retained)
+    }
+
+    fn unsubscribe(&self, unsubscribe: &Unsubscribe, client_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.subscriptions.unsubscribe(unsubscribe, &client_id.into());
+    }
+
+    /// Store the message if it is retained, and send it to the matching subscribers.
+    fn route(&self, publish: &OwnedPublish) {
+        let mut state = self.state.lock().unwrap();
+        state.retained.store(&publish.as_borrowed());
+        for (client_id, qos) in state.subscriptions.lookup(&publish.topic_name) {
+            if let Some(client) = state.clients.get(client_id) {
+                // Messages sent to existing subscriptions aren't retained (MQTT-3.3.1-9).
+                let publish = OwnedPublish {
+                    dup: false,
+                    qospid: qospid(min_qos(qos, publish.qospid.qos())),
+                    retain: false,
+                    topic_name: publish.topic_name.clone(),
+                    payload: publish.payload.clone(),
+                };
+                let _ = client.messages.send(Message::Publish(publish));
+            }
+        }
+    }
+}
+
+impl Default for Broker {
+    fn default() -> Self {
+        Broker::new()
+    }
+}
+
+impl fmt::Debug for Broker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Broker").finish_non_exhaustive()
+    }
+}
+
+/// A placeholder `QosPid` for `qos`, whose pid is set when the message is sent.
+fn qospid(qos: QoS) -> QosPid {
+    match qos {
+        QoS::AtMostOnce => QosPid::AtMostOnce,
+        QoS::AtLeastOnce => QosPid::AtLeastOnce(Pid::new()),
+        QoS::ExactlyOnce => QosPid::ExactlyOnce(Pid::new()),
+    }
+}
+
+fn min_qos(a: QoS, b: QoS) -> QoS {
+    if a.to_u8() < b.to_u8() {
+        a
+    } else {
+        b
+    }
+}
+
+/// Task serving one client connection.
+struct Connection<S> {
+    broker: Broker,
+    reader: ReadHalf<S>,
+    writer: WriteHalf<S>,
+    codec: MqttCodec,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    conn: u64,
+    client_id: String,
+    messages: mpsc::UnboundedReceiver<Message>,
+    keep_alive: Duration,
+    last_received: Instant,
+    /// Discarded when the client disconnects cleanly.
+    last_will: Option<OwnedLastWill>,
+    /// Pids of outgoing messages waiting for `Puback`, `Pubrec` or `Pubcomp`.
+    outgoing: HashSet<Pid>,
+    /// Messages waiting for room in `outgoing`, in order.
+    queued: VecDeque<OwnedPublish>,
+    /// Pids of incoming `QoS::ExactlyOnce` messages, between `Pubrec` and `Pubrel`.
+    incoming: HashSet<Pid>,
+    next_pid: Pid,
+}
+
+impl<S: AsyncRead + AsyncWrite> Connection<S> {
+    /// Process packets and messages until the client disconnects (`Ok`) or the connection is
+    /// lost (`Err`).
+    async fn run(&mut self) -> Result<(), Error> {
+        loop {
+            if let Some(packet) = self.codec.decode(&mut self.read_buf)? {
+                self.last_received = Instant::now();
+                if packet == OwnedPacket::Disconnect {
+                    self.last_will = None;
+                    return Ok(());
+                }
+                self.handle(packet).await?;
+                continue;
+            }
+            let keep_alive = self.keep_alive > Duration::from_secs(0);
+            let deadline = self.last_received + self.keep_alive * 3 / 2;
+            tokio::select! {
+                read = self.reader.read_buf(&mut self.read_buf) => {
+                    if read? == 0 {
+                        return Err(Error::UnexpectedEof);
+                    }
+                }
+                message = self.messages.recv() => match message {
+                    Some(Message::Publish(publish)) => self.deliver(publish).await?,
+                    Some(Message::Close) | None => {
+                        let msg = "client id taken over by another connection";
+                        return Err(Error::IoError(ErrorKind::ConnectionAborted, msg.into()));
+                    }
+                },
+                _ = sleep_until(deadline), if keep_alive => {
+                    return Err(Error::IoError(ErrorKind::TimedOut, "keep-alive timeout".into()));
+                }
+            }
+        }
+    }
+
+    async fn handle(&mut self, packet: OwnedPacket) -> Result<(), Error> {
+        let typ = packet.get_type();
+        match packet {
+            OwnedPacket::Publish(publish) => {
+                check_topic_name(&publish.topic_name)?;
+                match publish.qospid {
+                    QosPid::AtMostOnce => self.broker.route(&publish),
+                    QosPid::AtLeastOnce(pid) => {
+                        self.broker.route(&publish);
+                        self.send(OwnedPacket::Puback(pid)).await?;
+                    }
+                    QosPid::ExactlyOnce(pid) => {
+                        // Duplicates are acknowledged again, but not routed.
+                        if self.incoming.insert(pid) {
+                            self.broker.route(&publish);
+                        }
+                        self.send(OwnedPacket::Pubrec(pid)).await?;
+                    }
+                }
+            }
+            OwnedPacket::Pubrel(pid) => {
+                self.incoming.remove(&pid);
+                self.send(OwnedPacket::Pubcomp(pid)).await?;
+            }
+            OwnedPacket::Puback(pid) | OwnedPacket::Pubcomp(pid) => {
+                if !self.outgoing.remove(&pid) {
+                    return Err(Error::UnexpectedPacket(typ));
+                }
+                self.send_queued().await?;
+            }
+            OwnedPacket::Pubrec(pid) => {
+                if !self.outgoing.contains(&pid) {
+                    return Err(Error::UnexpectedPacket(typ));
+                }
+                self.send(OwnedPacket::Pubrel(pid)).await?;
+            }
+            OwnedPacket::Subscribe(subscribe) => {
+                let (return_codes, retained) = self.broker.subscribe(&subscribe, &self.client_id);
+                let suback = Suback {
+                    pid: subscribe.pid,
+                    return_codes,
+                };
+                self.send(OwnedPacket::Suback(suback)).await?;
+                for publish in retained {
+                    self.deliver(publish).await?;
+                }
+            }
+            OwnedPacket::Unsubscribe(unsubscribe) => {
+                self.broker.unsubscribe(&unsubscribe, &self.client_id);
+                self.send(OwnedPacket::Unsuback(unsubscribe.pid)).await?;
+            }
+            OwnedPacket::Pingreq => self.send(OwnedPacket::Pingresp).await?,
+            _ => return Err(Error::UnexpectedPacket(typ)),
+        }
+        Ok(())
+    }
+
+    /// Send a message to the client, or queue it if `MAX_IN_FLIGHT` messages are in flight.
+    async fn deliver(&mut self, publish: OwnedPublish) -> Result<(), Error> {
+        self.queued.push_back(publish);
+        self.send_queued().await
+    }
+
+    /// Send queued messages with new pids, in order, until the in-flight window is full.
+    async fn send_queued(&mut self) -> Result<(), Error> {
+        while let Some(mut publish) = self.queued.pop_front() {
+            if publish.qospid != QosPid::AtMostOnce && self.outgoing.len() >= MAX_IN_FLIGHT {
+                self.queued.push_front(publish);
+                break;
+            }
+            publish.qospid = match publish.qospid {
+                QosPid::AtMostOnce => QosPid::AtMostOnce,
+                QosPid::AtLeastOnce(_) => QosPid::AtLeastOnce(self.next_pid()),
+                QosPid::ExactlyOnce(_) => QosPid::ExactlyOnce(self.next_pid()),
+            };
+            if let Some(pid) = publish.qospid.pid() {
+                self.outgoing.insert(pid);
+            }
+            self.send(OwnedPacket::Publish(publish)).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&mut self, packet: OwnedPacket) -> Result<(), Error> {
+        self.write_buf.clear();
+        self.codec.encode(packet, &mut self.write_buf)?;
+        self.writer.write_all(&self.write_buf).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn read_packet(&mut self) -> Result<OwnedPacket, Error> {
+        loop {
+            if let Some(packet) = self.codec.decode(&mut self.read_buf)? {
+                return Ok(packet);
+            }
+            if self.reader.read_buf(&mut self.read_buf).await? == 0 {
+                return Err(Error::UnexpectedEof);
+            }
+        }
+    }
+
+    /// Get a [`Pid`] that isn't used by an outgoing message. There is always one, as there are at
+    /// most `MAX_IN_FLIGHT` outgoing messages.
+    fn next_pid(&mut self) -> Pid {
+        loop {
+            let pid = self.next_pid;
+            self.next_pid = pid + 1;
+            if !self.outgoing.contains(&pid) {
+                return pid;
+            }
+        }
+    }
+}
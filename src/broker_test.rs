@@ -0,0 +1,230 @@
+use crate::{broker::*, client::*, test_util::topic, *};
+use crate::broker::MAX_IN_FLIGHT;
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::DuplexStream,
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_util::codec::Framed;
+
+fn connect_packet<'a>(client_id: &'a str, last_will: Option<LastWill<'a>>) -> Connect<'a> {
+    Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive: 0,
+        client_id,
+        clean_session: true,
+        last_will,
+        username: None,
+        password: None,
+    }
+}
+
+/// Start serving a new in-memory connection.
+fn serve(broker: &Broker) -> (DuplexStream, JoinHandle<Result<(), Error>>) {
+    let (client, server) = tokio::io::duplex(1024);
+    (client, tokio::spawn(broker.clone().serve_connection(server)))
+}
+
+async fn connect(broker: &Broker, client_id: &str) -> (AsyncClient, Publishes) {
+    let (stream, _) = serve(broker);
+    AsyncClient::connect(stream, connect_packet(client_id, None)).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_pubsub() {
+    let broker = Broker::new();
+    let (sub, mut publishes) = connect(&broker, "sub").await;
+    let (publisher, _) = connect(&broker, "pub").await;
+
+    let topics = vec![topic("a/+", QoS::AtLeastOnce), topic("c/#", QoS::AtMostOnce)];
+    let codes = sub.subscribe(topics).await.unwrap().return_codes;
+    let expected = [
+        SubscribeReturnCodes::Success(QoS::AtLeastOnce),
+        SubscribeReturnCodes::Success(QoS::AtMostOnce),
+    ];
+    assert_eq!(&expected[..], &codes[..]);
+
+    // Messages are delivered with the lowest QoS of the publication and the subscription.
+    publisher.publish("a/0", b"0", QoS::AtMostOnce, false).await.unwrap();
+    publisher.publish("a/b/c", b"nope", QoS::AtMostOnce, false).await.unwrap();
+    publisher.publish("a/1", b"1", QoS::AtLeastOnce, false).await.unwrap();
+    publisher.publish("a/2", b"2", QoS::ExactlyOnce, false).await.unwrap();
+    broker.publish("a/3", b"3", QoS::AtLeastOnce, true).unwrap();
+    let received: Vec<_> = publishes.by_ref().take(4).collect().await;
+    let received: Vec<_> = received
+        .iter()
+        .map(|p| (p.topic_name.as_str(), &p.payload[..], p.qospid.qos(), p.retain))
+        .collect();
+    assert_eq!(
+        vec![
+            ("a/0", &b"0"[..], QoS::AtMostOnce, false),
+            ("a/1", &b"1"[..], QoS::AtLeastOnce, false),
+            ("a/2", &b"2"[..], QoS::AtLeastOnce, false),
+            ("a/3", &b"3"[..], QoS::AtLeastOnce, false),
+        ],
+        received
+    );
+
+    sub.unsubscribe(vec!["a/+".into()]).await.unwrap();
+    publisher.publish("a/4", b"4", QoS::AtLeastOnce, false).await.unwrap();
+    sub.subscribe(vec![topic("b", QoS::ExactlyOnce)]).await.unwrap();
+    publisher.publish("b", b"5", QoS::ExactlyOnce, false).await.unwrap();
+    let publish = publishes.recv().await.unwrap();
+    assert_eq!(("b", QoS::ExactlyOnce), (&publish.topic_name[..], publish.qospid.qos()));
+
+    assert_eq!(Err(Error::InvalidTopicName), broker.publish("a/+", b"", QoS::AtMostOnce, false));
+}
+
+#[tokio::test]
+async fn test_retained() {
+    let broker = Broker::new();
+    let (publisher, _) = connect(&broker, "pub").await;
+    publisher.publish("a/1", b"1", QoS::AtLeastOnce, true).await.unwrap();
+    publisher.publish("a/2", b"2", QoS::AtMostOnce, true).await.unwrap();
+    publisher.publish("a/3", b"3", QoS::AtLeastOnce, true).await.unwrap();
+    // An empty payload deletes the retained message.
+    publisher.publish("a/3", b"", QoS::AtLeastOnce, true).await.unwrap();
+
+    let (sub, mut publishes) = connect(&broker, "sub").await;
+    sub.subscribe(vec![topic("a/+", QoS::ExactlyOnce)]).await.unwrap();
+    let mut received: Vec<_> = publishes.by_ref().take(2).collect().await;
+    received.sort_by(|a, b| a.topic_name.cmp(&b.topic_name));
+    let received: Vec<_> = received
+        .iter()
+        .map(|p| (p.topic_name.as_str(), p.qospid.qos(), p.retain))
+        .collect();
+    assert_eq!(
+        vec![("a/1", QoS::AtLeastOnce, true), ("a/2", QoS::AtMostOnce, true)],
+        received
+    );
+
+    publisher.publish("a/1", b"new", QoS::AtMostOnce, true).await.unwrap();
+    let publish = publishes.recv().await.unwrap();
+    assert_eq!((&b"new"[..], false), (&publish.payload[..], publish.retain));
+}
+
+#[tokio::test]
+async fn test_retained_store() {
+    let mut store = MemoryRetainedStore::new();
+    let publish = Publish {
+        dup: false,
+        qospid: QosPid::AtMostOnce,
+        retain: true,
+        topic_name: "a",
+        payload: b"stored",
+    };
+    store.store(&publish);
+    let broker = Broker::with_retained_store(store);
+
+    let (sub, mut publishes) = connect(&broker, "sub").await;
+    sub.subscribe(vec![topic("#", QoS::AtMostOnce)]).await.unwrap();
+    let publish = publishes.recv().await.unwrap();
+    assert_eq!((&b"stored"[..], true), (&publish.payload[..], publish.retain));
+}
+
+#[tokio::test]
+async fn test_last_will() {
+    let broker = Broker::new();
+    let (sub, mut publishes) = connect(&broker, "sub").await;
+    sub.subscribe(vec![topic("will/#", QoS::AtLeastOnce)]).await.unwrap();
+
+    let will = |topic| LastWill {
+        topic,
+        message: b"gone",
+        qos: QoS::AtLeastOnce,
+        retain: false,
+    };
+    // The Last Will of a lost connection is published.
+    let (stream, served) = serve(&broker);
+    let mut client = Framed::new(stream, MqttCodec::default());
+    let packet = Packet::Connect(connect_packet("lost", Some(will("will/lost"))));
+    client.send(packet).await.unwrap();
+    assert!(matches!(client.next().await, Some(Ok(OwnedPacket::Connack(_)))));
+    drop(client);
+    assert_eq!(Err(Error::UnexpectedEof), served.await.unwrap());
+    assert_eq!("will/lost", publishes.recv().await.unwrap().topic_name);
+
+    // But not after a Disconnect.
+    let (stream, served) = serve(&broker);
+    let packet = connect_packet("clean", Some(will("will/clean")));
+    let (client, _) = AsyncClient::connect(stream, packet).await.unwrap();
+    client.disconnect().await.unwrap();
+    assert_eq!(Ok(()), served.await.unwrap());
+    broker.publish("will/check", b"", QoS::AtMostOnce, false).unwrap();
+    assert_eq!("will/check", publishes.recv().await.unwrap().topic_name);
+}
+
+#[tokio::test]
+async fn test_connect() {
+    let broker = Broker::new();
+
+    // A client id can't be empty without a clean session.
+    let (stream, served) = serve(&broker);
+    let packet = Connect {
+        clean_session: false,
+        ..connect_packet("", None)
+    };
+    let res = AsyncClient::connect(stream, packet).await.map(|_| ());
+    let refused = Err(Error::ConnectionRefused(ConnectReturnCode::RefusedIdentifierRejected));
+    assert_eq!(refused, res);
+    assert_eq!(refused, served.await.unwrap());
+    connect(&broker, "").await;
+
+    // A second connection with the same client id closes the first one.
+    let (_first, mut first_publishes) = connect(&broker, "id").await;
+    let (second, mut second_publishes) = connect(&broker, "id").await;
+    assert_eq!(None, first_publishes.recv().await);
+    second.subscribe(vec![topic("a", QoS::AtMostOnce)]).await.unwrap();
+    broker.publish("a", b"", QoS::AtMostOnce, false).unwrap();
+    assert!(second_publishes.recv().await.is_some());
+}
+
+#[tokio::test]
+async fn test_serve() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { Broker::new().serve(listener).await });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (client, mut publishes) =
+        AsyncClient::connect(stream, connect_packet("tcp", None)).await.unwrap();
+    client.subscribe(vec![topic("#", QoS::ExactlyOnce)]).await.unwrap();
+    client.publish("a/b", b"hello", QoS::ExactlyOnce, false).await.unwrap();
+    let publish = publishes.recv().await.unwrap();
+    assert_eq!(("a/b", &b"hello"[..]), (&publish.topic_name[..], &publish.payload[..]));
+}
+
+#[tokio::test]
+async fn test_in_flight_window() {
+    let broker = Broker::new();
+    let (stream, _) = serve(&broker);
+    let mut client = Framed::new(stream, MqttCodec::default());
+    client.send(Packet::Connect(connect_packet("sub", None))).await.unwrap();
+    assert!(matches!(client.next().await, Some(Ok(OwnedPacket::Connack(_)))));
+    let subscribe = Subscribe::new(Pid::new(), vec![topic("a", QoS::AtLeastOnce)]);
+    client.send(Packet::Subscribe(subscribe)).await.unwrap();
+    assert!(matches!(client.next().await, Some(Ok(OwnedPacket::Suback(_)))));
+
+    for i in 0..=MAX_IN_FLIGHT {
+        broker.publish("a", i.to_string().as_bytes(), QoS::AtLeastOnce, false).unwrap();
+    }
+    let mut pids = Vec::new();
+    for _ in 0..MAX_IN_FLIGHT {
+        match client.next().await {
+            Some(Ok(OwnedPacket::Publish(publish))) => pids.push(publish.qospid.pid().unwrap()),
+            packet => panic!("unexpected {:?}", packet),
+        }
+    }
+    // The last message waits for an acknowledgement.
+    client.send(Packet::Pingreq).await.unwrap();
+    assert_eq!(Some(Ok(OwnedPacket::Pingresp)), client.next().await);
+    client.send(Packet::Puback(pids[0])).await.unwrap();
+    match client.next().await {
+        Some(Ok(OwnedPacket::Publish(publish))) => {
+            assert_eq!(MAX_IN_FLIGHT.to_string().as_bytes(), &publish.payload[..]);
+            assert!(!pids[1..].contains(&publish.qospid.pid().unwrap()));
+        }
+        packet => panic!("unexpected {:?}", packet),
+    }
+}
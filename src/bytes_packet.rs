@@ -0,0 +1,199 @@
+use crate::{decoder::read_frame_len, *};
+use bytes::{Bytes, BytesMut};
+
+/// Version of [Packet] whose strings and binary data are [Bytes] handles into the receive buffer.
+///
+/// Returned by [decode_bytes()]. Cloning a `Bytes` only bumps a reference count, so a decoded
+/// `Publish` can be handed to many consumers without copying its payload.
+///
+/// [Packet]: type.Packet.html
+/// [Bytes]: https://docs.rs/bytes/1.0.0/bytes/struct.Bytes.html
+/// [decode_bytes()]: fn.decode_bytes.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum BytesPacket {
+    Connect(BytesConnect),
+    Connack(Connack),
+    Publish(BytesPublish),
+    Puback(Pid),
+    Pubrec(Pid),
+    Pubrel(Pid),
+    Pubcomp(Pid),
+    Subscribe(Subscribe),
+    Suback(Suback),
+    Unsubscribe(Unsubscribe),
+    Unsuback(Pid),
+    Pingreq,
+    Pingresp,
+    Disconnect,
+}
+
+/// [Bytes] version of [Connect].
+///
+/// [Bytes]: https://docs.rs/bytes/1.0.0/bytes/struct.Bytes.html
+/// [Connect]: struct.Connect.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytesConnect {
+    pub protocol: Protocol,
+    pub keep_alive: u16,
+    pub client_id: Bytes,
+    pub clean_session: bool,
+    pub last_will: Option<BytesLastWill>,
+    pub username: Option<Bytes>,
+    pub password: Option<Bytes>,
+}
+
+/// [Bytes] version of [LastWill].
+///
+/// [Bytes]: https://docs.rs/bytes/1.0.0/bytes/struct.Bytes.html
+/// [LastWill]: struct.LastWill.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytesLastWill {
+    pub topic: Bytes,
+    pub message: Bytes,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// [Bytes] version of [Publish].
+///
+/// [Bytes]: https://docs.rs/bytes/1.0.0/bytes/struct.Bytes.html
+/// [Publish]: struct.Publish.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytesPublish {
+    pub dup: bool,
+    pub qospid: QosPid,
+    pub retain: bool,
+    /// UTF-8 topic name, see [topic_str()](#method.topic_str).
+    pub topic_name: Bytes,
+    pub payload: Bytes,
+}
+
+impl BytesPacket {
+    /// Return the packet type variant.
+    pub fn get_type(&self) -> PacketType {
+        match self {
+            BytesPacket::Connect(_) => PacketType::Connect,
+            BytesPacket::Connack(_) => PacketType::Connack,
+            BytesPacket::Publish(_) => PacketType::Publish,
+            BytesPacket::Puback(_) => PacketType::Puback,
+            BytesPacket::Pubrec(_) => PacketType::Pubrec,
+            BytesPacket::Pubrel(_) => PacketType::Pubrel,
+            BytesPacket::Pubcomp(_) => PacketType::Pubcomp,
+            BytesPacket::Subscribe(_) => PacketType::Subscribe,
+            BytesPacket::Suback(_) => PacketType::Suback,
+            BytesPacket::Unsubscribe(_) => PacketType::Unsubscribe,
+            BytesPacket::Unsuback(_) => PacketType::Unsuback,
+            BytesPacket::Pingreq => PacketType::Pingreq,
+            BytesPacket::Pingresp => PacketType::Pingresp,
+            BytesPacket::Disconnect => PacketType::Disconnect,
+        }
+    }
+
+    fn from_frame(packet: Packet<'_>, frame: &Bytes) -> Self {
+        match packet {
+            Packet::Connect(c) => BytesPacket::Connect(BytesConnect {
+                protocol: c.protocol,
+                keep_alive: c.keep_alive,
+                client_id: frame.slice_ref(c.client_id.as_bytes()),
+                clean_session: c.clean_session,
+                last_will: c.last_will.map(|w| BytesLastWill {
+                    topic: frame.slice_ref(w.topic.as_bytes()),
+                    message: frame.slice_ref(w.message),
+                    qos: w.qos,
+                    retain: w.retain,
+                }),
+                username: c.username.map(|u| frame.slice_ref(u.as_bytes())),
+                password: c.password.map(|p| frame.slice_ref(p)),
+            }),
+            Packet::Connack(c) => BytesPacket::Connack(c),
+            Packet::Publish(p) => BytesPacket::Publish(BytesPublish {
+                dup: p.dup,
+                qospid: p.qospid,
+                retain: p.retain,
+                topic_name: frame.slice_ref(p.topic_name.as_bytes()),
+                payload: frame.slice_ref(p.payload),
+            }),
+            Packet::Puback(pid) => BytesPacket::Puback(pid),
+            Packet::Pubrec(pid) => BytesPacket::Pubrec(pid),
+            Packet::Pubrel(pid) => BytesPacket::Pubrel(pid),
+            Packet::Pubcomp(pid) => BytesPacket::Pubcomp(pid),
+            Packet::Subscribe(s) => BytesPacket::Subscribe(s),
+            Packet::Suback(s) => BytesPacket::Suback(s),
+            Packet::Unsubscribe(u) => BytesPacket::Unsubscribe(u),
+            Packet::Unsuback(pid) => BytesPacket::Unsuback(pid),
+            Packet::Pingreq => BytesPacket::Pingreq,
+            Packet::Pingresp => BytesPacket::Pingresp,
+            Packet::Disconnect => BytesPacket::Disconnect,
+        }
+    }
+}
+
+impl BytesPublish {
+    /// Return the topic name as a `&str`.
+    ///
+    /// Always succeeds for a decoded packet, unless `topic_name` was modified since.
+    pub fn topic_str(&self) -> Result<&str, Error> {
+        core::str::from_utf8(&self.topic_name).map_err(Error::InvalidString)
+    }
+}
+
+/// Decode the next packet from a [BytesMut] buffer, without copying its data.
+///
+/// On success, exactly the bytes of the decoded packet are split off `buf`, and the returned
+/// [BytesPacket] refers to them. The rest of `buf` can keep receiving data. Nothing is consumed
+/// when the packet is incomplete or invalid.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use bytes::BytesMut;
+/// let mut buf = BytesMut::from(&[0b00110000, 10, 0, 3, b'a', b'/', b'b',
+///                                b'h', b'e', b'l', b'l', b'o', 0b11000000][..]);
+/// match decode_bytes(&mut buf) {
+///     Ok(Some(BytesPacket::Publish(p))) => {
+///         assert_eq!(Ok("a/b"), p.topic_str());
+///         assert_eq!(&b"hello"[..], p.payload);
+///     }
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// // The incomplete Pingreq stays in the buffer.
+/// assert_eq!(Ok(None), decode_bytes(&mut buf));
+/// assert_eq!(1, buf.len());
+/// ```
+///
+/// [BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
+/// [BytesPacket]: enum.BytesPacket.html
+pub fn decode_bytes(buf: &mut BytesMut) -> Result<Option<BytesPacket>, Error> {
+    decode_bytes_with_options(buf, &DecodeOptions::default())
+}
+
+/// Decode the next packet from a [BytesMut] buffer like [decode_bytes()], with the given
+/// [DecodeOptions].
+///
+/// [BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
+/// [decode_bytes()]: fn.decode_bytes.html
+/// [DecodeOptions]: struct.DecodeOptions.html
+pub fn decode_bytes_with_options(
+    buf: &mut BytesMut,
+    opts: &DecodeOptions,
+) -> Result<Option<BytesPacket>, Error> {
+    let len = match read_frame_len(buf)? {
+        Some((header_len, remaining_len)) => header_len + remaining_len,
+        None => return Ok(None),
+    };
+    opts.check_packet_size(len)?;
+    if buf.len() < len {
+        return Ok(None);
+    }
+    let frame = buf.split_to(len).freeze();
+    match decode_slice_with_options(&frame, opts) {
+        Ok(Some((_, packet))) => Ok(Some(BytesPacket::from_frame(packet, &frame))),
+        Ok(None) => Err(Error::InvalidLength),
+        Err(e) => {
+            // Put the invalid packet back. `frame` isn't shared, so this doesn't copy it.
+            let mut frame = BytesMut::from(frame);
+            frame.unsplit(core::mem::take(buf));
+            *buf = frame;
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::*;
+use bytes::{Bytes, BytesMut};
+use core::convert::TryFrom;
+
+#[test]
+fn test_decode_bytes() {
+    let connect = Packet::from(Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive: 30,
+        client_id: "test",
+        clean_session: true,
+        last_will: Some(LastWill {
+            topic: "will",
+            message: b"bye",
+            qos: QoS::AtMostOnce,
+            retain: false,
+        }),
+        username: None,
+        password: Some(b"pass"),
+    });
+    let publish = Packet::from(Publish {
+        dup: false,
+        qospid: QosPid::AtLeastOnce(Pid::try_from(3).unwrap()),
+        retain: false,
+        topic_name: "a/b",
+        payload: b"hello",
+    });
+    let mut slice = [0u8; 128];
+    let mut len = encode_slice(&connect, &mut slice).unwrap();
+    len += encode_slice(&publish, &mut slice[len..]).unwrap();
+    len += encode_slice(&Packet::Puback(Pid::new()), &mut slice[len..]).unwrap();
+
+    let mut buf = BytesMut::from(&slice[..len - 1]);
+    match decode_bytes(&mut buf) {
+        Ok(Some(BytesPacket::Connect(c))) => {
+            assert_eq!(Bytes::from_static(b"test"), c.client_id);
+            let will = c.last_will.unwrap();
+            assert_eq!(Bytes::from_static(b"will"), will.topic);
+            assert_eq!(Bytes::from_static(b"bye"), will.message);
+            assert_eq!(None, c.username);
+            assert_eq!(Some(Bytes::from_static(b"pass")), c.password);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    let remaining = buf.len();
+    let p = match decode_bytes(&mut buf) {
+        Ok(Some(BytesPacket::Publish(p))) => p,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(remaining - 14, buf.len());
+    assert_eq!(Ok("a/b"), p.topic_str());
+    assert_eq!(Bytes::from_static(b"hello"), p.payload);
+
+    // Incomplete packets aren't consumed.
+    assert_eq!(Ok(None), decode_bytes(&mut buf));
+    assert_eq!(3, buf.len());
+    buf.extend_from_slice(&slice[len - 1..len]);
+    assert_eq!(Ok(Some(BytesPacket::Puback(Pid::new()))), decode_bytes(&mut buf));
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_decode_bytes_invalid() {
+    let mut buf = BytesMut::from(&[0b01100000, 2, 0, 1][..]);
+    assert_eq!(Err(Error::InvalidHeader), decode_bytes(&mut buf));
+    assert_eq!(4, buf.len());
+}
+
+#[test]
+fn test_decode_bytes_with_options() {
+    // A Publish with a wildcard topic, followed by a Pingreq.
+    let data = [0b00110000, 5, 0, 3, b'a', b'/', b'+', 0b11000000, 0];
+    let mut buf = BytesMut::from(&data[..]);
+    let strict = DecodeOptions::strict();
+    assert_eq!(Err(Error::InvalidTopicName), decode_bytes_with_options(&mut buf, &strict));
+    assert_eq!(&data[..], &buf[..]);
+    assert!(matches!(decode_bytes(&mut buf), Ok(Some(BytesPacket::Publish(_)))));
+    assert_eq!(Ok(Some(BytesPacket::Pingreq)), decode_bytes_with_options(&mut buf, &strict));
+
+    // Too big packets are rejected before they are complete.
+    let opts = DecodeOptions {
+        max_packet_size: Some(4),
+        ..DecodeOptions::default()
+    };
+    let mut buf = BytesMut::from(&data[..2]);
+    assert_eq!(Err(Error::PacketTooLarge), decode_bytes_with_options(&mut buf, &opts));
+}
@@ -0,0 +1,485 @@
+//! Async MQTT 3.1.1 client for [tokio], built on [MqttCodec].
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), mqttrs::Error> {
+//! use mqttrs::{client::AsyncClient, *};
+//! use tokio::net::TcpStream;
+//!
+//! let stream = TcpStream::connect("127.0.0.1:1883").await?;
+//! let connect = Connect { protocol: Protocol::MQTT311,
+//!                         keep_alive: 30,
+//!                         client_id: "doc_client",
+//!                         clean_session: true,
+//!                         last_will: None,
+//!                         username: None,
+//!                         password: None };
+//! let (client, mut publishes) = AsyncClient::connect(stream, connect).await?;
+//! let topic = SubscribeTopic { topic_path: "sensors/#".into(), qos: QoS::AtLeastOnce };
+//! client.subscribe(vec![topic]).await?;
+//! client.publish("sensors/1", b"21.5", QoS::ExactlyOnce, false).await?;
+//! while let Some(publish) = publishes.recv().await {
+//!     println!("{}: {:?}", publish.topic_name, publish.payload);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [tokio]: https://tokio.rs/
+//! [MqttCodec]: ../struct.MqttCodec.html
+
+use crate::*;
+use bytes::BytesMut;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures_core::Stream;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::ErrorKind,
+    string::String,
+    vec::Vec,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf},
+    sync::{mpsc, oneshot},
+    time::{sleep_until, Instant},
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Maximum number of requests waiting for an acknowledgement, which also keeps free pids
+/// available.
+pub(crate) const MAX_IN_FLIGHT: usize = 64;
+
+/// Handle to an MQTT connection, whose packets are sent and received by a background task.
+///
+/// The task answers incoming messages with `Puback`, or `Pubrec`/`Pubcomp`, and sends a `Pingreq`
+/// when nothing has been sent for [`Connect::keep_alive`] seconds. The connection is closed if the
+/// server doesn't answer it in time.
+///
+/// Requests resolve once the server acknowledged them. They return
+/// `Error::IoError(ErrorKind::NotConnected, _)` when the connection was closed, or the error that
+/// closed it. `AsyncClient` can be cloned to make requests from several tasks. At most 64 requests
+/// are waiting for an acknowledgement, further requests are sent in order as they complete.
+///
+/// [`Connect::keep_alive`]: ../struct.Connect.html#structfield.keep_alive
+#[derive(Debug, Clone)]
+pub struct AsyncClient {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+/// Stream of the messages received from the server, returned by [AsyncClient::connect()].
+///
+/// It ends when the connection is closed. Messages are buffered until they are read, so that the
+/// connection keeps serving requests and acknowledgements while the application is busy.
+///
+/// [AsyncClient::connect()]: struct.AsyncClient.html#method.connect
+#[derive(Debug)]
+pub struct Publishes {
+    rx: mpsc::UnboundedReceiver<OwnedPublish>,
+}
+
+type Reply<T> = oneshot::Sender<Result<T, Error>>;
+
+#[derive(Debug)]
+enum Command {
+    Publish(OwnedPublish, Reply<()>),
+    Subscribe(Vec<SubscribeTopic>, Reply<Suback>),
+    Unsubscribe(Vec<String>, Reply<()>),
+    Disconnect(Reply<()>),
+}
+
+/// Request waiting for an acknowledgement from the server.
+#[derive(Debug)]
+enum Pending {
+    /// Waiting for `Puback` (QoS 1) or `Pubrec` (QoS 2).
+    Publish(QoS, Reply<()>),
+    /// Waiting for `Pubcomp`.
+    Pubrel(Reply<()>),
+    Subscribe(Reply<Suback>),
+    Unsubscribe(Reply<()>),
+}
+
+impl AsyncClient {
+    /// Send `connect` over `stream`, and wait for the server to accept the connection.
+    ///
+    /// Returns `Error::ConnectionRefused` if the server refuses it. On success, the connection is
+    /// handed to a background task spawned on the current tokio runtime.
+    pub async fn connect<S>(stream: S, connect: Connect<'_>) -> Result<(Self, Publishes), Error>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let keep_alive = Duration::from_secs(connect.keep_alive.into());
+        let (publishes_tx, publishes_rx) = mpsc::unbounded_channel();
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        let mut task = Task {
+            reader,
+            writer,
+            codec: MqttCodec::default(),
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            commands: commands_rx,
+            publishes: publishes_tx,
+            pending: HashMap::new(),
+            queued: VecDeque::new(),
+            incoming: HashSet::new(),
+            next_pid: Pid::new(),
+            keep_alive,
+            last_sent: Instant::now(),
+            ping_sent: None,
+        };
+
+        task.send(OwnedPacket::Connect(connect.into())).await?;
+        match task.read_packet().await? {
+            OwnedPacket::Connack(Connack { code: ConnectReturnCode::Accepted, .. }) => (),
+            OwnedPacket::Connack(connack) => return Err(Error::ConnectionRefused(connack.code)),
+            packet => return Err(Error::UnexpectedPacket(packet.get_type())),
+        }
+        tokio::spawn(task.run());
+
+        let client = AsyncClient {
+            commands: commands_tx,
+        };
+        Ok((client, Publishes { rx: publishes_rx }))
+    }
+
+    /// Publish a message, waiting for its `Puback` (`QoS::AtLeastOnce`) or `Pubcomp`
+    /// (`QoS::ExactlyOnce`), or until it was sent (`QoS::AtMostOnce`).
+    pub async fn publish(
+        &self,
+        topic_name: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<(), Error> {
+        // The task sets the pid.
+        let qospid = match qos {
+            QoS::AtMostOnce => QosPid::AtMostOnce,
+            QoS::AtLeastOnce => QosPid::AtLeastOnce(Pid::new()),
+            QoS::ExactlyOnce => QosPid::ExactlyOnce(Pid::new()),
+        };
+        let publish = OwnedPublish {
+            dup: false,
+            qospid,
+            retain,
+            topic_name: topic_name.into(),
+            payload: payload.into(),
+        };
+        self.request(|reply| Command::Publish(publish, reply)).await
+    }
+
+    /// Subscribe to `topics`, returning the server's `Suback`.
+    pub async fn subscribe(&self, topics: Vec<SubscribeTopic>) -> Result<Suback, Error> {
+        self.request(|reply| Command::Subscribe(topics, reply)).await
+    }
+
+    /// Unsubscribe from `topics`, waiting for the server's `Unsuback`.
+    pub async fn unsubscribe(&self, topics: Vec<String>) -> Result<(), Error> {
+        self.request(|reply| Command::Unsubscribe(topics, reply)).await
+    }
+
+    /// Send a `Disconnect` packet and close the connection.
+    ///
+    /// Requests that are still waiting for an acknowledgement fail.
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        self.request(Command::Disconnect).await
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> Result<T, Error> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).map_err(|_| not_connected())?;
+        response.await.map_err(|_| not_connected())?
+    }
+}
+
+impl Publishes {
+    /// Receive the next message, or `None` once the connection is closed.
+    pub async fn recv(&mut self) -> Option<OwnedPublish> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for Publishes {
+    type Item = OwnedPublish;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<OwnedPublish>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+fn not_connected() -> Error {
+    Error::IoError(ErrorKind::NotConnected, "connection closed".into())
+}
+
+/// Background task owning the connection.
+struct Task<S> {
+    reader: ReadHalf<S>,
+    writer: WriteHalf<S>,
+    codec: MqttCodec,
+    read_buf: BytesMut,
+    write_buf: BytesMut,
+    commands: mpsc::UnboundedReceiver<Command>,
+    publishes: mpsc::UnboundedSender<OwnedPublish>,
+    pending: HashMap<Pid, Pending>,
+    /// Commands waiting for room in `pending`, in order.
+    queued: VecDeque<Command>,
+    /// Pids of incoming `QoS::ExactlyOnce` messages, between `Pubrec` and `Pubrel`.
+    incoming: HashSet<Pid>,
+    next_pid: Pid,
+    keep_alive: Duration,
+    last_sent: Instant,
+    ping_sent: Option<Instant>,
+}
+
+impl<S: AsyncRead + AsyncWrite> Task<S> {
+    async fn run(mut self) {
+        let err = match self.serve().await {
+            Ok(()) => not_connected(),
+            Err(e) => e,
+        };
+        for (_, pending) in self.pending.drain() {
+            pending.fail(err.clone());
+        }
+        for command in self.queued.drain(..) {
+            command.fail(err.clone());
+        }
+    }
+
+    /// Process packets and commands until the connection is closed.
+    async fn serve(&mut self) -> Result<(), Error> {
+        loop {
+            if let Some(packet) = self.codec.decode(&mut self.read_buf)? {
+                self.handle(packet).await?;
+                continue;
+            }
+            let keep_alive = self.keep_alive > Duration::from_secs(0);
+            let deadline = self.ping_sent.unwrap_or(self.last_sent) + self.keep_alive;
+            tokio::select! {
+                read = self.reader.read_buf(&mut self.read_buf) => {
+                    if read? == 0 {
+                        if self.read_buf.is_empty() {
+                            return Ok(());
+                        }
+                        return Err(Error::UnexpectedEof);
+                    }
+                }
+                command = self.commands.recv() => match command {
+                    Some(Command::Disconnect(reply)) => {
+                        let _ = reply.send(self.send(OwnedPacket::Disconnect).await);
+                        return Ok(());
+                    }
+                    Some(command) => self.command(command).await?,
+                    // Every `AsyncClient` was dropped.
+                    None => return self.send(OwnedPacket::Disconnect).await,
+                },
+                _ = sleep_until(deadline), if keep_alive => {
+                    if self.ping_sent.is_some() {
+                        return Err(Error::IoError(ErrorKind::TimedOut, "no Pingresp".into()));
+                    }
+                    self.send(OwnedPacket::Pingreq).await?;
+                    self.ping_sent = Some(self.last_sent);
+                }
+            }
+        }
+    }
+
+    /// Send a command, or queue it if `MAX_IN_FLIGHT` requests are pending.
+    async fn command(&mut self, command: Command) -> Result<(), Error> {
+        self.queued.push_back(command);
+        self.send_queued().await
+    }
+
+    /// Send queued commands in order, until the pending requests are full.
+    async fn send_queued(&mut self) -> Result<(), Error> {
+        while let Some(command) = self.queued.pop_front() {
+            let needs_pid = match &command {
+                Command::Publish(publish, _) => publish.qospid != QosPid::AtMostOnce,
+                _ => true,
+            };
+            if needs_pid && self.pending.len() >= MAX_IN_FLIGHT {
+                self.queued.push_front(command);
+                break;
+            }
+            self.send_command(command).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_command(&mut self, command: Command) -> Result<(), Error> {
+        let (packet, pid, pending) = match command {
+            Command::Publish(mut publish, reply) => {
+                let qos = publish.qospid.qos();
+                let pid = match qos {
+                    QoS::AtMostOnce => None,
+                    _ => Some(self.next_pid()),
+                };
+                publish.qospid = match pid {
+                    None => QosPid::AtMostOnce,
+                    Some(pid) if qos == QoS::AtLeastOnce => QosPid::AtLeastOnce(pid),
+                    Some(pid) => QosPid::ExactlyOnce(pid),
+                };
+                (publish.into(), pid, Pending::Publish(qos, reply))
+            }
+            Command::Subscribe(topics, reply) => {
+                let pid = self.next_pid();
+                let subscribe = Subscribe::new(pid, topics);
+                (subscribe.into(), Some(pid), Pending::Subscribe(reply))
+            }
+            Command::Unsubscribe(topics, reply) => {
+                let pid = self.next_pid();
+                let unsubscribe = Unsubscribe::new(pid, topics);
+                (unsubscribe.into(), Some(pid), Pending::Unsubscribe(reply))
+            }
+            Command::Disconnect(_) => unreachable!("handled by serve()"),
+        };
+        match (self.send(packet).await, pid) {
+            (Ok(()), Some(pid)) => {
+                self.pending.insert(pid, pending);
+                Ok(())
+            }
+            // `QoS::AtMostOnce` messages are done once sent.
+            (Ok(()), None) => {
+                pending.succeed();
+                Ok(())
+            }
+            // Only close the connection if the error comes from it, not from the packet.
+            (Err(e @ Error::IoError(..)), _) => Err(e),
+            (Err(e), _) => {
+                pending.fail(e);
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle(&mut self, packet: OwnedPacket) -> Result<(), Error> {
+        let typ = packet.get_type();
+        match packet {
+            OwnedPacket::Publish(publish) => match publish.qospid {
+                QosPid::AtMostOnce => self.deliver(publish),
+                QosPid::AtLeastOnce(pid) => {
+                    self.deliver(publish);
+                    self.send(OwnedPacket::Puback(pid)).await?;
+                }
+                QosPid::ExactlyOnce(pid) => {
+                    // Duplicates are acknowledged again, but not delivered.
+                    if self.incoming.insert(pid) {
+                        self.deliver(publish);
+                    }
+                    self.send(OwnedPacket::Pubrec(pid)).await?;
+                }
+            },
+            OwnedPacket::Pubrel(pid) => {
+                self.incoming.remove(&pid);
+                self.send(OwnedPacket::Pubcomp(pid)).await?;
+            }
+            OwnedPacket::Puback(pid) => match self.pending.remove(&pid) {
+                Some(Pending::Publish(QoS::AtLeastOnce, reply)) => {
+                    let _ = reply.send(Ok(()));
+                }
+                _ => return Err(Error::UnexpectedPacket(typ)),
+            },
+            OwnedPacket::Pubrec(pid) => match self.pending.remove(&pid) {
+                Some(Pending::Publish(QoS::ExactlyOnce, reply)) => {
+                    self.pending.insert(pid, Pending::Pubrel(reply));
+                    self.send(OwnedPacket::Pubrel(pid)).await?;
+                }
+                _ => return Err(Error::UnexpectedPacket(typ)),
+            },
+            OwnedPacket::Pubcomp(pid) => match self.pending.remove(&pid) {
+                Some(Pending::Pubrel(reply)) => {
+                    let _ = reply.send(Ok(()));
+                }
+                _ => return Err(Error::UnexpectedPacket(typ)),
+            },
+            OwnedPacket::Suback(suback) => match self.pending.remove(&suback.pid) {
+                Some(Pending::Subscribe(reply)) => {
+                    let _ = reply.send(Ok(suback));
+                }
+                _ => return Err(Error::UnexpectedPacket(typ)),
+            },
+            OwnedPacket::Unsuback(pid) => match self.pending.remove(&pid) {
+                Some(Pending::Unsubscribe(reply)) => {
+                    let _ = reply.send(Ok(()));
+                }
+                _ => return Err(Error::UnexpectedPacket(typ)),
+            },
+            OwnedPacket::Pingresp => self.ping_sent = None,
+            _ => return Err(Error::UnexpectedPacket(typ)),
+        }
+        // An acknowledgement may have made room for queued commands.
+        self.send_queued().await
+    }
+
+    /// Hand a message to `Publishes`, dropping it if `Publishes` was dropped.
+    fn deliver(&mut self, publish: OwnedPublish) {
+        let _ = self.publishes.send(publish);
+    }
+
+    async fn send(&mut self, packet: OwnedPacket) -> Result<(), Error> {
+        self.write_buf.clear();
+        self.codec.encode(packet, &mut self.write_buf)?;
+        self.writer.write_all(&self.write_buf).await?;
+        self.writer.flush().await?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    async fn read_packet(&mut self) -> Result<OwnedPacket, Error> {
+        loop {
+            if let Some(packet) = self.codec.decode(&mut self.read_buf)? {
+                return Ok(packet);
+            }
+            if self.reader.read_buf(&mut self.read_buf).await? == 0 {
+                return Err(not_connected());
+            }
+        }
+    }
+
+    /// Get a [`Pid`] that isn't used by a pending request. There is always one, as there are at
+    /// most `MAX_IN_FLIGHT` pending requests.
+    fn next_pid(&mut self) -> Pid {
+        loop {
+            let pid = self.next_pid;
+            self.next_pid = pid + 1;
+            if !self.pending.contains_key(&pid) {
+                return pid;
+            }
+        }
+    }
+}
+
+impl Command {
+    fn fail(self, err: Error) {
+        match self {
+            Command::Publish(_, reply)
+            | Command::Unsubscribe(_, reply)
+            | Command::Disconnect(reply) => {
+                let _ = reply.send(Err(err));
+            }
+            Command::Subscribe(_, reply) => {
+                let _ = reply.send(Err(err));
+            }
+        }
+    }
+}
+
+impl Pending {
+    fn succeed(self) {
+        if let Pending::Publish(_, reply) = self {
+            let _ = reply.send(Ok(()));
+        }
+    }
+
+    fn fail(self, err: Error) {
+        match self {
+            Pending::Publish(_, reply) | Pending::Pubrel(reply) | Pending::Unsubscribe(reply) => {
+                let _ = reply.send(Err(err));
+            }
+            Pending::Subscribe(reply) => {
+                let _ = reply.send(Err(err));
+            }
+        }
+    }
+}
@@ -0,0 +1,347 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use crate::{
+    qos_flows::{QosFlows, Received},
+    subscribe::{LimitedString, LimitedVec},
+    *,
+};
+use core::time::Duration;
+use heapless::Vec;
+
+/// Event returned by [`ClientSession::handle()`].
+///
+/// [`ClientSession::handle()`]: struct.ClientSession.html#method.handle
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientEvent<'p> {
+    /// The server accepted the connection.
+    Connected { session_present: bool },
+    /// A message for the application. Duplicate `QoS::ExactlyOnce` messages are filtered out.
+    Publish(Publish<'p>),
+    /// An outgoing message has been fully acknowledged by the server.
+    Published(Pid),
+    Suback(Suback),
+    Unsuback(Pid),
+}
+
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+/// Client-side session state machine.
+///
+/// `ClientSession` handles the parts of the protocol that every client needs to get right, but
+/// doesn't do any IO itself:
+///
+/// * It allocates a [`Pid`] for each outgoing `QoS::AtLeastOnce` or `QoS::ExactlyOnce` message,
+///   and keeps the message until the `Puback` or `Pubrec`/`Pubrel`/`Pubcomp` handshake completes.
+/// * After a reconnection, it re-sends in-flight messages with the DUP flag set, and in-flight
+///   `Pubrel`s ([MQTT 4.4]).
+/// * It answers incoming messages with `Puback`, or `Pubrec`/`Pubcomp`, and filters out duplicate
+///   `QoS::ExactlyOnce` messages.
+/// * It sends a `Pingreq` when nothing has been sent for [`Connect::keep_alive`] seconds.
+///
+/// Feed it the decoded packets received from the server with [`handle()`], and send the packets
+/// returned by [`poll_transmit()`] until it returns `None`. Times are monotonic durations since an
+/// arbitrary point chosen by the caller.
+///
+/// Storage is fixed-size, using [heapless]: `N` is the maximum number of in-flight outgoing
+/// messages, of incoming `QoS::ExactlyOnce` messages waiting for `Pubrel`, of `Subscribe` and
+/// `Unsubscribe` requests waiting for their acknowledgement, and of queued packets. Messages
+/// borrow their topic and payload for the lifetime `'a`, which must outlive their acknowledgement.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use core::time::Duration;
+/// let mut session = ClientSession::<8>::new();
+/// session.connect(Connect { protocol: Protocol::MQTT311,
+///                           keep_alive: 30,
+///                           client_id: "client",
+///                           clean_session: false,
+///                           last_will: None,
+///                           username: None,
+///                           password: None });
+/// let pid = session.publish("to/pic", b"payload", QoS::AtLeastOnce, false).unwrap().unwrap();
+///
+/// let now = Duration::from_secs(0);
+/// assert!(matches!(session.poll_transmit(now), Some(Packet::Connect(_))));
+/// // Messages are held back until the server accepts the connection.
+/// assert_eq!(None, session.poll_transmit(now));
+///
+/// let connack = Connack { session_present: false, code: ConnectReturnCode::Accepted };
+/// session.handle(Packet::Connack(connack), now).unwrap();
+/// assert!(matches!(session.poll_transmit(now), Some(Packet::Publish(_))));
+///
+/// assert_eq!(Ok(Some(ClientEvent::Published(pid))), session.handle(Packet::Puback(pid), now));
+/// assert_eq!(Some(Packet::Pingreq), session.poll_transmit(Duration::from_secs(30)));
+/// ```
+///
+/// [`Pid`]: struct.Pid.html
+/// [MQTT 4.4]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718103
+/// [`Connect::keep_alive`]: struct.Connect.html#structfield.keep_alive
+/// [`handle()`]: #method.handle
+/// [`poll_transmit()`]: #method.poll_transmit
+/// [heapless]: https://docs.rs/heapless
+#[derive(Debug)]
+pub struct ClientSession<'a, const N: usize> {
+    state: State,
+    connect: Option<Connect<'a>>,
+    keep_alive: Duration,
+    flows: QosFlows<'a, N>,
+    /// Pids and types of the `Subscribe` and `Unsubscribe` packets waiting for their
+    /// acknowledgement.
+    requests: Vec<(Pid, PacketType), N>,
+    last_sent: Duration,
+    ping_sent: Option<Duration>,
+}
+
+impl<'a, const N: usize> ClientSession<'a, N> {
+    pub fn new() -> Self {
+        ClientSession {
+            state: State::Disconnected,
+            connect: None,
+            keep_alive: Duration::from_secs(0),
+            flows: QosFlows::new(),
+            requests: Vec::new(),
+            last_sent: Duration::from_secs(0),
+            ping_sent: None,
+        }
+    }
+
+    /// Start a new connection, the `Connect` packet will be the next one returned by
+    /// [`poll_transmit()`].
+    ///
+    /// If `clean_session` is set, in-flight messages are discarded. Otherwise they will be sent
+    /// again once connected.
+    ///
+    /// [`poll_transmit()`]: #method.poll_transmit
+    pub fn connect(&mut self, connect: Connect<'a>) {
+        self.disconnected();
+        if connect.clean_session {
+            self.flows.clear();
+        }
+        self.keep_alive = Duration::from_secs(connect.keep_alive.into());
+        self.connect = Some(connect);
+        self.state = State::Connecting;
+    }
+
+    /// Notify the session that the connection was lost or closed.
+    ///
+    /// Queued packets and pending subscription requests are dropped, and in-flight messages will
+    /// be sent again after the next [`connect()`].
+    ///
+    /// [`connect()`]: #method.connect
+    pub fn disconnected(&mut self) {
+        self.state = State::Disconnected;
+        self.connect = None;
+        self.flows.disconnected();
+        self.requests.clear();
+        self.ping_sent = None;
+    }
+
+    /// Whether the server has accepted the current connection.
+    pub fn is_connected(&self) -> bool {
+        self.state == State::Connected
+    }
+
+    /// Number of outgoing messages waiting for an acknowledgement.
+    pub fn in_flight(&self) -> usize {
+        self.flows.in_flight()
+    }
+
+    /// Get a [`Pid`] that isn't used by an in-flight message or a pending subscription request.
+    ///
+    /// [`Pid`]: struct.Pid.html
+    pub fn next_pid(&mut self) -> Pid {
+        let requests = &self.requests;
+        self.flows.next_pid(|pid| requests.iter().any(|(p, _)| *p == pid))
+    }
+
+    /// Queue a message, returning its [`Pid`] if `qos` requires an acknowledgement.
+    ///
+    /// `QoS::AtMostOnce` messages are dropped if the session isn't connected, and so are
+    /// [`subscribe()`] requests made before [`connect()`]. Returns `Error::QueueFull` if `N`
+    /// messages are already in flight (or queued, for `QoS::AtMostOnce`).
+    ///
+    /// [`Pid`]: struct.Pid.html
+    /// [`subscribe()`]: #method.subscribe
+    /// [`connect()`]: #method.connect
+    pub fn publish(
+        &mut self,
+        topic_name: &'a str,
+        payload: &'a [u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<Option<Pid>, Error> {
+        // Like other packets, QoS 0 messages are not kept across connections.
+        if qos == QoS::AtMostOnce && self.state != State::Connected {
+            return Ok(None);
+        }
+        let requests = &self.requests;
+        self.flows.publish(topic_name, payload, qos, retain, |pid| {
+            requests.iter().any(|(p, _)| *p == pid)
+        })
+    }
+
+    /// Queue a `Subscribe` packet, returning its [`Pid`].
+    ///
+    /// Like `QoS::AtMostOnce` messages in [`publish()`], requests are dropped with the connection,
+    /// so they need a session that is connecting or connected: call [`connect()`] first. Returns
+    /// `Error::UnexpectedPacket` while disconnected, and `Error::QueueFull` if `N` requests are
+    /// already waiting for their `Suback` or `Unsuback`.
+    ///
+    /// [`Pid`]: struct.Pid.html
+    /// [`publish()`]: #method.publish
+    /// [`connect()`]: #method.connect
+    pub fn subscribe(&mut self, topics: LimitedVec<SubscribeTopic>) -> Result<Pid, Error> {
+        let pid = self.next_pid();
+        self.request(Packet::Subscribe(Subscribe { pid, topics }), pid)?;
+        Ok(pid)
+    }
+
+    /// Queue an `Unsubscribe` packet, returning its [`Pid`].
+    ///
+    /// Returns `Error::UnexpectedPacket` or `Error::QueueFull` like [`subscribe()`].
+    ///
+    /// [`Pid`]: struct.Pid.html
+    /// [`subscribe()`]: #method.subscribe
+    pub fn unsubscribe(&mut self, topics: LimitedVec<LimitedString>) -> Result<Pid, Error> {
+        let pid = self.next_pid();
+        self.request(Packet::Unsubscribe(Unsubscribe { pid, topics }), pid)?;
+        Ok(pid)
+    }
+
+    /// Process a packet received from the server.
+    ///
+    /// Acknowledgements for unknown [`Pid`]s (including a `Suback` or `Unsuback` that doesn't
+    /// answer a pending request) and packets that a server shouldn't send return
+    /// `Error::UnexpectedPacket`. A refused connection returns `Error::ConnectionRefused`.
+    ///
+    /// [`Pid`]: struct.Pid.html
+    pub fn handle<'p>(
+        &mut self,
+        packet: Packet<'p>,
+        now: Duration,
+    ) -> Result<Option<ClientEvent<'p>>, Error> {
+        let typ = packet.get_type();
+        match (self.state, packet) {
+            (State::Connecting, Packet::Connack(connack)) => {
+                if connack.code != ConnectReturnCode::Accepted {
+                    self.disconnected();
+                    return Err(Error::ConnectionRefused(connack.code));
+                }
+                self.state = State::Connected;
+                self.last_sent = now;
+                Ok(Some(ClientEvent::Connected {
+                    session_present: connack.session_present,
+                }))
+            }
+            (State::Connected, packet) => match self.flows.receive(packet)? {
+                Received::Publish(publish) => Ok(Some(ClientEvent::Publish(publish))),
+                Received::Published(pid) => Ok(Some(ClientEvent::Published(pid))),
+                Received::Handled => Ok(None),
+                Received::Other(Packet::Suback(suback)) => {
+                    self.acknowledge(suback.pid, PacketType::Subscribe, typ)?;
+                    Ok(Some(ClientEvent::Suback(suback)))
+                }
+                Received::Other(Packet::Unsuback(pid)) => {
+                    self.acknowledge(pid, PacketType::Unsubscribe, typ)?;
+                    Ok(Some(ClientEvent::Unsuback(pid)))
+                }
+                Received::Other(Packet::Pingresp) => {
+                    self.ping_sent = None;
+                    Ok(None)
+                }
+                Received::Other(_) => Err(Error::UnexpectedPacket(typ)),
+            },
+            _ => Err(Error::UnexpectedPacket(typ)),
+        }
+    }
+
+    /// Get the next packet to send, if any.
+    pub fn poll_transmit(&mut self, now: Duration) -> Option<Packet<'a>> {
+        let packet = self.next_packet(now)?;
+        self.last_sent = now;
+        if packet == Packet::Pingreq {
+            self.ping_sent = Some(now);
+        }
+        Some(packet)
+    }
+
+    /// Time at which [`poll_transmit()`] should be called again to send a `Pingreq`.
+    ///
+    /// Returns `None` if keep-alive is disabled or the session isn't connected.
+    ///
+    /// [`poll_transmit()`]: #method.poll_transmit
+    pub fn poll_timeout(&self) -> Option<Duration> {
+        match (self.state, self.ping_sent) {
+            (State::Connected, None) if self.keep_alive > Duration::from_secs(0) => {
+                Some(self.last_sent + self.keep_alive)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the server failed to answer a `Pingreq` within the keep-alive period.
+    ///
+    /// The caller should then close the connection and call [`disconnected()`].
+    ///
+    /// [`disconnected()`]: #method.disconnected
+    pub fn is_timed_out(&self, now: Duration) -> bool {
+        match self.ping_sent {
+            Some(sent) => now >= sent + self.keep_alive,
+            None => false,
+        }
+    }
+
+    fn next_packet(&mut self, now: Duration) -> Option<Packet<'a>> {
+        match self.state {
+            State::Disconnected => None,
+            State::Connecting => self.connect.take().map(Packet::Connect),
+            State::Connected => {
+                if let Some(packet) = self.flows.poll_transmit() {
+                    return Some(packet);
+                }
+                match self.poll_timeout() {
+                    Some(timeout) if now >= timeout => Some(Packet::Pingreq),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Queue a `Subscribe` or `Unsubscribe` packet, and wait for its acknowledgement.
+    fn request(&mut self, packet: Packet<'a>, pid: Pid) -> Result<(), Error> {
+        let typ = packet.get_type();
+        // The next connect() would drop the request without an answer.
+        if self.state == State::Disconnected {
+            return Err(Error::UnexpectedPacket(typ));
+        }
+        if self.requests.is_full() {
+            return Err(Error::QueueFull);
+        }
+        self.flows.push(packet)?;
+        self.requests.push((pid, typ)).map_err(|_| Error::QueueFull)
+    }
+
+    /// Match a `Suback` or `Unsuback` with a pending request of type `request`.
+    fn acknowledge(&mut self, pid: Pid, request: PacketType, typ: PacketType) -> Result<(), Error> {
+        match self.requests.iter().position(|r| *r == (pid, request)) {
+            Some(i) => {
+                self.requests.swap_remove(i);
+                Ok(())
+            }
+            None => Err(Error::UnexpectedPacket(typ)),
+        }
+    }
+}
+
+impl<'a, const N: usize> Default for ClientSession<'a, N> {
+    fn default() -> Self {
+        ClientSession::new()
+    }
+}
@@ -0,0 +1,283 @@
+use crate::*;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+fn secs(s: u64) -> Duration {
+    Duration::from_secs(s)
+}
+
+fn connect(clean_session: bool) -> Connect<'static> {
+    Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive: 10,
+        client_id: "test",
+        clean_session,
+        last_will: None,
+        username: None,
+        password: None,
+    }
+}
+
+fn connack(session_present: bool) -> Packet<'static> {
+    Packet::Connack(Connack {
+        session_present,
+        code: ConnectReturnCode::Accepted,
+    })
+}
+
+fn connected<'a>(clean_session: bool) -> ClientSession<'a, 4> {
+    let mut session = ClientSession::new();
+    session.connect(connect(clean_session));
+    assert_eq!(
+        Some(Packet::Connect(connect(clean_session))),
+        session.poll_transmit(secs(0))
+    );
+    assert_eq!(
+        Ok(Some(ClientEvent::Connected {
+            session_present: false
+        })),
+        session.handle(connack(false), secs(0))
+    );
+    session
+}
+
+fn publish(dup: bool, qospid: QosPid) -> Packet<'static> {
+    Packet::Publish(Publish {
+        dup,
+        qospid,
+        retain: false,
+        topic_name: "a/b",
+        payload: b"hi",
+    })
+}
+
+#[test]
+fn test_qos1_outgoing() {
+    let mut session = connected(true);
+    let pid = session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap().unwrap();
+    assert_eq!(
+        Some(publish(false, QosPid::AtLeastOnce(pid))),
+        session.poll_transmit(secs(1))
+    );
+    assert_eq!(None, session.poll_transmit(secs(1)));
+    assert_eq!(1, session.in_flight());
+    assert_eq!(
+        Ok(Some(ClientEvent::Published(pid))),
+        session.handle(Packet::Puback(pid), secs(2))
+    );
+    assert_eq!(0, session.in_flight());
+    // A second Puback is unexpected.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Puback)),
+        session.handle(Packet::Puback(pid), secs(2))
+    );
+}
+
+#[test]
+fn test_qos2_outgoing() {
+    let mut session = connected(true);
+    let pid = session.publish("a/b", b"hi", QoS::ExactlyOnce, false).unwrap().unwrap();
+    assert_eq!(
+        Some(publish(false, QosPid::ExactlyOnce(pid))),
+        session.poll_transmit(secs(1))
+    );
+    // Pubcomp before Pubrec is a protocol error.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Pubcomp)),
+        session.handle(Packet::Pubcomp(pid), secs(1))
+    );
+    assert_eq!(Ok(None), session.handle(Packet::Pubrec(pid), secs(1)));
+    assert_eq!(Some(Packet::Pubrel(pid)), session.poll_transmit(secs(1)));
+    assert_eq!(
+        Ok(Some(ClientEvent::Published(pid))),
+        session.handle(Packet::Pubcomp(pid), secs(1))
+    );
+    assert_eq!(0, session.in_flight());
+}
+
+#[test]
+fn test_qos0_outgoing() {
+    let mut session = ClientSession::<4>::new();
+    assert_eq!(Ok(None), session.publish("a/b", b"hi", QoS::AtMostOnce, false));
+    let mut session = connected(true);
+    assert_eq!(Ok(None), session.publish("a/b", b"hi", QoS::AtMostOnce, false));
+    assert_eq!(Some(publish(false, QosPid::AtMostOnce)), session.poll_transmit(secs(0)));
+    assert_eq!(0, session.in_flight());
+}
+
+#[test]
+fn test_resend_on_reconnect() {
+    let mut session = connected(false);
+    let pid1 = session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap().unwrap();
+    let pid2 = session.publish("a/b", b"hi", QoS::ExactlyOnce, false).unwrap().unwrap();
+    assert_eq!(
+        Some(publish(false, QosPid::AtLeastOnce(pid1))),
+        session.poll_transmit(secs(1))
+    );
+    assert_eq!(
+        Some(publish(false, QosPid::ExactlyOnce(pid2))),
+        session.poll_transmit(secs(1))
+    );
+    assert_eq!(Ok(None), session.handle(Packet::Pubrec(pid2), secs(1)));
+    // Not sent yet.
+    let pid3 = session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap().unwrap();
+
+    session.disconnected();
+    assert!(!session.is_connected());
+    assert_eq!(None, session.poll_transmit(secs(2)));
+
+    session.connect(connect(false));
+    assert_eq!(
+        Some(Packet::Connect(connect(false))),
+        session.poll_transmit(secs(3))
+    );
+    assert_eq!(None, session.poll_transmit(secs(3)));
+    session.handle(connack(true), secs(3)).unwrap();
+    assert_eq!(
+        Some(publish(true, QosPid::AtLeastOnce(pid1))),
+        session.poll_transmit(secs(3))
+    );
+    assert_eq!(Some(Packet::Pubrel(pid2)), session.poll_transmit(secs(3)));
+    assert_eq!(
+        Some(publish(false, QosPid::AtLeastOnce(pid3))),
+        session.poll_transmit(secs(3))
+    );
+    assert_eq!(None, session.poll_transmit(secs(3)));
+
+    // A clean session drops in-flight messages.
+    session.connect(connect(true));
+    assert_eq!(0, session.in_flight());
+}
+
+#[test]
+fn test_incoming() {
+    let mut session = connected(true);
+    let pid = Pid::try_from(7).unwrap();
+
+    let pkt = publish(false, QosPid::AtMostOnce);
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ClientEvent::Publish(_)))));
+    assert_eq!(None, session.poll_transmit(secs(0)));
+
+    let pkt = publish(false, QosPid::AtLeastOnce(pid));
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ClientEvent::Publish(_)))));
+    assert_eq!(Some(Packet::Puback(pid)), session.poll_transmit(secs(0)));
+
+    // Duplicate QoS 2 messages are acknowledged but only delivered once.
+    let pkt = publish(false, QosPid::ExactlyOnce(pid));
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ClientEvent::Publish(_)))));
+    assert_eq!(Some(Packet::Pubrec(pid)), session.poll_transmit(secs(0)));
+    let pkt = publish(true, QosPid::ExactlyOnce(pid));
+    assert_eq!(Ok(None), session.handle(pkt, secs(0)));
+    assert_eq!(Some(Packet::Pubrec(pid)), session.poll_transmit(secs(0)));
+    assert_eq!(Ok(None), session.handle(Packet::Pubrel(pid), secs(0)));
+    assert_eq!(Some(Packet::Pubcomp(pid)), session.poll_transmit(secs(0)));
+    // After Pubrel, the pid can be reused for a new message.
+    let pkt = publish(false, QosPid::ExactlyOnce(pid));
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ClientEvent::Publish(_)))));
+}
+
+#[test]
+fn test_keep_alive() {
+    let mut session = connected(true);
+    assert_eq!(Some(secs(10)), session.poll_timeout());
+    assert_eq!(None, session.poll_transmit(secs(9)));
+    // Sending any packet resets the timer.
+    session.publish("a/b", b"hi", QoS::AtMostOnce, false).unwrap();
+    assert!(session.poll_transmit(secs(9)).is_some());
+    assert_eq!(Some(secs(19)), session.poll_timeout());
+    assert_eq!(None, session.poll_transmit(secs(18)));
+    assert_eq!(Some(Packet::Pingreq), session.poll_transmit(secs(19)));
+    assert_eq!(None, session.poll_timeout());
+    assert_eq!(None, session.poll_transmit(secs(40)));
+    assert!(!session.is_timed_out(secs(28)));
+    assert!(session.is_timed_out(secs(29)));
+    assert_eq!(Ok(None), session.handle(Packet::Pingresp, secs(25)));
+    assert!(!session.is_timed_out(secs(40)));
+    assert_eq!(Some(secs(29)), session.poll_timeout());
+}
+
+#[test]
+fn test_subscribe() {
+    let topic = SubscribeTopic {
+        topic_path: "a/b".parse().unwrap(),
+        qos: QoS::AtLeastOnce,
+    };
+    let topics = || core::iter::once(topic.clone()).collect();
+    let unsub_topics = || core::iter::once("a/b".parse().unwrap()).collect();
+
+    // Requests made before connect() would be dropped without an answer.
+    let mut session = ClientSession::<4>::new();
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Subscribe)),
+        session.subscribe(topics())
+    );
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Unsubscribe)),
+        session.unsubscribe(unsub_topics())
+    );
+    // They are sent once the connection is accepted.
+    session.connect(connect(true));
+    let sub_pid = session.subscribe(topics()).unwrap();
+    assert!(matches!(session.poll_transmit(secs(0)), Some(Packet::Connect(_))));
+    assert_eq!(None, session.poll_transmit(secs(0)));
+    session.handle(connack(false), secs(0)).unwrap();
+    assert!(matches!(session.poll_transmit(secs(0)), Some(Packet::Subscribe(_))));
+    let code = SubscribeReturnCodes::Success(QoS::AtLeastOnce);
+    let suback = |pid| Suback::new(pid, core::iter::once(code).collect());
+    session.handle(suback(sub_pid).into(), secs(0)).unwrap();
+
+    let mut session = connected(true);
+    let sub_pid = session.subscribe(topics()).unwrap();
+    let unsub_pid = session.unsubscribe(unsub_topics()).unwrap();
+    assert_ne!(sub_pid, unsub_pid);
+    assert!(matches!(session.poll_transmit(secs(1)), Some(Packet::Subscribe(_))));
+    assert!(matches!(session.poll_transmit(secs(1)), Some(Packet::Unsubscribe(_))));
+
+    // Acknowledgements must match a pending request of the same type.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Suback)),
+        session.handle(suback(unsub_pid).into(), secs(2))
+    );
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Unsuback)),
+        session.handle(Packet::Unsuback(sub_pid), secs(2))
+    );
+    assert_eq!(
+        Ok(Some(ClientEvent::Suback(suback(sub_pid)))),
+        session.handle(suback(sub_pid).into(), secs(2))
+    );
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Suback)),
+        session.handle(suback(sub_pid).into(), secs(2))
+    );
+    assert_eq!(
+        Ok(Some(ClientEvent::Unsuback(unsub_pid))),
+        session.handle(Packet::Unsuback(unsub_pid), secs(2))
+    );
+}
+
+#[test]
+fn test_errors() {
+    let mut session = ClientSession::<2>::new();
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Connack)),
+        session.handle(connack(false), secs(0))
+    );
+    session.connect(connect(true));
+    let refused = Packet::Connack(Connack {
+        session_present: false,
+        code: ConnectReturnCode::NotAuthorized,
+    });
+    assert_eq!(
+        Err(Error::ConnectionRefused(ConnectReturnCode::NotAuthorized)),
+        session.handle(refused, secs(0))
+    );
+    assert!(!session.is_connected());
+
+    session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap();
+    session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap();
+    assert_eq!(
+        Err(Error::QueueFull),
+        session.publish("a/b", b"hi", QoS::AtLeastOnce, false)
+    );
+}
@@ -0,0 +1,247 @@
+use crate::{
+    client::*,
+    test_util::{pid, publish_qos},
+    *,
+};
+use futures::{SinkExt, StreamExt};
+use std::io::ErrorKind;
+use tokio::io::DuplexStream;
+use tokio_util::codec::Framed;
+
+type Broker = Framed<DuplexStream, MqttCodec>;
+
+async fn next(broker: &mut Broker) -> OwnedPacket {
+    broker.next().await.unwrap().unwrap()
+}
+
+async fn connect_with(
+    keep_alive: u16,
+    code: ConnectReturnCode,
+) -> (Result<(AsyncClient, Publishes), Error>, Broker) {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut broker = Framed::new(server, MqttCodec::default());
+    let connect = Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive,
+        client_id: "test",
+        clean_session: true,
+        last_will: None,
+        username: None,
+        password: None,
+    };
+    let broker_side = async {
+        match next(&mut broker).await {
+            OwnedPacket::Connect(c) => assert_eq!("test", c.client_id),
+            other => panic!("unexpected {:?}", other),
+        }
+        let connack = Connack {
+            session_present: false,
+            code,
+        };
+        broker.send(Packet::Connack(connack)).await.unwrap();
+    };
+    let (res, ()) = tokio::join!(AsyncClient::connect(client, connect), broker_side);
+    (res, broker)
+}
+
+async fn connect(keep_alive: u16) -> (AsyncClient, Publishes, Broker) {
+    let (res, broker) = connect_with(keep_alive, ConnectReturnCode::Accepted).await;
+    let (client, publishes) = res.unwrap();
+    (client, publishes, broker)
+}
+
+#[tokio::test]
+async fn test_connect_refused() {
+    let (res, _broker) = connect_with(0, ConnectReturnCode::NotAuthorized).await;
+    assert_eq!(
+        Err(Error::ConnectionRefused(ConnectReturnCode::NotAuthorized)),
+        res.map(|_| ())
+    );
+}
+
+#[tokio::test]
+async fn test_publish() {
+    let (client, _publishes, mut broker) = connect(0).await;
+
+    client.publish("a/b", b"0", QoS::AtMostOnce, false).await.unwrap();
+    assert_eq!(OwnedPacket::from(publish_qos(QosPid::AtMostOnce, b"0")), next(&mut broker).await);
+
+    // Acknowledgements are matched by pid, whatever their order.
+    let qos1 = async {
+        let (a, b) = tokio::join!(
+            client.publish("a/b", b"1", QoS::AtLeastOnce, false),
+            client.publish("a/b", b"2", QoS::AtLeastOnce, false)
+        );
+        (a.unwrap(), b.unwrap())
+    };
+    let broker_side = async {
+        let first = next(&mut broker).await;
+        let second = next(&mut broker).await;
+        for packet in [second, first].iter() {
+            match packet {
+                OwnedPacket::Publish(p) => {
+                    let pid = p.qospid.pid().unwrap();
+                    broker.send(Packet::Puback(pid)).await.unwrap();
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    };
+    tokio::join!(qos1, broker_side);
+
+    let qos2 = client.publish("a/b", b"3", QoS::ExactlyOnce, false);
+    let broker_side = async {
+        let pid = match next(&mut broker).await {
+            OwnedPacket::Publish(p) => p.qospid.pid().unwrap(),
+            other => panic!("unexpected {:?}", other),
+        };
+        broker.send(Packet::Pubrec(pid)).await.unwrap();
+        assert_eq!(OwnedPacket::Pubrel(pid), next(&mut broker).await);
+        broker.send(Packet::Pubcomp(pid)).await.unwrap();
+    };
+    let (res, ()) = tokio::join!(qos2, broker_side);
+    assert_eq!(Ok(()), res);
+
+    // Invalid packets fail without closing the connection.
+    let res = client.publish("a/+", b"4", QoS::AtLeastOnce, false).await;
+    assert_eq!(Err(Error::InvalidTopicName), res);
+    client.publish("a/b", b"5", QoS::AtMostOnce, false).await.unwrap();
+    assert_eq!(OwnedPacket::from(publish_qos(QosPid::AtMostOnce, b"5")), next(&mut broker).await);
+}
+
+#[tokio::test]
+async fn test_subscribe() {
+    let (client, _publishes, mut broker) = connect(0).await;
+
+    let topic = SubscribeTopic {
+        topic_path: "a/#".into(),
+        qos: QoS::AtLeastOnce,
+    };
+    let subscribe = client.subscribe(vec![topic.clone()]);
+    let broker_side = async {
+        let pid = match next(&mut broker).await {
+            OwnedPacket::Subscribe(s) => {
+                assert_eq!(vec![topic], s.topics);
+                s.pid
+            }
+            other => panic!("unexpected {:?}", other),
+        };
+        let suback = Suback::new(pid, vec![SubscribeReturnCodes::Success(QoS::AtLeastOnce)]);
+        broker.send(Packet::Suback(suback.clone())).await.unwrap();
+        suback
+    };
+    let (res, suback) = tokio::join!(subscribe, broker_side);
+    assert_eq!(Ok(suback), res);
+
+    let unsubscribe = client.unsubscribe(vec!["a/#".into()]);
+    let broker_side = async {
+        match next(&mut broker).await {
+            OwnedPacket::Unsubscribe(u) => broker.send(Packet::Unsuback(u.pid)).await.unwrap(),
+            other => panic!("unexpected {:?}", other),
+        };
+    };
+    let (res, ()) = tokio::join!(unsubscribe, broker_side);
+    assert_eq!(Ok(()), res);
+}
+
+#[tokio::test]
+async fn test_incoming() {
+    let (_client, mut publishes, mut broker) = connect(0).await;
+
+    broker.send(publish_qos(QosPid::AtMostOnce, b"0")).await.unwrap();
+    broker.send(publish_qos(QosPid::AtLeastOnce(pid(1)), b"1")).await.unwrap();
+    assert_eq!(OwnedPacket::Puback(pid(1)), next(&mut broker).await);
+    // The duplicate isn't delivered.
+    broker.send(publish_qos(QosPid::ExactlyOnce(pid(2)), b"2")).await.unwrap();
+    assert_eq!(OwnedPacket::Pubrec(pid(2)), next(&mut broker).await);
+    broker.send(publish_qos(QosPid::ExactlyOnce(pid(2)), b"2")).await.unwrap();
+    assert_eq!(OwnedPacket::Pubrec(pid(2)), next(&mut broker).await);
+    broker.send(Packet::Pubrel(pid(2))).await.unwrap();
+    assert_eq!(OwnedPacket::Pubcomp(pid(2)), next(&mut broker).await);
+
+    let payloads: Vec<_> = publishes.by_ref().take(3).map(|p| p.payload).collect().await;
+    assert_eq!(vec![b"0".to_vec(), b"1".to_vec(), b"2".to_vec()], payloads);
+
+    // The stream ends with the connection.
+    drop(broker);
+    assert_eq!(None, publishes.recv().await);
+}
+
+#[tokio::test]
+async fn test_unread_publishes() {
+    let (client, mut publishes, mut broker) = connect(0).await;
+
+    // Messages that aren't read yet don't block requests.
+    for _ in 0..100 {
+        broker.send(publish_qos(QosPid::AtMostOnce, b"0")).await.unwrap();
+    }
+    let qos1 = client.publish("a/b", b"1", QoS::AtLeastOnce, false);
+    let broker_side = async {
+        let pid = match next(&mut broker).await {
+            OwnedPacket::Publish(p) => p.qospid.pid().unwrap(),
+            other => panic!("unexpected {:?}", other),
+        };
+        broker.send(Packet::Puback(pid)).await.unwrap();
+    };
+    let (res, ()) = tokio::join!(qos1, broker_side);
+    assert_eq!(Ok(()), res);
+    assert_eq!(100, publishes.by_ref().take(100).count().await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keep_alive() {
+    let (client, _publishes, mut broker) = connect(10).await;
+
+    assert_eq!(OwnedPacket::Pingreq, next(&mut broker).await);
+    broker.send(Packet::Pingresp).await.unwrap();
+
+    // Without an answer, pending requests fail and the connection is closed.
+    let qos1 = client.publish("a/b", b"1", QoS::AtLeastOnce, false);
+    let broker_side = async {
+        assert!(matches!(next(&mut broker).await, OwnedPacket::Publish(_)));
+        assert_eq!(OwnedPacket::Pingreq, next(&mut broker).await);
+        assert!(broker.next().await.is_none());
+    };
+    let (res, ()) = tokio::join!(qos1, broker_side);
+    assert!(matches!(res, Err(Error::IoError(ErrorKind::TimedOut, _))));
+    let res = client.publish("a/b", b"2", QoS::AtMostOnce, false).await;
+    assert!(matches!(res, Err(Error::IoError(ErrorKind::NotConnected, _))));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_in_flight_window() {
+    let (client, _publishes, mut broker) = connect(10).await;
+    for i in 0..=MAX_IN_FLIGHT {
+        let client = client.clone();
+        tokio::spawn(async move {
+            client.publish("a/b", i.to_string().as_bytes(), QoS::AtLeastOnce, false).await
+        });
+    }
+    let mut pids = Vec::new();
+    for _ in 0..MAX_IN_FLIGHT {
+        match next(&mut broker).await {
+            OwnedPacket::Publish(p) => pids.push(p.qospid.pid().unwrap()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    // The last message waits for an acknowledgement, until the connection is idle.
+    assert_eq!(OwnedPacket::Pingreq, next(&mut broker).await);
+    broker.send(Packet::Pingresp).await.unwrap();
+    broker.send(Packet::Puback(pids[0])).await.unwrap();
+    match next(&mut broker).await {
+        OwnedPacket::Publish(p) => {
+            assert_eq!(MAX_IN_FLIGHT.to_string().as_bytes(), &p.payload[..]);
+            assert!(!pids[1..].contains(&p.qospid.pid().unwrap()));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_disconnect() {
+    let (client, mut publishes, mut broker) = connect(0).await;
+    client.disconnect().await.unwrap();
+    assert_eq!(OwnedPacket::Disconnect, next(&mut broker).await);
+    assert!(broker.next().await.is_none());
+    assert_eq!(None, publishes.recv().await);
+}
@@ -1,6 +1,6 @@
 #[cfg(feature = "defmt")]
 use defmt::Format;
-use crate::{decoder::*, encoder::*, *};
+use crate::{decoder::*, encoder::*, topic::check_topic_name, *};
 #[cfg(not(feature = "std"))]
 use heapless::String;
 #[cfg(feature = "std")]
@@ -17,54 +17,77 @@ use core::str::FromStr;
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Protocol {
-    /// [MQTT 3.1.1] is the most commonly implemented version. [MQTT 5] isn't yet supported my by
-    /// `mqttrs`.
+    /// [MQTT 3.1.1] is the most commonly implemented version.
     ///
     /// [MQTT 3.1.1]: https://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html
-    /// [MQTT 5]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html
     MQTT311,
     /// MQIsdp, aka SCADA are pre-standardisation names of MQTT. It should mostly conform to MQTT
     /// 3.1.1, but you should watch out for implementation discrepancies. `Mqttrs` handles it like
     /// standard MQTT 3.1.1.
     MQIsdp,
+    /// [MQTT 5] packets have a different layout, and are handled by the [`v5`] module.
+    ///
+    /// [MQTT 5]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html
+    /// [`v5`]: v5/index.html
+    MQTT5,
 }
 impl Protocol {
     pub(crate) fn new(name: &str, level: u8) -> Result<Protocol, Error> {
         match (name, level) {
             ("MQIsdp", 3) => Ok(Protocol::MQIsdp),
             ("MQTT", 4) => Ok(Protocol::MQTT311),
-            _ => Err(Error::InvalidProtocol(String::from_str(name).unwrap(), 0)),
-    }
+            ("MQTT", 5) => Ok(Protocol::MQTT5),
+            _ => Err(invalid_protocol(name, level)),
+        }
     }
-    pub(crate) fn from_buffer<'a>(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
+    pub(crate) fn from_buffer(buf: &[u8], offset: &mut usize) -> Result<Self, Error> {
         let protocol_name = read_str(buf, offset)?;
-        let protocol_level = buf[*offset];
-        *offset += 1;
+        let protocol_level = read_u8(buf, offset)?;
 
         Protocol::new(protocol_name, protocol_level)
     }
-    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+    pub(crate) fn to_buffer(self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let slice: &[u8] = match self {
+            Protocol::MQTT311 => &[0u8, 4, b'M', b'Q', b'T', b'T', 4],
+            Protocol::MQIsdp => &[0u8, 6, b'M', b'Q', b'I', b's', b'd', b'p', 3],
+            Protocol::MQTT5 => &[0u8, 4, b'M', b'Q', b'T', b'T', 5],
+        };
+        for &byte in slice {
+            write_u8(buf, offset, byte)?;
+        }
+        Ok(slice.len())
+    }
+    /// Error returned when this protocol's packet layout isn't handled by the caller.
+    pub(crate) fn unsupported(self) -> Error {
+        let (name, level) = match self {
+            Protocol::MQTT311 => ("MQTT", 4),
+            Protocol::MQIsdp => ("MQIsdp", 3),
+            Protocol::MQTT5 => ("MQTT", 5),
+        };
+        invalid_protocol(name, level)
+    }
+    /// Number of bytes written by `to_buffer()`.
+    pub(crate) fn len(self) -> usize {
         match self {
-            Protocol::MQTT311 => {
-                let slice = &[0u8, 4, 'M' as u8, 'Q' as u8, 'T' as u8, 'T' as u8, 4];
-                for &byte in slice {
-                    write_u8(buf, offset, byte)?;
-                }
-                Ok(slice.len())
-            }
-            Protocol::MQIsdp => {
-                let slice = &[
-                    0u8, 4, 'M' as u8, 'Q' as u8, 'i' as u8, 's' as u8, 'd' as u8, 'p' as u8, 4,
-                ];
-                for &byte in slice {
-                    write_u8(buf, offset, byte)?;
-                }
-                Ok(slice.len())
-            }
+            Protocol::MQIsdp => 9,
+            _ => 7,
         }
     }
 }
 
+/// `Error::InvalidProtocol`, with the name truncated to the error's capacity without `std`.
+fn invalid_protocol(name: &str, level: u8) -> Error {
+    #[cfg(not(feature = "std"))]
+    let name = {
+        let mut len = name.len().min(10);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        &name[..len]
+    };
+    Error::InvalidProtocol(String::from_str(name).unwrap_or_default(), level)
+}
+
 /// Message that the server should publish when the client disconnects.
 ///
 /// Sent by the client in the [Connect] packet. [MQTT 3.1.3.3].
@@ -87,7 +110,7 @@ pub struct LastWill<'a> {
 /// [Connack]: struct.Connack.html
 /// [MQTT 3.2.2.3]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718035
 #[cfg_attr(feature = "defmt",derive(Format))]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectReturnCode {
     Accepted,
     RefusedProtocolVersion,
@@ -97,8 +120,8 @@ pub enum ConnectReturnCode {
     NotAuthorized,
 }
 impl ConnectReturnCode {
-    fn to_u8(&self) -> u8 {
-        match *self {
+    fn to_u8(self) -> u8 {
+        match self {
             ConnectReturnCode::Accepted => 0,
             ConnectReturnCode::RefusedProtocolVersion => 1,
             ConnectReturnCode::RefusedIdentifierRejected => 2,
@@ -146,17 +169,32 @@ pub struct Connack {
 }
 
 impl<'a> Connect<'a> {
-    pub(crate) fn from_buffer(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
+    pub(crate) fn from_buffer(
+        buf: &'a [u8],
+        offset: &mut usize,
+        opts: &DecodeOptions,
+    ) -> Result<Self, Error> {
         let protocol = Protocol::from_buffer(buf, offset)?;
+        if protocol == Protocol::MQTT5 {
+            return Err(Protocol::MQTT5.unsupported());
+        }
 
-        let connect_flags = buf[*offset];
-        let keep_alive = ((buf[*offset + 1] as u16) << 8) | buf[*offset + 2] as u16;
-        *offset += 3;
+        let connect_flags = read_u8(buf, offset)?;
+        // Reserved bit, or will QoS and retain without a will (MQTT-3.1.2-3, 11, 13 and 15).
+        if opts.strict
+            && (connect_flags & 0b1 != 0
+                || (connect_flags & 0b100 == 0 && connect_flags & 0b00111000 != 0))
+        {
+            return Err(Error::InvalidConnectFlags(connect_flags));
+        }
+        let keep_alive = read_u16(buf, offset)?;
 
         let client_id = read_str(buf, offset)?;
+        opts.check_client_id_len(client_id)?;
 
         let last_will = if connect_flags & 0b100 != 0 {
             let will_topic = read_str(buf, offset)?;
+            opts.check_topic_len(will_topic)?;
             let will_message = read_bytes(buf, offset)?;
             let will_qod = QoS::from_u8((connect_flags & 0b11000) >> 3)?;
             Some(LastWill {
@@ -183,7 +221,7 @@ impl<'a> Connect<'a> {
 
         let clean_session = (connect_flags & 0b10) != 0;
 
-        Ok(Connect {
+        let connect = Connect {
             protocol,
             keep_alive,
             client_id,
@@ -191,27 +229,62 @@ impl<'a> Connect<'a> {
             password,
             last_will,
             clean_session,
-        })
+        };
+        if opts.strict {
+            connect.validate()?;
+        }
+        Ok(connect)
     }
 
-    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
-        let header: u8 = 0b00010000;
-        let mut length: usize = 6 + 1 + 1; // NOTE: protocol_name(6) + protocol_level(1) + flags(1);
+    /// Check the rules of [MQTT 3.1] that can't be enforced by types.
+    ///
+    /// * A password requires a username (`Error::InvalidConnectFlags`).
+    /// * An empty client id requires a clean session (`Error::InvalidClientId`). A server should
+    ///   answer with [`ConnectReturnCode::RefusedIdentifierRejected`].
+    /// * The will topic must be a valid topic name (`Error::InvalidTopicName`).
+    ///
+    /// Strict decoding (see [DecodeOptions]) calls this, and also rejects a reserved flag bit or
+    /// will flags without a will, which can't be represented in a `Connect`.
+    ///
+    /// ```
+    /// # use mqttrs::*;
+    /// let connect = Connect { protocol: Protocol::MQTT311,
+    ///                         keep_alive: 60,
+    ///                         client_id: "",
+    ///                         clean_session: false,
+    ///                         last_will: None,
+    ///                         username: None,
+    ///                         password: None };
+    /// assert_eq!(Err(Error::InvalidClientId), connect.validate());
+    /// ```
+    ///
+    /// [MQTT 3.1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718028
+    /// [`ConnectReturnCode::RefusedIdentifierRejected`]: enum.ConnectReturnCode.html
+    /// [DecodeOptions]: struct.DecodeOptions.html
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.password.is_some() && self.username.is_none() {
+            return Err(Error::InvalidConnectFlags(self.flags()));
+        }
+        if self.client_id.is_empty() && !self.clean_session {
+            return Err(Error::InvalidClientId);
+        }
+        if let Some(last_will) = &self.last_will {
+            check_topic_name(last_will.topic)?;
+        }
+        Ok(())
+    }
+
+    /// Connect flags byte.
+    fn flags(&self) -> u8 {
         let mut connect_flags: u8 = 0b00000000;
         if self.clean_session {
             connect_flags |= 0b10;
         };
-        length += 2 + self.client_id.len();
-        length += 2; // keep alive
-        if let Some(username) = self.username {
+        if self.username.is_some() {
             connect_flags |= 0b10000000;
-            length += username.len();
-            length += 2;
         };
-        if let Some(password) = self.password {
+        if self.password.is_some() {
             connect_flags |= 0b01000000;
-            length += password.len();
-            length += 2;
         };
         if let Some(last_will) = &self.last_will {
             connect_flags |= 0b00000100;
@@ -219,10 +292,33 @@ impl<'a> Connect<'a> {
             if last_will.retain {
                 connect_flags |= 0b00100000;
             };
-            length += last_will.message.len();
-            length += last_will.topic.len();
-            length += 4;
         };
+        connect_flags
+    }
+
+    /// Length of the packet after the fixed header.
+    pub(crate) fn remaining_len(&self) -> usize {
+        // Protocol name and level, flags(1), client id and keep alive(2).
+        let mut length = self.protocol.len() + 1 + 2 + self.client_id.len() + 2;
+        if let Some(username) = self.username {
+            length += 2 + username.len();
+        };
+        if let Some(password) = self.password {
+            length += 2 + password.len();
+        };
+        if let Some(last_will) = &self.last_will {
+            length += 4 + last_will.topic.len() + last_will.message.len();
+        };
+        length
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        if self.protocol == Protocol::MQTT5 {
+            return Err(Protocol::MQTT5.unsupported());
+        }
+        let header: u8 = 0b00010000;
+        let length = self.remaining_len();
+        let connect_flags = self.flags();
         check_remaining(buf, offset, length + 1)?;
 
         // NOTE: putting data into buffer.
@@ -238,7 +334,7 @@ impl<'a> Connect<'a> {
 
         if let Some(last_will) = &self.last_will {
             write_string(buf, offset, last_will.topic)?;
-            write_bytes(buf, offset, &last_will.message)?;
+            write_bytes(buf, offset, last_will.message)?;
         };
 
         if let Some(username) = self.username {
@@ -253,16 +349,15 @@ impl<'a> Connect<'a> {
 }
 
 impl Connack {
-    pub(crate) fn from_buffer<'a>(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
-        let flags = buf[*offset];
-        let return_code = buf[*offset + 1];
-        *offset += 2;
+    pub(crate) fn from_buffer(buf: &[u8], offset: &mut usize) -> Result<Self, Error> {
+        let flags = read_u8(buf, offset)?;
+        let return_code = read_u8(buf, offset)?;
         Ok(Connack {
             session_present: (flags & 0b1 == 1),
             code: ConnectReturnCode::from_u8(return_code)?,
         })
     }
-    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+    pub(crate) fn to_buffer(self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
         check_remaining(buf, offset, 4)?;
         let header: u8 = 0b00100000;
         let length: u8 = 2;
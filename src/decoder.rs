@@ -1,3 +1,5 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
 use crate::*;
 
 pub fn clone_packet(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
@@ -17,6 +19,9 @@ pub fn clone_packet(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
     if let Some((_, remaining_len)) = read_header(input, &mut offset)? {
         let end = offset + remaining_len;
         let len = end - start;
+        if output.len() < len {
+            return Err(Error::WriteZero);
+        }
         output[..len].copy_from_slice(&input[start..end]);
         Ok(len)
     } else {
@@ -50,7 +55,7 @@ pub fn clone_packet(input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
 /// }
 /// ```
 ///
-/// [Packet]: ../enum.Packet.html
+/// [Packet]: ../type.Packet.html
 /// [BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
 pub fn decode_slice<'a>(buf: &'a [u8]) -> Result<Option<Packet<'a>>, Error> {
     if let Some((_, r)) = decode_slice_with_len(buf)? {
@@ -104,12 +109,120 @@ pub fn decode_slice<'a>(buf: &'a [u8]) -> Result<Option<Packet<'a>>, Error> {
 /// }
 /// ```
 ///
-/// [Packet]: ../enum.Packet.html
+/// [Packet]: ../type.Packet.html
 /// [BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
 pub fn decode_slice_with_len<'a>(buf: &'a [u8]) -> Result<Option<(usize, Packet<'a>)>, Error> {
+    decode_slice_with_options(buf, &DecodeOptions::default())
+}
+
+/// Options for [decode_slice_with_options()].
+///
+/// The default options are lenient, like [decode_slice()].
+///
+/// [decode_slice_with_options()]: fn.decode_slice_with_options.html
+/// [decode_slice()]: fn.decode_slice.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecodeOptions {
+    /// Reject packets that are well-formed but break the rules of the spec, such as a `Publish`
+    /// topic name with wildcards or a `Subscribe` with an invalid topic filter.
+    ///
+    /// A broker should use strict decoding to reject misbehaving clients, while a sniffer or a
+    /// debugging tool can stay lenient to inspect them.
+    pub strict: bool,
+    /// Maximum size of a whole packet, header included (`Error::PacketTooLarge`).
+    ///
+    /// Bigger packets are rejected as soon as their fixed header is received, before waiting for
+    /// the rest of the packet.
+    pub max_packet_size: Option<usize>,
+    /// Maximum number of topics in a `Subscribe` or `Unsubscribe` packet
+    /// (`Error::TooManyTopics`).
+    pub max_topics: Option<usize>,
+    /// Maximum length in bytes of a topic name or filter, including the `Connect` will topic
+    /// (`Error::TopicTooLong`).
+    pub max_topic_len: Option<usize>,
+    /// Maximum length in bytes of a `Connect` client id (`Error::ClientIdTooLong`).
+    pub max_client_id_len: Option<usize>,
+}
+
+impl DecodeOptions {
+    /// Options with `strict` decoding enabled.
+    pub fn strict() -> Self {
+        DecodeOptions {
+            strict: true,
+            ..DecodeOptions::default()
+        }
+    }
+
+    pub(crate) fn check_packet_size(&self, size: usize) -> Result<(), Error> {
+        match self.max_packet_size {
+            Some(max) if size > max => Err(Error::PacketTooLarge),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_topic_count(&self, count: usize) -> Result<(), Error> {
+        match self.max_topics {
+            Some(max) if count > max => Err(Error::TooManyTopics),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_topic_len(&self, topic: &str) -> Result<(), Error> {
+        match self.max_topic_len {
+            Some(max) if topic.len() > max => Err(Error::TopicTooLong),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_client_id_len(&self, client_id: &str) -> Result<(), Error> {
+        match self.max_client_id_len {
+            Some(max) if client_id.len() > max => Err(Error::ClientIdTooLong),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Decode bytes from a slice like [decode_slice_with_len()], with the given [DecodeOptions].
+///
+/// ```
+/// # use mqttrs::*;
+/// // A Publish packet with a wildcard in its topic name.
+/// let buf = [0b00110000, 5, 0, 3, b'a', b'/', b'#'];
+///
+/// assert!(matches!(decode_slice_with_options(&buf, &DecodeOptions::default()),
+///                  Ok(Some((7, Packet::Publish(_))))));
+/// assert_eq!(Err(Error::InvalidTopicName),
+///            decode_slice_with_options(&buf, &DecodeOptions::strict()));
+/// ```
+///
+/// [decode_slice_with_len()]: fn.decode_slice_with_len.html
+/// [DecodeOptions]: struct.DecodeOptions.html
+pub fn decode_slice_with_options<'a>(
+    buf: &'a [u8],
+    opts: &DecodeOptions,
+) -> Result<Option<(usize, Packet<'a>)>, Error> {
+    decode_packet(buf, opts)
+}
+
+pub(crate) fn decode_packet<'a, const N: usize, const L: usize>(
+    buf: &'a [u8],
+    opts: &DecodeOptions,
+) -> Result<Option<(usize, GenericPacket<'a, N, L>)>, Error> {
+    if let Some((header_len, remaining_len)) = read_frame_len(buf)? {
+        opts.check_packet_size(header_len + remaining_len)?;
+    }
     let mut offset = 0;
     if let Some((header, remaining_len)) = read_header(buf, &mut offset)? {
-        let r = read_packet(header, remaining_len, buf, &mut offset)?;
+        check_remaining_len(header.typ, remaining_len)?;
+        // Make sure that parsers can't read past the end of this packet.
+        let end = offset + remaining_len;
+        let buf = &buf[..end];
+        let r = read_packet(header, remaining_len, buf, &mut offset, opts)?;
+        // Or stop before it, which would desynchronize the stream.
+        if offset != end {
+            return Err(Error::InvalidRemainingLength(header.typ, remaining_len));
+        }
         Ok(Some((offset, r)))
     } else {
         // Don't have a full packet
@@ -117,51 +230,137 @@ pub fn decode_slice_with_len<'a>(buf: &'a [u8]) -> Result<Option<(usize, Packet<
     }
 }
 
-fn read_packet<'a>(
+/// Decode bytes from a slice as a packet of the negotiated `protocol` version, returning a tuple
+/// containing the number of bytes read from the buffer and the [VersionedPacket].
+///
+/// `Connect` packets carry their own protocol version, so they are always decoded according to
+/// it. A server can therefore start with any `protocol` value, and switch to the version of the
+/// `Connect` packet it received for the rest of the connection.
+///
+/// ```
+/// # use mqttrs::*;
+/// let mut buf = [0u8; 64];
+/// let connect = v5::Connect { keep_alive: 30,
+///                             client_id: "doc_client",
+///                             clean_start: true,
+///                             last_will: None,
+///                             username: None,
+///                             password: None,
+///                             properties: v5::Properties::new() };
+/// let len = v5::encode_slice(&connect.into(), &mut buf).unwrap();
+/// buf[len..len + 2].copy_from_slice(&[0b11100000, 0]); // Disconnect
+///
+/// // A server that doesn't know the client's protocol version yet.
+/// let mut protocol = Protocol::MQTT311;
+/// let (n, pkt) = decode_slice_versioned(&buf, protocol).unwrap().unwrap();
+/// if let VersionedPacket::V5(v5::Packet::Connect(_)) = pkt {
+///     protocol = Protocol::MQTT5;
+/// }
+/// match decode_slice_versioned(&buf[n..], protocol) {
+///     Ok(Some((2, VersionedPacket::V5(v5::Packet::Disconnect(_))))) => (),
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// ```
+///
+/// [VersionedPacket]: enum.VersionedPacket.html
+pub fn decode_slice_versioned<'a>(
+    buf: &'a [u8],
+    protocol: Protocol,
+) -> Result<Option<(usize, VersionedPacket<'a>)>, Error> {
+    let mut offset = 0;
+    let protocol = match read_header(buf, &mut offset)? {
+        Some((header, _)) if header.typ == PacketType::Connect => {
+            Protocol::from_buffer(buf, &mut offset)?
+        }
+        Some(_) => protocol,
+        None => return Ok(None),
+    };
+    Ok(match protocol {
+        Protocol::MQTT5 => v5::decode_slice_with_len(buf)?.map(|(n, p)| (n, p.into())),
+        _ => decode_slice_with_len(buf)?.map(|(n, p)| (n, p.into())),
+    })
+}
+
+fn read_packet<'a, const N: usize, const L: usize>(
     header: Header,
     remaining_len: usize,
     buf: &'a [u8],
     offset: &mut usize,
-) -> Result<Packet<'a>, Error> {
+    opts: &DecodeOptions,
+) -> Result<GenericPacket<'a, N, L>, Error> {
     Ok(match header.typ {
-        PacketType::Pingreq => Packet::Pingreq,
-        PacketType::Pingresp => Packet::Pingresp,
-        PacketType::Disconnect => Packet::Disconnect,
-        PacketType::Connect => Connect::from_buffer(buf, offset)?.into(),
+        PacketType::Pingreq => GenericPacket::Pingreq,
+        PacketType::Pingresp => GenericPacket::Pingresp,
+        PacketType::Disconnect => GenericPacket::Disconnect,
+        PacketType::Connect => Connect::from_buffer(buf, offset, opts)?.into(),
         PacketType::Connack => Connack::from_buffer(buf, offset)?.into(),
-        PacketType::Publish => Publish::from_buffer(&header, remaining_len, buf, offset)?.into(),
-        PacketType::Puback => Packet::Puback(Pid::from_buffer(buf, offset)?),
-        PacketType::Pubrec => Packet::Pubrec(Pid::from_buffer(buf, offset)?),
-        PacketType::Pubrel => Packet::Pubrel(Pid::from_buffer(buf, offset)?),
-        PacketType::Pubcomp => Packet::Pubcomp(Pid::from_buffer(buf, offset)?),
-        PacketType::Subscribe => Subscribe::from_buffer(remaining_len, buf, offset)?.into(),
-        PacketType::Suback => Suback::from_buffer(remaining_len, buf, offset)?.into(),
-        PacketType::Unsubscribe => Unsubscribe::from_buffer(remaining_len, buf, offset)?.into(),
-        PacketType::Unsuback => Packet::Unsuback(Pid::from_buffer(buf, offset)?),
+        PacketType::Publish => {
+            Publish::from_buffer(&header, remaining_len, buf, offset, opts)?.into()
+        }
+        PacketType::Puback => GenericPacket::Puback(Pid::from_buffer(buf, offset)?),
+        PacketType::Pubrec => GenericPacket::Pubrec(Pid::from_buffer(buf, offset)?),
+        PacketType::Pubrel => GenericPacket::Pubrel(Pid::from_buffer(buf, offset)?),
+        PacketType::Pubcomp => GenericPacket::Pubcomp(Pid::from_buffer(buf, offset)?),
+        PacketType::Subscribe => {
+            GenericSubscribe::from_buffer(remaining_len, buf, offset, opts)?.into()
+        }
+        PacketType::Suback => GenericSuback::from_buffer(remaining_len, buf, offset)?.into(),
+        PacketType::Unsubscribe => {
+            GenericUnsubscribe::from_buffer(remaining_len, buf, offset, opts)?.into()
+        }
+        PacketType::Unsuback => GenericPacket::Unsuback(Pid::from_buffer(buf, offset)?),
+        // AUTH only exists in MQTT 5, see `v5::decode_slice()`.
+        PacketType::Auth => return Err(Error::InvalidHeader),
     })
 }
 
+/// Check the remaining_len of fixed-size packets.
+fn check_remaining_len(typ: PacketType, remaining_len: usize) -> Result<(), Error> {
+    let expected = match typ {
+        PacketType::Pingreq | PacketType::Pingresp | PacketType::Disconnect => 0,
+        PacketType::Connack
+        | PacketType::Puback
+        | PacketType::Pubrec
+        | PacketType::Pubrel
+        | PacketType::Pubcomp
+        | PacketType::Unsuback => 2,
+        _ => return Ok(()),
+    };
+    if remaining_len != expected {
+        return Err(Error::InvalidRemainingLength(typ, remaining_len));
+    }
+    Ok(())
+}
+
 /// Read the parsed header and remaining_len from the buffer. Only return Some() and advance the
 /// buffer position if there is enough data in the buffer to read the full packet.
-pub(crate) fn read_header<'a>(
-    buf: &'a [u8],
+pub(crate) fn read_header(
+    buf: &[u8],
     offset: &mut usize,
 ) -> Result<Option<(Header, usize)>, Error> {
+    match read_frame_len(&buf[*offset..])? {
+        Some((header_len, len)) if buf.len() >= *offset + header_len + len => {
+            // Parse header byte, skip past the header, and return
+            let header = Header::new(buf[*offset])?;
+            *offset += header_len;
+            Ok(Some((header, len)))
+        }
+        // Won't be able to read full packet
+        _ => Ok(None),
+    }
+}
+
+/// Read the length of the fixed header and the remaining_len from the start of the buffer, as
+/// soon as they are available (the rest of the packet may not be in the buffer yet).
+pub(crate) fn read_frame_len(buf: &[u8]) -> Result<Option<(usize, usize)>, Error> {
     let mut len: usize = 0;
     for pos in 0..=3 {
-        if buf.len() > *offset + pos + 1 {
-            let byte = buf[*offset + pos + 1];
+        if buf.len() > pos + 1 {
+            let byte = buf[pos + 1];
             len += (byte as usize & 0x7F) << (pos * 7);
             if (byte & 0x80) == 0 {
                 // Continuation bit == 0, length is parsed
-                if buf.len() < *offset + 2 + pos + len {
-                    // Won't be able to read full packet
-                    return Ok(None);
-                }
-                // Parse header byte, skip past the header, and return
-                let header = Header::new(buf[*offset])?;
-                *offset += pos + 2;
-                return Ok(Some((header, len)));
+                return Ok(Some((pos + 2, len)));
             }
         } else {
             // Couldn't read full length
@@ -196,6 +395,7 @@ impl Header {
             12 => (PacketType::Pingreq, hd & 0b1111 == 0),
             13 => (PacketType::Pingresp, hd & 0b1111 == 0),
             14 => (PacketType::Disconnect, hd & 0b1111 == 0),
+            15 => (PacketType::Auth, hd & 0b1111 == 0),
             _ => (PacketType::Connect, false),
         };
         if !flags_ok {
@@ -211,20 +411,46 @@ impl Header {
 }
 
 pub(crate) fn read_str<'a>(buf: &'a [u8], offset: &mut usize) -> Result<&'a str, Error> {
-    core::str::from_utf8(read_bytes(buf, offset)?).map_err(|e| Error::InvalidString(e))
+    core::str::from_utf8(read_bytes(buf, offset)?).map_err(Error::InvalidString)
 }
 
 pub(crate) fn read_bytes<'a>(buf: &'a [u8], offset: &mut usize) -> Result<&'a [u8], Error> {
-    if buf[*offset..].len() < 2 {
-        return Err(Error::InvalidLength);
+    let len = read_u16(buf, offset)? as usize;
+    match buf.get(*offset..*offset + len) {
+        Some(bytes) => {
+            *offset += len;
+            Ok(bytes)
+        }
+        None => Err(Error::InvalidLength),
     }
-    let len = ((buf[*offset] as usize) << 8) | buf[*offset + 1] as usize;
-    *offset += 2;
-    if len > buf[*offset..].len() {
-        Err(Error::InvalidLength)
-    } else {
-        let bytes = &buf[*offset..*offset + len];
-        *offset += len;
-        Ok(bytes)
+}
+
+pub(crate) fn read_u8(buf: &[u8], offset: &mut usize) -> Result<u8, Error> {
+    let byte = *buf.get(*offset).ok_or(Error::InvalidLength)?;
+    *offset += 1;
+    Ok(byte)
+}
+
+pub(crate) fn read_u16(buf: &[u8], offset: &mut usize) -> Result<u16, Error> {
+    Ok(((read_u8(buf, offset)? as u16) << 8) | read_u8(buf, offset)? as u16)
+}
+
+pub(crate) fn read_u32(buf: &[u8], offset: &mut usize) -> Result<u32, Error> {
+    Ok(((read_u16(buf, offset)? as u32) << 16) | read_u16(buf, offset)? as u32)
+}
+
+/// Read a [Variable Byte Integer], as used by MQTT 5 property lengths.
+///
+/// [Variable Byte Integer]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901011
+pub(crate) fn read_varint(buf: &[u8], offset: &mut usize) -> Result<usize, Error> {
+    let mut val: usize = 0;
+    for pos in 0..=3 {
+        let byte = read_u8(buf, offset)?;
+        val += (byte as usize & 0x7F) << (pos * 7);
+        if (byte & 0x80) == 0 {
+            return Ok(val);
+        }
     }
+    // Continuation bit == 1 four times, that's illegal.
+    Err(Error::InvalidLength)
 }
@@ -1,6 +1,18 @@
+#![allow(
+    clippy::bool_assert_comparison,
+    clippy::char_lit_as_u8,
+    clippy::get_first,
+    clippy::match_like_matches_macro,
+    clippy::needless_borrow,
+    clippy::unnecessary_cast,
+    clippy::unnecessary_mut_passed
+)]
+
 use crate::*;
+use core::convert::TryFrom;
 use bytes::BytesMut;
 use subscribe::LimitedString;
+#[cfg(not(feature = "std"))]
 use core::str::FromStr;
 
 macro_rules! header {
@@ -47,6 +59,7 @@ fn header_firstbyte() {
         (0b1100_0000, header!(Pingreq, false, AtMostOnce, false)),
         (0b1101_0000, header!(Pingresp, false, AtMostOnce, false)),
         (0b1110_0000, header!(Disconnect, false, AtMostOnce, false)),
+        (0b1111_0000, header!(Auth, false, AtMostOnce, false)),
     ];
     for n in 0..=255 {
         let res = match valid.iter().find(|(byte, _)| *byte == n) {
@@ -511,3 +524,272 @@ fn test_unsub_ack() {
         other => panic!("Failed decode: {:?}", other),
     }
 }
+
+#[test]
+fn test_auth_is_v5_only() {
+    let data: &[u8] = &[0b11110000, 0];
+    assert_eq!(Err(Error::InvalidHeader), decode_slice(data));
+}
+
+#[test]
+fn test_decode_versioned() {
+    let data: &[u8] = &[
+        // MQTT 5 connect packet
+        0b00010000, 17, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x05, 0b00000010, 0x00, 0x0a, 0x00,
+        0x00, 0x04, b't', b'e', b's', b't',
+        // MQTT 5 puback packet with reason code
+        0b01000000, 3, 0, 10, 0x10,
+    ];
+    let (n, pkt) = decode_slice_versioned(data, Protocol::MQTT311).unwrap().unwrap();
+    assert_eq!(n, 19);
+    assert!(matches!(pkt, VersionedPacket::V5(v5::Packet::Connect(_))));
+    match decode_slice_versioned(&data[n..], Protocol::MQTT5) {
+        Ok(Some((5, VersionedPacket::V5(v5::Packet::Puback(a))))) => {
+            assert_eq!(a.pid.get(), 10);
+            assert_eq!(a.reason_code, v5::ReasonCode::NoMatchingSubscribers);
+        }
+        other => panic!("Failed decode: {:?}", other),
+    }
+    // Not enough data yet
+    assert_eq!(Ok(None), decode_slice_versioned(&data[..18], Protocol::MQTT311));
+
+    // The 3.1.1 decoder doesn't know how to read MQTT 5 packets.
+    assert!(matches!(
+        decode_slice(data),
+        Err(Error::InvalidProtocol(_, 5))
+    ));
+    let puback = VersionedPacket::V311(Packet::Puback(Pid::try_from(10).unwrap()));
+    assert_eq!(
+        Ok(Some((4, puback))),
+        decode_slice_versioned(&[0b01000000, 2, 0, 10], Protocol::MQIsdp)
+    );
+}
+
+#[test]
+fn test_strict_topics() {
+    let strict = DecodeOptions::strict();
+    let lenient = DecodeOptions::default();
+    let publishes: [&[u8]; 4] = [
+        &[0b00110000, 2, 0, 0],
+        &[0b00110000, 5, 0, 3, b'a', b'/', b'+'],
+        &[0b00110000, 5, 0, 3, b'a', b'/', b'#'],
+        &[0b00110000, 5, 0, 3, b'a', 0, b'b'],
+    ];
+    for data in publishes.iter() {
+        assert!(matches!(
+            decode_slice_with_options(data, &lenient),
+            Ok(Some((_, Packet::Publish(_))))
+        ));
+        assert_eq!(Err(Error::InvalidTopicName), decode_slice_with_options(data, &strict));
+    }
+    let data: &[u8] = &[0b00110000, 5, 0, 3, b'a', b'/', b'b'];
+    assert!(decode_slice_with_options(data, &strict).unwrap().is_some());
+
+    let subscribe: &[u8] = &[0b10000010, 8, 0, 10, 0, 3, b'a', b'#', b'b', 0];
+    let unsubscribe: &[u8] = &[0b10100010, 4, 0, 10, 0, 0];
+    for data in [subscribe, unsubscribe].iter() {
+        assert!(decode_slice_with_options(data, &lenient).unwrap().is_some());
+        assert_eq!(Err(Error::InvalidTopicFilter), decode_slice_with_options(data, &strict));
+    }
+    let data: &[u8] = &[0b10000010, 8, 0, 10, 0, 3, b'a', b'/', b'#', 0];
+    assert!(decode_slice_with_options(data, &strict).unwrap().is_some());
+}
+
+#[test]
+fn test_strict_connect() {
+    fn connect(flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0b00010000, 10 + payload.len() as u8, 0, 4, b'M', b'Q', b'T', b'T', 4];
+        buf.extend_from_slice(&[flags, 0, 10]);
+        buf.extend_from_slice(payload);
+        buf
+    }
+    let strict = DecodeOptions::strict();
+    let lenient = DecodeOptions::default();
+    let cases = [
+        // Reserved bit, will QoS and will retain without a will
+        (connect(0b00000011, &[0, 1, b'c']), Error::InvalidConnectFlags(0b00000011)),
+        (connect(0b00001010, &[0, 1, b'c']), Error::InvalidConnectFlags(0b00001010)),
+        (connect(0b00100010, &[0, 1, b'c']), Error::InvalidConnectFlags(0b00100010)),
+        // Password without username
+        (connect(0b01000010, &[0, 1, b'c', 0, 1, b'p']), Error::InvalidConnectFlags(0b01000010)),
+        // Empty client id without clean session
+        (connect(0b00000000, &[0, 0]), Error::InvalidClientId),
+        // Will topic with a wildcard
+        (connect(0b00000110, &[0, 1, b'c', 0, 3, b'a', b'/', b'+', 0, 0]), Error::InvalidTopicName),
+    ];
+    for (data, err) in cases.iter() {
+        assert!(decode_slice_with_options(data, &lenient).unwrap().is_some());
+        assert_eq!(Err(err.clone()), decode_slice_with_options(data, &strict));
+    }
+    let data = connect(0b00000010, &[0, 0]);
+    assert!(decode_slice_with_options(&data, &strict).unwrap().is_some());
+}
+
+#[test]
+fn test_limits() {
+    let opts = DecodeOptions {
+        max_packet_size: Some(8),
+        max_topics: Some(2),
+        max_topic_len: Some(3),
+        max_client_id_len: Some(4),
+        ..DecodeOptions::default()
+    };
+    // Rejected as soon as the length is known.
+    let buf = [0b00110000, 7];
+    assert_eq!(Err(Error::PacketTooLarge), decode_slice_with_options(&buf, &opts));
+    let buf = [0b00110000, 6, 0, 3, b'a', b'/', b'b', b'x'];
+    assert!(decode_slice_with_options(&buf, &opts).unwrap().is_some());
+    let buf = [0b00110000, 6, 0, 4, b'a', b'/', b'b', b'c'];
+    assert_eq!(Err(Error::TopicTooLong), decode_slice_with_options(&buf, &opts));
+
+    let opts = DecodeOptions {
+        max_packet_size: Some(64),
+        ..opts
+    };
+    let buf = [0b10000010, 14, 0, 1, 0, 1, b'a', 0, 0, 1, b'b', 0, 0, 1, b'c', 0];
+    assert_eq!(Err(Error::TooManyTopics), decode_slice_with_options(&buf, &opts));
+    let buf = [0b10100010, 11, 0, 1, 0, 1, b'a', 0, 1, b'b', 0, 1, b'c'];
+    assert_eq!(Err(Error::TooManyTopics), decode_slice_with_options(&buf, &opts));
+    let buf = [0b10100010, 8, 0, 1, 0, 4, b'a', b'b', b'c', b'd'];
+    assert_eq!(Err(Error::TopicTooLong), decode_slice_with_options(&buf, &opts));
+
+    let mut buf = [0u8; 64];
+    let mut connect = Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive: 30,
+        client_id: "12345",
+        clean_session: true,
+        last_will: None,
+        username: None,
+        password: None,
+    };
+    let len = encode_slice(&connect.clone().into(), &mut buf).unwrap();
+    assert_eq!(Err(Error::ClientIdTooLong), decode_slice_with_options(&buf[..len], &opts));
+    connect.client_id = "1234";
+    connect.last_will = Some(LastWill {
+        topic: "will",
+        message: b"",
+        qos: QoS::AtMostOnce,
+        retain: false,
+    });
+    let len = encode_slice(&connect.into(), &mut buf).unwrap();
+    assert_eq!(Err(Error::TopicTooLong), decode_slice_with_options(&buf[..len], &opts));
+}
+
+#[test]
+fn test_long_subscribe_topic() {
+    // Longer than a no_std `LimitedString`, this must not panic.
+    let mut buf = [b'a'; 265];
+    buf[..7].copy_from_slice(&[0b10000010, 0x86, 0x02, 0, 1, 0x01, 0x01]);
+    buf[264] = 0;
+    let result = decode_slice(&buf);
+    #[cfg(feature = "std")]
+    assert!(matches!(result, Ok(Some(Packet::Subscribe(_)))));
+    #[cfg(not(feature = "std"))]
+    assert_eq!(Err(Error::InvalidLength), result);
+}
+
+#[test]
+fn test_generic_capacities() {
+    // Subscribe to 2 topics of 3 bytes.
+    let buf = [0b10000010, 14, 0, 1, 0, 3, b'a', b'/', b'b', 0, 0, 3, b'c', b'/', b'd', 1];
+    let opts = DecodeOptions::default();
+    #[cfg(not(feature = "std"))]
+    {
+        assert_eq!(Err(Error::InvalidLength), GenericPacket::<1, 64>::decode(&buf, &opts));
+        assert_eq!(Err(Error::InvalidLength), GenericPacket::<2, 2>::decode(&buf, &opts));
+    }
+    let (len, packet) = GenericPacket::<2, 3>::decode(&buf, &opts).unwrap().unwrap();
+    assert_eq!(16, len);
+    match &packet {
+        GenericPacket::Subscribe(s) => {
+            assert_eq!(2, s.topics.len());
+            assert_eq!("c/d", s.topics[1].topic_path.as_str());
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let mut out = [0; 16];
+    assert_eq!(Ok(16), packet.encode(&mut out));
+    assert_eq!(buf, out);
+}
+
+#[test]
+fn test_long_protocol_name() {
+    // Longer than a no_std `Error::InvalidProtocol` name, this must not panic.
+    let mut buf = [b'X'; 23];
+    buf[..4].copy_from_slice(&[0b00010000, 21, 0, 18]);
+    match decode_slice(&buf) {
+        Err(Error::InvalidProtocol(name, b'X')) => assert!(name.starts_with("XXXXXXXXXX")),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_short_remaining_len() {
+    use PacketType::*;
+    // Each packet is followed by bytes that would make it valid, if they were read.
+    let cases: &[(&[u8], Error)] = &[
+        // Connack, Puback, Pubrel, Unsuback and Suback
+        (&[0b00100000, 1, 0, 0], Error::InvalidRemainingLength(Connack, 1)),
+        (&[0b01000000, 1, 0, 1], Error::InvalidRemainingLength(Puback, 1)),
+        (&[0b01100010, 0, 0, 1], Error::InvalidRemainingLength(Pubrel, 0)),
+        (&[0b10110000, 1, 0, 1], Error::InvalidRemainingLength(Unsuback, 1)),
+        (&[0b10010000, 1, 0, 1, 0], Error::InvalidLength),
+        // Subscribe without qos, Publish without pid, Connect without keep alive
+        (&[0b10000010, 5, 0, 1, 0, 1, b'a', 0], Error::InvalidLength),
+        (&[0b00110010, 4, 0, 1, b'a', 0, 1], Error::InvalidLength),
+        (&[0b00010000, 8, 0, 4, b'M', b'Q', b'T', b'T', 4, 0, 0, 10, 0, 0], Error::InvalidLength),
+    ];
+    for (buf, err) in cases {
+        assert_eq!(Err(err.clone()), decode_slice(buf), "{:?}", buf);
+    }
+}
+
+#[test]
+fn test_remaining_len_mismatch() {
+    use PacketType::*;
+    let cases: &[(&[u8], PacketType, usize)] = &[
+        (&[0b00100000, 1, 0, 0], Connack, 1),
+        (&[0b00100000, 3, 0, 0, 0], Connack, 3),
+        (&[0b01000000, 1, 0, 1], Puback, 1),
+        (&[0b01000000, 5, 0, 1, 0, 0, 0], Puback, 5),
+        (&[0b01100010, 0, 0, 1], Pubrel, 0),
+        (&[0b10110000, 3, 0, 1, 0], Unsuback, 3),
+        (&[0b11000000, 1, 0], Pingreq, 1),
+        (&[0b11010000, 2, 0, 0], Pingresp, 2),
+        (&[0b11100000, 1, 0], Disconnect, 1),
+        // Connect with a trailing byte after the client id
+        (&[0b00010000, 13, 0, 4, b'M', b'Q', b'T', b'T', 4, 0, 0, 10, 0, 0, 0], Connect, 13),
+    ];
+    for (buf, typ, len) in cases {
+        let expected = Err(Error::InvalidRemainingLength(*typ, *len));
+        assert_eq!(expected, decode_slice(buf), "{:?}", buf);
+    }
+}
+
+#[test]
+fn test_clone_packet_short_output() {
+    let data: &[u8] = &[0b00110000, 3, 0, 1, b'a'];
+    let mut out = [0u8; 5];
+    assert_eq!(Err(Error::WriteZero), clone_packet(data, &mut out[..4]));
+    assert_eq!(Ok(5), clone_packet(data, &mut out));
+    assert_eq!(data, &out[..]);
+}
+
+#[test]
+fn test_no_panic() {
+    // Every header and remaining length, followed by various payloads.
+    let fillers: [&[u8]; 4] = [&[0; 8], &[0xFF; 8], &[0, 1, b'a', 1, 0, 2, 0, 1], &[0, 4, 0x80]];
+    for hd in 0..=255u8 {
+        for len in 0..8u8 {
+            for filler in &fillers {
+                let mut buf = vec![hd, len];
+                buf.extend_from_slice(filler);
+                let _ = decode_slice(&buf);
+                let _ = v5::decode_slice(&buf);
+                let _ = StreamingDecoder::new().decode(&buf);
+            }
+        }
+    }
+}
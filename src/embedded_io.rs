@@ -0,0 +1,134 @@
+use crate::{decoder::read_frame_len, *};
+use embedded_io_async::{Error as _, Read, Write};
+
+/// Async transport of MQTT 3.1.1 packets over [embedded_io_async] `Read` and `Write` traits, as
+/// implemented by embassy-net sockets.
+///
+/// Packets are decoded from a caller-provided receive buffer, which limits the size of received
+/// packets (`Error::PacketTooLarge`), and encoded into a caller-provided transmit buffer. Nothing
+/// is allocated.
+///
+/// ```
+/// # use mqttrs::*;
+/// # futures::executor::block_on(async {
+/// let data: &[u8] = &[0b11010000, 0, 0b11100000, 0];
+/// let (mut rx, mut tx) = ([0; 64], [0; 64]);
+/// let mut transport = FramedTransport::new(data, &mut rx, &mut tx);
+/// assert_eq!(Ok(Some(Packet::Pingresp)), transport.read_packet().await);
+/// assert_eq!(Ok(Some(Packet::Disconnect)), transport.read_packet().await);
+/// assert_eq!(Ok(None), transport.read_packet().await);
+/// # });
+/// ```
+///
+/// [embedded_io_async]: https://docs.rs/embedded-io-async/0.6/embedded_io_async/
+#[derive(Debug)]
+pub struct FramedTransport<'b, T> {
+    io: T,
+    options: DecodeOptions,
+    rx: &'b mut [u8],
+    /// Number of received bytes at the start of `rx`.
+    rx_len: usize,
+    /// Length of the packet returned by the last call to `read_packet()`, at the start of `rx`.
+    consumed: usize,
+    tx: &'b mut [u8],
+}
+
+impl<'b, T> FramedTransport<'b, T> {
+    pub fn new(io: T, rx: &'b mut [u8], tx: &'b mut [u8]) -> Self {
+        Self::with_options(io, rx, tx, DecodeOptions::default())
+    }
+
+    /// Create a transport decoding with the given [DecodeOptions].
+    ///
+    /// [DecodeOptions]: struct.DecodeOptions.html
+    pub fn with_options(
+        io: T,
+        rx: &'b mut [u8],
+        tx: &'b mut [u8],
+        options: DecodeOptions,
+    ) -> Self {
+        FramedTransport {
+            io,
+            options,
+            rx,
+            rx_len: 0,
+            consumed: 0,
+            tx,
+        }
+    }
+
+    /// Get a reference to the underlying transport.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Get a mutable reference to the underlying transport.
+    ///
+    /// Reading from it directly would desynchronize the packet stream.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Return the underlying transport. Data that was received but not returned yet is lost.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+impl<'b, T: Read> FramedTransport<'b, T> {
+    /// Read the next packet, waiting until it has been fully received.
+    ///
+    /// Returns `Ok(None)` when the transport reaches end of file between two packets, and
+    /// `Error::UnexpectedEof` when it does in the middle of a packet.
+    pub async fn read_packet(&mut self) -> Result<Option<Packet<'_>>, Error> {
+        // Move the bytes received after the previous packet to the start of the buffer.
+        self.rx.copy_within(self.consumed..self.rx_len, 0);
+        self.rx_len -= self.consumed;
+        self.consumed = 0;
+
+        // Read until the buffer holds a whole packet.
+        loop {
+            if let Some((header_len, remaining_len)) = read_frame_len(&self.rx[..self.rx_len])? {
+                let frame_len = header_len + remaining_len;
+                self.options.check_packet_size(frame_len)?;
+                if frame_len > self.rx.len() {
+                    return Err(Error::PacketTooLarge);
+                }
+                if self.rx_len >= frame_len {
+                    break;
+                }
+            }
+            if self.rx_len == self.rx.len() {
+                return Err(Error::PacketTooLarge);
+            }
+            let n = self.io.read(&mut self.rx[self.rx_len..]).await;
+            match n.map_err(|e| Error::Transport(e.kind()))? {
+                0 if self.rx_len == 0 => return Ok(None),
+                0 => return Err(Error::UnexpectedEof),
+                n => self.rx_len += n,
+            }
+        }
+
+        match decode_slice_with_options(&self.rx[..self.rx_len], &self.options)? {
+            Some((len, packet)) => {
+                self.consumed = len;
+                Ok(Some(packet))
+            }
+            None => Err(Error::InvalidLength),
+        }
+    }
+}
+
+impl<'b, T: Write> FramedTransport<'b, T> {
+    /// Encode and write a whole packet. It must fit in the transmit buffer (`Error::WriteZero`).
+    pub async fn write_packet(&mut self, packet: &Packet<'_>) -> Result<(), Error> {
+        let len = encode_slice(packet, self.tx)?;
+        let written = self.io.write_all(&self.tx[..len]).await;
+        written.map_err(|e| Error::Transport(e.kind()))
+    }
+
+    /// Flush the underlying transport.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.io.flush().await.map_err(|e| Error::Transport(e.kind()))
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{test_util::publish, *};
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+use futures::executor::block_on;
+
+/// Transport returning at most `chunk` bytes per read, and recording writes.
+struct SlowTransport<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    written: Vec<u8>,
+}
+
+impl ErrorType for SlowTransport<'_> {
+    type Error = ErrorKind;
+}
+
+impl Read for SlowTransport<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        let len = buf.len().min(self.chunk).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+impl Write for SlowTransport<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, ErrorKind> {
+        if self.written.len() > 100 {
+            return Err(ErrorKind::BrokenPipe);
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+fn transport(data: &[u8], chunk: usize) -> SlowTransport<'_> {
+    SlowTransport {
+        data,
+        chunk,
+        written: Vec::new(),
+    }
+}
+
+fn encode(packet: &Packet) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; packet.encoded_len()];
+    encode_slice(packet, &mut buf)?;
+    Ok(buf)
+}
+
+#[test]
+fn test_read() {
+    let mut data = encode(&publish(&[7; 50])).unwrap();
+    data.extend(encode(&Packet::Pingreq).unwrap());
+    data.extend(encode(&publish(b"hello")).unwrap());
+    block_on(async {
+        for &chunk in &[1, 3, 100] {
+            let (mut rx, mut tx) = ([0; 64], [0; 0]);
+            let mut framed = FramedTransport::new(transport(&data, chunk), &mut rx, &mut tx);
+            assert_eq!(Ok(Some(publish(&[7; 50]))), framed.read_packet().await);
+            assert_eq!(Ok(Some(Packet::Pingreq)), framed.read_packet().await);
+            assert_eq!(Ok(Some(publish(b"hello"))), framed.read_packet().await);
+            assert_eq!(Ok(None), framed.read_packet().await);
+        }
+    });
+}
+
+#[test]
+fn test_read_errors() {
+    let data = encode(&publish(&[7; 50])).unwrap();
+    block_on(async {
+        // Too big for the receive buffer.
+        let (mut rx, mut tx) = ([0; 32], [0; 0]);
+        let mut framed = FramedTransport::new(transport(&data, 10), &mut rx, &mut tx);
+        assert_eq!(Err(Error::PacketTooLarge), framed.read_packet().await);
+
+        // Truncated.
+        let (mut rx, mut tx) = ([0; 64], [0; 0]);
+        let mut framed = FramedTransport::new(transport(&data[..20], 10), &mut rx, &mut tx);
+        assert_eq!(Err(Error::UnexpectedEof), framed.read_packet().await);
+    });
+}
+
+#[test]
+fn test_write() {
+    block_on(async {
+        let (mut rx, mut tx) = ([0; 0], [0; 64]);
+        let mut framed = FramedTransport::new(transport(&[], 1), &mut rx, &mut tx);
+        framed.write_packet(&Packet::Pingreq).await.unwrap();
+        framed.write_packet(&publish(b"hello")).await.unwrap();
+        framed.flush().await.unwrap();
+        let mut expected = encode(&Packet::Pingreq).unwrap();
+        expected.extend(encode(&publish(b"hello")).unwrap());
+        assert_eq!(expected, framed.get_ref().written);
+
+        assert_eq!(Err(Error::WriteZero), framed.write_packet(&publish(&[0; 64])).await);
+        framed.get_mut().written = vec![0; 101];
+        let err = framed.write_packet(&Packet::Pingreq).await;
+        assert_eq!(Err(Error::Transport(ErrorKind::BrokenPipe)), err);
+    });
+}
@@ -1,6 +1,93 @@
-use crate::{Error, Packet};
+use crate::{v5, Error, GenericPacket, Packet, VersionedPacket};
+use bytes::{BufMut, BytesMut};
+#[cfg(feature = "std")]
+use std::{vec, vec::Vec};
 
-/// Encode a [Packet] enum into a [BufMut] buffer.
+/// Encode a [Packet] enum into a [BufMut] buffer, returning the number of bytes written.
+///
+/// The packet is encoded into a temporary buffer of [Packet::encoded_len()] bytes, then copied
+/// into `buf`, which may not be contiguous. To encode directly into a buffer, use
+/// [encode_bytes_mut()] or [encode_slice()].
+///
+/// ```
+/// # use mqttrs::*;
+/// # use bytes::BytesMut;
+/// let packet = Packet::from(Publish { dup: false,
+///                                     qospid: QosPid::AtMostOnce,
+///                                     retain: false,
+///                                     topic_name: "test",
+///                                     payload: b"hello" });
+/// let mut buf = BytesMut::with_capacity(packet.encoded_len());
+/// assert_eq!(Ok(13), encode_buf(&packet, &mut buf));
+/// assert_eq!(Ok(2), encode_buf(&Packet::Pingreq, &mut buf));
+/// assert_eq!(15, buf.len());
+/// ```
+///
+/// [Packet]: ../type.Packet.html
+/// [BufMut]: https://docs.rs/bytes/1.0.0/bytes/trait.BufMut.html
+/// [BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
+/// [Packet::encoded_len()]: enum.GenericPacket.html#method.encoded_len
+/// [encode_bytes_mut()]: fn.encode_bytes_mut.html
+/// [encode_slice()]: fn.encode_slice.html
+pub fn encode_buf(packet: &Packet, buf: &mut impl BufMut) -> Result<usize, Error> {
+    let len = packet.encoded_len();
+    if buf.remaining_mut() < len {
+        return Err(Error::WriteZero);
+    }
+    let mut tmp = BytesMut::zeroed(len);
+    let written = encode_slice(packet, &mut tmp)?;
+    buf.put_slice(&tmp[..written]);
+    Ok(written)
+}
+
+/// Encode a [Packet] enum at the end of a [BytesMut], returning the number of bytes written.
+///
+/// `buf` grows by [Packet::encoded_len()] bytes at most, and the packet is encoded in place, so
+/// nothing is allocated if `buf` already has the capacity. On error, `buf` is left unchanged.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use bytes::BytesMut;
+/// let packet = Packet::from(Publish { dup: false,
+///                                     qospid: QosPid::AtMostOnce,
+///                                     retain: false,
+///                                     topic_name: "test",
+///                                     payload: b"hello" });
+/// let mut buf = BytesMut::new();
+/// assert_eq!(Ok(13), encode_bytes_mut(&packet, &mut buf));
+/// assert_eq!(13, buf.len());
+/// ```
+///
+/// [Packet]: ../type.Packet.html
+/// [BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
+/// [Packet::encoded_len()]: enum.GenericPacket.html#method.encoded_len
+pub fn encode_bytes_mut(packet: &Packet, buf: &mut BytesMut) -> Result<usize, Error> {
+    let start = buf.len();
+    buf.resize(start + packet.encoded_len(), 0);
+    match encode_slice(packet, &mut buf[start..]) {
+        Ok(written) => {
+            buf.truncate(start + written);
+            Ok(written)
+        }
+        Err(e) => {
+            buf.truncate(start);
+            Err(e)
+        }
+    }
+}
+
+/// Encode a [Packet] enum into a new `Vec`, allocated once with the exact size.
+///
+/// [Packet]: ../type.Packet.html
+#[cfg(feature = "std")]
+pub fn encode_to_vec(packet: &Packet) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; packet.encoded_len()];
+    let written = encode_slice(packet, &mut buf)?;
+    buf.truncate(written);
+    Ok(buf)
+}
+
+/// Encode a [Packet] enum into a slice, returning the number of bytes written.
 ///
 /// ```
 /// # use mqttrs::*;
@@ -24,22 +111,22 @@ use crate::{Error, Packet};
 ///                    'h' as u8, 'e' as u8, 'l' as u8, 'l' as u8, 'o' as u8]);
 /// ```
 ///
-/// [Packet]: ../enum.Packet.html
-/// [BufMut]: https://docs.rs/bytes/1.0.0/bytes/trait.BufMut.html
-// #[cfg(feature = "std")]
-// pub fn encode_slice(packet: &Packet, buf: impl BufMut) -> Result<usize, Error> {
-//     let mut offset = 0;
-//     encode_slice(packet, buf.bytes_mut(), &mut offset)
-// }
-
+/// [Packet]: ../type.Packet.html
 pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
+    encode_packet(packet, buf)
+}
+
+pub(crate) fn encode_packet<const N: usize, const L: usize>(
+    packet: &GenericPacket<'_, N, L>,
+    buf: &mut [u8],
+) -> Result<usize, Error> {
     let mut offset = 0;
 
     match packet {
-        Packet::Connect(connect) => connect.to_buffer(buf, &mut offset),
-        Packet::Connack(connack) => connack.to_buffer(buf, &mut offset),
-        Packet::Publish(publish) => publish.to_buffer(buf, &mut offset),
-        Packet::Puback(pid) => {
+        GenericPacket::Connect(connect) => connect.to_buffer(buf, &mut offset),
+        GenericPacket::Connack(connack) => connack.to_buffer(buf, &mut offset),
+        GenericPacket::Publish(publish) => publish.to_buffer(buf, &mut offset),
+        GenericPacket::Puback(pid) => {
             check_remaining(buf, &mut offset, 4)?;
             let header: u8 = 0b01000000;
             let length: u8 = 2;
@@ -48,7 +135,7 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             pid.to_buffer(buf, &mut offset)?;
             Ok(4)
         }
-        Packet::Pubrec(pid) => {
+        GenericPacket::Pubrec(pid) => {
             check_remaining(buf, &mut offset, 4)?;
             let header: u8 = 0b01010000;
             let length: u8 = 2;
@@ -57,7 +144,7 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             pid.to_buffer(buf, &mut offset)?;
             Ok(4)
         }
-        Packet::Pubrel(pid) => {
+        GenericPacket::Pubrel(pid) => {
             check_remaining(buf, &mut offset, 4)?;
             let header: u8 = 0b01100010;
             let length: u8 = 2;
@@ -66,7 +153,7 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             pid.to_buffer(buf, &mut offset)?;
             Ok(4)
         }
-        Packet::Pubcomp(pid) => {
+        GenericPacket::Pubcomp(pid) => {
             check_remaining(buf, &mut offset, 4)?;
             let header: u8 = 0b01110000;
             let length: u8 = 2;
@@ -75,10 +162,10 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             pid.to_buffer(buf, &mut offset)?;
             Ok(4)
         }
-        Packet::Subscribe(subscribe) => subscribe.to_buffer(buf, &mut offset),
-        Packet::Suback(suback) => suback.to_buffer(buf, &mut offset),
-        Packet::Unsubscribe(unsub) => unsub.to_buffer(buf, &mut offset),
-        Packet::Unsuback(pid) => {
+        GenericPacket::Subscribe(subscribe) => subscribe.to_buffer(buf, &mut offset),
+        GenericPacket::Suback(suback) => suback.to_buffer(buf, &mut offset),
+        GenericPacket::Unsubscribe(unsub) => unsub.to_buffer(buf, &mut offset),
+        GenericPacket::Unsuback(pid) => {
             check_remaining(buf, &mut offset, 4)?;
             let header: u8 = 0b10110000;
             let length: u8 = 2;
@@ -87,7 +174,7 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             pid.to_buffer(buf, &mut offset)?;
             Ok(4)
         }
-        Packet::Pingreq => {
+        GenericPacket::Pingreq => {
             check_remaining(buf, &mut offset, 2)?;
             let header: u8 = 0b11000000;
             let length: u8 = 0;
@@ -95,7 +182,7 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             write_u8(buf, &mut offset, length)?;
             Ok(2)
         }
-        Packet::Pingresp => {
+        GenericPacket::Pingresp => {
             check_remaining(buf, &mut offset, 2)?;
             let header: u8 = 0b11010000;
             let length: u8 = 0;
@@ -103,7 +190,7 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
             write_u8(buf, &mut offset, length)?;
             Ok(2)
         }
-        Packet::Disconnect => {
+        GenericPacket::Disconnect => {
             check_remaining(buf, &mut offset, 2)?;
             let header: u8 = 0b11100000;
             let length: u8 = 0;
@@ -114,6 +201,16 @@ pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
     }
 }
 
+/// Encode a [VersionedPacket] enum into a slice, using the framing of its protocol version.
+///
+/// [VersionedPacket]: enum.VersionedPacket.html
+pub fn encode_slice_versioned(packet: &VersionedPacket, buf: &mut [u8]) -> Result<usize, Error> {
+    match packet {
+        VersionedPacket::V311(p) => encode_slice(p, buf),
+        VersionedPacket::V5(p) => v5::encode_slice(p, buf),
+    }
+}
+
 /// Check wether buffer has `len` bytes of write capacity left. Use this to return a clean
 /// Result::Err instead of panicking.
 pub(crate) fn check_remaining(buf: &mut [u8], offset: &mut usize, len: usize) -> Result<(), Error> {
@@ -145,18 +242,36 @@ pub(crate) fn write_length(buf: &mut [u8], offset: &mut usize, len: usize) -> Re
         }
         _ => return Err(Error::InvalidLength),
     };
+    write_varint(buf, offset, len)?;
+    Ok(write_len)
+}
+
+/// Write a Variable Byte Integer, as used by the remaining length and MQTT 5 property lengths.
+///
+/// The caller is responsible for checking the value range and buffer size beforehand.
+pub(crate) fn write_varint(buf: &mut [u8], offset: &mut usize, val: usize) -> Result<(), Error> {
     let mut done = false;
-    let mut x = len;
+    let mut x = val;
     while !done {
         let mut byte = (x % 128) as u8;
-        x = x / 128;
+        x /= 128;
         if x > 0 {
-            byte = byte | 128;
+            byte |= 128;
         }
         write_u8(buf, offset, byte)?;
-        done = x <= 0;
+        done = x == 0;
+    }
+    Ok(())
+}
+
+/// Number of bytes needed to encode `val` as a Variable Byte Integer.
+pub(crate) fn varint_len(val: usize) -> usize {
+    match val {
+        0..=127 => 1,
+        128..=16383 => 2,
+        16384..=2097151 => 3,
+        _ => 4,
     }
-    Ok(write_len)
 }
 
 pub(crate) fn write_u8(buf: &mut [u8], offset: &mut usize, val: u8) -> Result<(), Error> {
@@ -170,6 +285,11 @@ pub(crate) fn write_u16(buf: &mut [u8], offset: &mut usize, val: u16) -> Result<
     write_u8(buf, offset, (val & 0xFF) as u8)
 }
 
+pub(crate) fn write_u32(buf: &mut [u8], offset: &mut usize, val: u32) -> Result<(), Error> {
+    write_u16(buf, offset, (val >> 16) as u16)?;
+    write_u16(buf, offset, (val & 0xFFFF) as u16)
+}
+
 pub(crate) fn write_bytes(buf: &mut [u8], offset: &mut usize, bytes: &[u8]) -> Result<(), Error> {
     write_u16(buf, offset, bytes.len() as u16)?;
 
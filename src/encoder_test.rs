@@ -1,10 +1,11 @@
+#![allow(clippy::char_lit_as_u8, clippy::iter_cloned_collect)]
+
 use crate::*;
 use core::convert::TryFrom;
 use subscribe::{LimitedString, LimitedVec};
+#[cfg(not(feature = "std"))]
 use core::str::FromStr;
 
-#[cfg(feature = "std")]
-use bytes::BytesMut;
 
 // macro_rules! assert_decode {
 //     ($res:pat, $pkt:expr) => {
@@ -204,3 +205,210 @@ fn test_disconnect() {
     // assert_decode!(Packet::Disconnect, &Packet::Disconnect);
     assert_decode_slice!(Packet::Disconnect, &Packet::Disconnect, 2);
 }
+
+#[test]
+fn test_connect_mqisdp() {
+    let packet = Connect {
+        protocol: Protocol::MQIsdp,
+        keep_alive: 120,
+        client_id: "imvj",
+        clean_session: true,
+        last_will: None,
+        username: None,
+        password: None,
+    }
+    .into();
+    let mut slice = [0u8; 64];
+    let written = encode_slice(&packet, &mut slice).unwrap();
+    assert_eq!(&slice[2..11], &[0, 6, b'M', b'Q', b'I', b's', b'd', b'p', 3]);
+    assert_eq!(Ok(Some(packet)), decode_slice(&slice[..written]));
+}
+
+#[test]
+fn test_encode_versioned() {
+    let mut slice = [0u8; 64];
+    let packet = VersionedPacket::V311(Packet::Disconnect);
+    assert_eq!(Ok(2), encode_slice_versioned(&packet, &mut slice));
+    assert_eq!(&slice[..2], &[0b11100000, 0]);
+
+    let packet = VersionedPacket::V5(v5::Packet::Disconnect(v5::Disconnect {
+        reason_code: v5::ReasonCode::DisconnectWithWillMessage,
+        properties: v5::Properties::new(),
+    }));
+    assert_eq!(Ok(3), encode_slice_versioned(&packet, &mut slice));
+    assert_eq!(&slice[..3], &[0b11100000, 1, 0x04]);
+
+    // MQTT 5 connect packets can't be encoded with the 3.1.1 model.
+    let packet = Connect {
+        protocol: Protocol::MQTT5,
+        keep_alive: 120,
+        client_id: "imvj",
+        clean_session: true,
+        last_will: None,
+        username: None,
+        password: None,
+    }
+    .into();
+    assert!(matches!(
+        encode_slice(&packet, &mut slice),
+        Err(Error::InvalidProtocol(_, 5))
+    ));
+}
+
+#[test]
+fn test_invalid_topics() {
+    let mut slice = [0u8; 64];
+    for topic_name in ["", "a/+", "#", "a\0"].iter() {
+        let packet = Publish {
+            dup: false,
+            qospid: QosPid::AtMostOnce,
+            retain: false,
+            topic_name,
+            payload: b"",
+        }
+        .into();
+        assert_eq!(Err(Error::InvalidTopicName), encode_slice(&packet, &mut slice));
+    }
+    let pid = Pid::try_from(10).unwrap();
+    let topic = SubscribeTopic {
+        topic_path: "a/#/b".parse().unwrap(),
+        qos: QoS::AtMostOnce,
+    };
+    let packet = Subscribe::new(pid, core::iter::once(topic).collect()).into();
+    assert_eq!(Err(Error::InvalidTopicFilter), encode_slice(&packet, &mut slice));
+    let topics = core::iter::once(LimitedString::new()).collect();
+    let packet = Unsubscribe::new(pid, topics).into();
+    assert_eq!(Err(Error::InvalidTopicFilter), encode_slice(&packet, &mut slice));
+}
+
+#[test]
+fn test_encoded_len() {
+    let pid = Pid::try_from(10).unwrap();
+    let topic = SubscribeTopic {
+        topic_path: "a/b".parse().unwrap(),
+        qos: QoS::AtMostOnce,
+    };
+    let return_codes = core::iter::once(SubscribeReturnCodes::Failure).collect();
+    let packets: [Packet; 9] = [
+        Connect {
+            protocol: Protocol::MQIsdp,
+            keep_alive: 120,
+            client_id: "imvj",
+            clean_session: true,
+            last_will: Some(LastWill {
+                topic: "will",
+                message: b"bye",
+                qos: QoS::AtLeastOnce,
+                retain: false,
+            }),
+            username: Some("user"),
+            password: Some(b"pass"),
+        }
+        .into(),
+        Connack {
+            session_present: false,
+            code: ConnectReturnCode::Accepted,
+        }
+        .into(),
+        Publish {
+            dup: false,
+            qospid: QosPid::AtLeastOnce(pid),
+            retain: false,
+            topic_name: "a/b",
+            payload: &[0; 200],
+        }
+        .into(),
+        Packet::Pubrel(pid),
+        Subscribe::new(pid, core::iter::once(topic).collect()).into(),
+        Suback::new(pid, return_codes).into(),
+        Unsubscribe::new(pid, core::iter::once("a/b".parse().unwrap()).collect()).into(),
+        Packet::Unsuback(pid),
+        Packet::Disconnect,
+    ];
+    let mut slice = [0u8; 512];
+    for packet in packets.iter() {
+        let len = encode_slice(packet, &mut slice).unwrap();
+        assert_eq!(len, packet.encoded_len(), "{:?}", packet);
+
+        let mut buf = bytes::BytesMut::new();
+        assert_eq!(Ok(len), encode_buf(packet, &mut buf));
+        assert_eq!(&slice[..len], &buf[..]);
+        let mut buf = bytes::BytesMut::new();
+        assert_eq!(Ok(len), encode_bytes_mut(packet, &mut buf));
+        assert_eq!(&slice[..len], &buf[..]);
+        #[cfg(feature = "std")]
+        assert_eq!(Ok(slice[..len].to_vec()), encode_to_vec(packet));
+    }
+}
+
+#[test]
+fn test_encode_buf() {
+    let packet = Packet::Pingresp;
+    let mut slice = [0u8; 3];
+    let mut buf = &mut slice[..];
+    assert_eq!(Ok(2), encode_buf(&packet, &mut buf));
+    assert_eq!(Err(Error::WriteZero), encode_buf(&packet, &mut buf));
+    assert_eq!([0b11010000, 0, 0], slice);
+}
+
+#[test]
+fn test_encode_bytes_mut() {
+    let publish = Publish {
+        dup: false,
+        qospid: QosPid::AtMostOnce,
+        retain: false,
+        topic_name: "a/b",
+        payload: &[7; 300],
+    };
+    let packet = Packet::from(publish.clone());
+    // Encoding into a presized buffer doesn't allocate.
+    let mut buf = bytes::BytesMut::with_capacity(2 + packet.encoded_len());
+    buf.extend_from_slice(&[1, 2]);
+    let (capacity, ptr) = (buf.capacity(), buf.as_ptr());
+    assert_eq!(Ok(packet.encoded_len()), encode_bytes_mut(&packet, &mut buf));
+    assert_eq!((capacity, ptr), (buf.capacity(), buf.as_ptr()));
+    assert_eq!(Ok(Some(packet)), decode_slice(&buf[2..]));
+
+    // Errors leave the buffer unchanged.
+    let invalid = Packet::from(Publish {
+        topic_name: "a/+",
+        ..publish
+    });
+    let mut buf = bytes::BytesMut::from(&[1, 2][..]);
+    assert_eq!(Err(Error::InvalidTopicName), encode_bytes_mut(&invalid, &mut buf));
+    assert_eq!(&[1, 2][..], &buf[..]);
+}
+
+#[test]
+fn test_publish_vectored() {
+    let payload = [7u8; 300];
+    let publish = Publish {
+        dup: true,
+        qospid: QosPid::ExactlyOnce(Pid::try_from(10).unwrap()),
+        retain: true,
+        topic_name: "a/b",
+        payload: &payload,
+    };
+    let mut slice = [0u8; 512];
+    let len = encode_slice(&publish.clone().into(), &mut slice).unwrap();
+
+    let mut header = [0u8; 16];
+    let header_len = publish.encode_header(&mut header).unwrap();
+    assert_eq!(10, header_len);
+    assert_eq!(header_len, publish.header_len());
+    assert_eq!(&slice[..header_len], &header[..header_len]);
+    assert_eq!(Err(Error::WriteZero), publish.encode_header(&mut [0; 9]));
+
+    let mut chain = publish.encode_chain(&mut header).unwrap();
+    assert_eq!(len, bytes::Buf::remaining(&chain));
+    assert_eq!(&slice[..len], &bytes::Buf::copy_to_bytes(&mut chain, len)[..]);
+
+    #[cfg(feature = "std")]
+    {
+        use std::io::Write;
+        let mut vec = Vec::new();
+        let slices = publish.encode_vectored(&mut header).unwrap();
+        assert_eq!(len, vec.write_vectored(&slices).unwrap());
+        assert_eq!(&slice[..len], &vec[..]);
+    }
+}
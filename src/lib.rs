@@ -5,7 +5,8 @@
 //! [bytes::BytesMut] struct. Convert between the two using [encode()] and [decode()]. Almost all
 //! struct fields can be accessed directly, to create or read packets.
 //!
-//! It currently targets [MQTT 3.1], with [MQTT 5] support planned.
+//! It targets [MQTT 3.1], and [MQTT 5] through the [v5] module. Use [decode_slice_versioned()]
+//! to handle peers of either version.
 //!
 //! ```
 //! use mqttrs::*;
@@ -39,9 +40,11 @@
 //! [MQTT 3.1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html
 //! [MQTT 5]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html
 //! [tokio]: https://tokio.rs/
-//! [Packet]: enum.Packet.html
+//! [Packet]: type.Packet.html
 //! [encode_slice()]: fn.encode_slice.html
 //! [decode_slice()]: fn.decode_slice.html
+//! [decode_slice_versioned()]: fn.decode_slice_versioned.html
+//! [v5]: v5/index.html
 //! [bytes::BytesMut]: https://docs.rs/bytes/1.0.0/bytes/struct.BytesMut.html
 
 #![cfg_attr(not(test), no_std)]
@@ -49,30 +52,110 @@
 #[cfg(feature = "std")]
 extern crate std;
 
+#[cfg(feature = "broker")]
+pub mod broker;
+mod bytes_packet;
+#[cfg(feature = "tokio")]
+pub mod client;
+mod client_session;
 mod connect;
 mod decoder;
+#[cfg(feature = "embedded-io-async")]
+mod embedded_io;
 mod encoder;
+#[cfg(feature = "std")]
+mod owned;
+mod owned_heapless;
 mod packet;
 mod publish;
+mod qos_flows;
+#[cfg(feature = "std")]
+mod retained;
+mod server_session;
+#[cfg(feature = "std")]
+mod std_io;
+mod streaming;
 mod subscribe;
+mod subscription_tree;
+#[cfg(feature = "tokio")]
+mod tokio_codec;
+mod topic;
 mod utils;
+pub mod v5;
 
 // Proptest does not currently support borrowed data in strategies:
 // https://github.com/AltSysrq/proptest/issues/9
 //
 // #[cfg(test)]
 // mod codec_test;
+#[cfg(all(test, feature = "broker"))]
+mod broker_test;
+#[cfg(test)]
+mod bytes_packet_test;
+#[cfg(all(test, feature = "tokio"))]
+mod client_test;
+#[cfg(test)]
+mod client_session_test;
 #[cfg(test)]
 mod decoder_test;
+#[cfg(all(test, feature = "embedded-io-async"))]
+mod embedded_io_test;
 #[cfg(test)]
 mod encoder_test;
+#[cfg(test)]
+mod owned_test;
+#[cfg(all(test, feature = "std"))]
+mod retained_test;
+#[cfg(test)]
+mod server_session_test;
+#[cfg(all(test, feature = "std"))]
+mod std_io_test;
+#[cfg(test)]
+mod streaming_test;
+#[cfg(test)]
+mod subscription_tree_test;
+#[cfg(test)]
+mod test_util;
+#[cfg(all(test, feature = "tokio"))]
+mod tokio_codec_test;
+#[cfg(test)]
+mod topic_test;
 
 pub use crate::{
+    bytes_packet::{
+        decode_bytes, decode_bytes_with_options, BytesConnect, BytesLastWill, BytesPacket,
+        BytesPublish,
+    },
+    client_session::{ClientEvent, ClientSession},
     connect::{Connack, Connect, ConnectReturnCode, LastWill, Protocol},
-    decoder::{clone_packet, decode_slice, decode_slice_with_len},
-    encoder::encode_slice,
-    packet::{Packet, PacketType},
+    decoder::{
+        clone_packet, decode_slice, decode_slice_versioned, decode_slice_with_len,
+        decode_slice_with_options, DecodeOptions,
+    },
+    encoder::{encode_buf, encode_bytes_mut, encode_slice, encode_slice_versioned},
+    owned_heapless::{HeaplessConnect, HeaplessLastWill, HeaplessPacket, HeaplessPublish},
+    packet::{GenericPacket, Packet, PacketType, VersionedPacket},
     publish::Publish,
-    subscribe::{Suback, Subscribe, SubscribeReturnCodes, SubscribeTopic, Unsubscribe},
+    server_session::{ServerEvent, ServerSession},
+    streaming::{PublishHeader, StreamEvent, StreamingDecoder},
+    subscribe::{
+        GenericSuback, GenericSubscribe, GenericSubscribeTopic, GenericUnsubscribe, Suback,
+        Subscribe, SubscribeReturnCodes, SubscribeTopic, Unsubscribe,
+    },
+    subscription_tree::{SubscriptionTree, TreeVec},
+    topic::TopicFilter,
     utils::{Error, Pid, QoS, QosPid},
 };
+
+#[cfg(feature = "embedded-io-async")]
+pub use crate::embedded_io::FramedTransport;
+#[cfg(feature = "std")]
+pub use crate::encoder::encode_to_vec;
+#[cfg(feature = "std")]
+pub use crate::owned::{OwnedConnect, OwnedLastWill, OwnedPacket, OwnedPublish};
+#[cfg(feature = "std")]
+pub use crate::retained::{MemoryRetainedStore, RetainedStore};
+#[cfg(feature = "std")]
+pub use crate::std_io::{PacketReader, PacketWriter};
+#[cfg(feature = "tokio")]
+pub use crate::tokio_codec::MqttCodec;
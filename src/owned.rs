@@ -0,0 +1,241 @@
+use crate::*;
+use std::{string::String, vec::Vec};
+
+/// Owned version of [Packet], that doesn't borrow from the decoding buffer.
+///
+/// Only the `Connect` and `Publish` variants differ from `Packet`, the other variants already own
+/// their data. See [HeaplessPacket] for a `no_std` equivalent.
+///
+/// ```
+/// # use mqttrs::*;
+/// let mut buf = [0b00110000, 10, 0, 3, b'a', b'/', b'b', b'h', b'e', b'l', b'l', b'o'];
+/// let owned = decode_slice(&buf).unwrap().unwrap().to_owned();
+/// // The receive buffer can now be reused.
+/// buf.fill(0);
+/// match owned.as_borrowed() {
+///     Packet::Publish(p) => assert_eq!(b"hello", p.payload),
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// ```
+///
+/// [Packet]: type.Packet.html
+/// [HeaplessPacket]: enum.HeaplessPacket.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedPacket {
+    Connect(OwnedConnect),
+    Connack(Connack),
+    Publish(OwnedPublish),
+    Puback(Pid),
+    Pubrec(Pid),
+    Pubrel(Pid),
+    Pubcomp(Pid),
+    Subscribe(Subscribe),
+    Suback(Suback),
+    Unsubscribe(Unsubscribe),
+    Unsuback(Pid),
+    Pingreq,
+    Pingresp,
+    Disconnect,
+}
+
+/// Owned version of [Connect].
+///
+/// [Connect]: struct.Connect.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedConnect {
+    pub protocol: Protocol,
+    pub keep_alive: u16,
+    pub client_id: String,
+    pub clean_session: bool,
+    pub last_will: Option<OwnedLastWill>,
+    pub username: Option<String>,
+    pub password: Option<Vec<u8>>,
+}
+
+/// Owned version of [LastWill].
+///
+/// [LastWill]: struct.LastWill.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedLastWill {
+    pub topic: String,
+    pub message: Vec<u8>,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// Owned version of [Publish].
+///
+/// [Publish]: struct.Publish.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedPublish {
+    pub dup: bool,
+    pub qospid: QosPid,
+    pub retain: bool,
+    pub topic_name: String,
+    pub payload: Vec<u8>,
+}
+
+impl OwnedPacket {
+    /// Return the packet type variant.
+    pub fn get_type(&self) -> PacketType {
+        match self {
+            OwnedPacket::Connect(_) => PacketType::Connect,
+            OwnedPacket::Connack(_) => PacketType::Connack,
+            OwnedPacket::Publish(_) => PacketType::Publish,
+            OwnedPacket::Puback(_) => PacketType::Puback,
+            OwnedPacket::Pubrec(_) => PacketType::Pubrec,
+            OwnedPacket::Pubrel(_) => PacketType::Pubrel,
+            OwnedPacket::Pubcomp(_) => PacketType::Pubcomp,
+            OwnedPacket::Subscribe(_) => PacketType::Subscribe,
+            OwnedPacket::Suback(_) => PacketType::Suback,
+            OwnedPacket::Unsubscribe(_) => PacketType::Unsubscribe,
+            OwnedPacket::Unsuback(_) => PacketType::Unsuback,
+            OwnedPacket::Pingreq => PacketType::Pingreq,
+            OwnedPacket::Pingresp => PacketType::Pingresp,
+            OwnedPacket::Disconnect => PacketType::Disconnect,
+        }
+    }
+
+    /// Borrow this packet as a [Packet], for example to encode it.
+    ///
+    /// [Packet]: type.Packet.html
+    pub fn as_borrowed(&self) -> Packet<'_> {
+        match self {
+            OwnedPacket::Connect(c) => Packet::Connect(c.as_borrowed()),
+            OwnedPacket::Connack(c) => Packet::Connack(*c),
+            OwnedPacket::Publish(p) => Packet::Publish(p.as_borrowed()),
+            OwnedPacket::Puback(pid) => Packet::Puback(*pid),
+            OwnedPacket::Pubrec(pid) => Packet::Pubrec(*pid),
+            OwnedPacket::Pubrel(pid) => Packet::Pubrel(*pid),
+            OwnedPacket::Pubcomp(pid) => Packet::Pubcomp(*pid),
+            OwnedPacket::Subscribe(s) => Packet::Subscribe(s.clone()),
+            OwnedPacket::Suback(s) => Packet::Suback(s.clone()),
+            OwnedPacket::Unsubscribe(u) => Packet::Unsubscribe(u.clone()),
+            OwnedPacket::Unsuback(pid) => Packet::Unsuback(*pid),
+            OwnedPacket::Pingreq => Packet::Pingreq,
+            OwnedPacket::Pingresp => Packet::Pingresp,
+            OwnedPacket::Disconnect => Packet::Disconnect,
+        }
+    }
+}
+
+impl OwnedConnect {
+    /// Borrow this packet as a [Connect].
+    ///
+    /// [Connect]: struct.Connect.html
+    pub fn as_borrowed(&self) -> Connect<'_> {
+        Connect {
+            protocol: self.protocol,
+            keep_alive: self.keep_alive,
+            client_id: &self.client_id,
+            clean_session: self.clean_session,
+            last_will: self.last_will.as_ref().map(|w| LastWill {
+                topic: &w.topic,
+                message: &w.message,
+                qos: w.qos,
+                retain: w.retain,
+            }),
+            username: self.username.as_deref(),
+            password: self.password.as_deref(),
+        }
+    }
+}
+
+impl OwnedPublish {
+    /// Borrow this packet as a [Publish].
+    ///
+    /// [Publish]: struct.Publish.html
+    pub fn as_borrowed(&self) -> Publish<'_> {
+        Publish {
+            dup: self.dup,
+            qospid: self.qospid,
+            retain: self.retain,
+            topic_name: &self.topic_name,
+            payload: &self.payload,
+        }
+    }
+}
+
+impl<'a> From<Packet<'a>> for OwnedPacket {
+    fn from(p: Packet<'a>) -> Self {
+        match p {
+            Packet::Connect(c) => OwnedPacket::Connect(c.into()),
+            Packet::Connack(c) => OwnedPacket::Connack(c),
+            Packet::Publish(p) => OwnedPacket::Publish(p.into()),
+            Packet::Puback(pid) => OwnedPacket::Puback(pid),
+            Packet::Pubrec(pid) => OwnedPacket::Pubrec(pid),
+            Packet::Pubrel(pid) => OwnedPacket::Pubrel(pid),
+            Packet::Pubcomp(pid) => OwnedPacket::Pubcomp(pid),
+            Packet::Subscribe(s) => OwnedPacket::Subscribe(s),
+            Packet::Suback(s) => OwnedPacket::Suback(s),
+            Packet::Unsubscribe(u) => OwnedPacket::Unsubscribe(u),
+            Packet::Unsuback(pid) => OwnedPacket::Unsuback(pid),
+            Packet::Pingreq => OwnedPacket::Pingreq,
+            Packet::Pingresp => OwnedPacket::Pingresp,
+            Packet::Disconnect => OwnedPacket::Disconnect,
+        }
+    }
+}
+
+impl<'a> From<Connect<'a>> for OwnedConnect {
+    fn from(c: Connect<'a>) -> Self {
+        OwnedConnect {
+            protocol: c.protocol,
+            keep_alive: c.keep_alive,
+            client_id: c.client_id.into(),
+            clean_session: c.clean_session,
+            last_will: c.last_will.map(Into::into),
+            username: c.username.map(Into::into),
+            password: c.password.map(Into::into),
+        }
+    }
+}
+
+impl<'a> From<LastWill<'a>> for OwnedLastWill {
+    fn from(w: LastWill<'a>) -> Self {
+        OwnedLastWill {
+            topic: w.topic.into(),
+            message: w.message.into(),
+            qos: w.qos,
+            retain: w.retain,
+        }
+    }
+}
+
+impl<'a> From<Publish<'a>> for OwnedPublish {
+    fn from(p: Publish<'a>) -> Self {
+        OwnedPublish {
+            dup: p.dup,
+            qospid: p.qospid,
+            retain: p.retain,
+            topic_name: p.topic_name.into(),
+            payload: p.payload.into(),
+        }
+    }
+}
+
+macro_rules! owned_packet_from {
+    ($($t:ident),+) => {
+        $(
+            impl From<$t> for OwnedPacket {
+                fn from(p: $t) -> Self {
+                    OwnedPacket::$t(p)
+                }
+            }
+        )+
+    }
+}
+
+owned_packet_from!(Suback, Connack, Subscribe, Unsubscribe);
+
+impl From<OwnedConnect> for OwnedPacket {
+    fn from(p: OwnedConnect) -> Self {
+        OwnedPacket::Connect(p)
+    }
+}
+
+impl From<OwnedPublish> for OwnedPacket {
+    fn from(p: OwnedPublish) -> Self {
+        OwnedPacket::Publish(p)
+    }
+}
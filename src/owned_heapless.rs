@@ -0,0 +1,221 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+
+use crate::*;
+use core::convert::TryFrom;
+use heapless::{String, Vec};
+
+/// Owned version of [Packet] with fixed-capacity heapless storage, for `no_std` targets.
+///
+/// Strings (topic names, client id, username) hold up to `N` bytes, and binary data (payloads,
+/// password) up to `P` bytes. Converting a [Packet] that doesn't fit returns `Error::WriteZero`.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use core::convert::TryFrom;
+/// let buf = [0b00110000, 10, 0, 3, b'a', b'/', b'b', b'h', b'e', b'l', b'l', b'o'];
+/// let owned = {
+///     let packet = decode_slice(&buf).unwrap().unwrap();
+///     packet.to_heapless::<16, 64>().unwrap()
+/// };
+/// assert_eq!(Ok(12), encode_slice(&owned.as_borrowed(), &mut [0; 64]));
+/// assert_eq!(Err(Error::WriteZero), HeaplessPacket::<16, 4>::try_from(owned.as_borrowed()));
+/// ```
+///
+/// [Packet]: type.Packet.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaplessPacket<const N: usize, const P: usize> {
+    Connect(HeaplessConnect<N, P>),
+    Connack(Connack),
+    Publish(HeaplessPublish<N, P>),
+    Puback(Pid),
+    Pubrec(Pid),
+    Pubrel(Pid),
+    Pubcomp(Pid),
+    Subscribe(Subscribe),
+    Suback(Suback),
+    Unsubscribe(Unsubscribe),
+    Unsuback(Pid),
+    Pingreq,
+    Pingresp,
+    Disconnect,
+}
+
+/// Heapless version of [Connect].
+///
+/// [Connect]: struct.Connect.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaplessConnect<const N: usize, const P: usize> {
+    pub protocol: Protocol,
+    pub keep_alive: u16,
+    pub client_id: String<N>,
+    pub clean_session: bool,
+    pub last_will: Option<HeaplessLastWill<N, P>>,
+    pub username: Option<String<N>>,
+    pub password: Option<Vec<u8, P>>,
+}
+
+/// Heapless version of [LastWill].
+///
+/// [LastWill]: struct.LastWill.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaplessLastWill<const N: usize, const P: usize> {
+    pub topic: String<N>,
+    pub message: Vec<u8, P>,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+/// Heapless version of [Publish].
+///
+/// [Publish]: struct.Publish.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaplessPublish<const N: usize, const P: usize> {
+    pub dup: bool,
+    pub qospid: QosPid,
+    pub retain: bool,
+    pub topic_name: String<N>,
+    pub payload: Vec<u8, P>,
+}
+
+fn string<const N: usize>(s: &str) -> Result<String<N>, Error> {
+    let mut string = String::new();
+    string.push_str(s).map_err(|_| Error::WriteZero)?;
+    Ok(string)
+}
+
+fn bytes<const P: usize>(b: &[u8]) -> Result<Vec<u8, P>, Error> {
+    Vec::from_slice(b).map_err(|_| Error::WriteZero)
+}
+
+impl<const N: usize, const P: usize> HeaplessPacket<N, P> {
+    /// Return the packet type variant.
+    pub fn get_type(&self) -> PacketType {
+        self.as_borrowed().get_type()
+    }
+
+    /// Borrow this packet as a [Packet], for example to encode it.
+    ///
+    /// [Packet]: type.Packet.html
+    pub fn as_borrowed(&self) -> Packet<'_> {
+        match self {
+            HeaplessPacket::Connect(c) => Packet::Connect(c.as_borrowed()),
+            HeaplessPacket::Connack(c) => Packet::Connack(*c),
+            HeaplessPacket::Publish(p) => Packet::Publish(p.as_borrowed()),
+            HeaplessPacket::Puback(pid) => Packet::Puback(*pid),
+            HeaplessPacket::Pubrec(pid) => Packet::Pubrec(*pid),
+            HeaplessPacket::Pubrel(pid) => Packet::Pubrel(*pid),
+            HeaplessPacket::Pubcomp(pid) => Packet::Pubcomp(*pid),
+            HeaplessPacket::Subscribe(s) => Packet::Subscribe(s.clone()),
+            HeaplessPacket::Suback(s) => Packet::Suback(s.clone()),
+            HeaplessPacket::Unsubscribe(u) => Packet::Unsubscribe(u.clone()),
+            HeaplessPacket::Unsuback(pid) => Packet::Unsuback(*pid),
+            HeaplessPacket::Pingreq => Packet::Pingreq,
+            HeaplessPacket::Pingresp => Packet::Pingresp,
+            HeaplessPacket::Disconnect => Packet::Disconnect,
+        }
+    }
+}
+
+impl<const N: usize, const P: usize> HeaplessConnect<N, P> {
+    /// Borrow this packet as a [Connect].
+    ///
+    /// [Connect]: struct.Connect.html
+    pub fn as_borrowed(&self) -> Connect<'_> {
+        Connect {
+            protocol: self.protocol,
+            keep_alive: self.keep_alive,
+            client_id: &self.client_id,
+            clean_session: self.clean_session,
+            last_will: self.last_will.as_ref().map(|w| LastWill {
+                topic: &w.topic,
+                message: &w.message,
+                qos: w.qos,
+                retain: w.retain,
+            }),
+            username: self.username.as_deref(),
+            password: self.password.as_deref(),
+        }
+    }
+}
+
+impl<const N: usize, const P: usize> HeaplessPublish<N, P> {
+    /// Borrow this packet as a [Publish].
+    ///
+    /// [Publish]: struct.Publish.html
+    pub fn as_borrowed(&self) -> Publish<'_> {
+        Publish {
+            dup: self.dup,
+            qospid: self.qospid,
+            retain: self.retain,
+            topic_name: &self.topic_name,
+            payload: &self.payload,
+        }
+    }
+}
+
+impl<'a, const N: usize, const P: usize> TryFrom<Packet<'a>> for HeaplessPacket<N, P> {
+    type Error = Error;
+
+    fn try_from(p: Packet<'a>) -> Result<Self, Error> {
+        Ok(match p {
+            Packet::Connect(c) => HeaplessPacket::Connect(HeaplessConnect::try_from(c)?),
+            Packet::Connack(c) => HeaplessPacket::Connack(c),
+            Packet::Publish(p) => HeaplessPacket::Publish(HeaplessPublish::try_from(p)?),
+            Packet::Puback(pid) => HeaplessPacket::Puback(pid),
+            Packet::Pubrec(pid) => HeaplessPacket::Pubrec(pid),
+            Packet::Pubrel(pid) => HeaplessPacket::Pubrel(pid),
+            Packet::Pubcomp(pid) => HeaplessPacket::Pubcomp(pid),
+            Packet::Subscribe(s) => HeaplessPacket::Subscribe(s),
+            Packet::Suback(s) => HeaplessPacket::Suback(s),
+            Packet::Unsubscribe(u) => HeaplessPacket::Unsubscribe(u),
+            Packet::Unsuback(pid) => HeaplessPacket::Unsuback(pid),
+            Packet::Pingreq => HeaplessPacket::Pingreq,
+            Packet::Pingresp => HeaplessPacket::Pingresp,
+            Packet::Disconnect => HeaplessPacket::Disconnect,
+        })
+    }
+}
+
+impl<'a, const N: usize, const P: usize> TryFrom<Connect<'a>> for HeaplessConnect<N, P> {
+    type Error = Error;
+
+    fn try_from(c: Connect<'a>) -> Result<Self, Error> {
+        let last_will = match c.last_will {
+            Some(w) => Some(HeaplessLastWill {
+                topic: string(w.topic)?,
+                message: bytes(w.message)?,
+                qos: w.qos,
+                retain: w.retain,
+            }),
+            None => None,
+        };
+        Ok(HeaplessConnect {
+            protocol: c.protocol,
+            keep_alive: c.keep_alive,
+            client_id: string(c.client_id)?,
+            clean_session: c.clean_session,
+            last_will,
+            username: c.username.map(string).transpose()?,
+            password: c.password.map(bytes).transpose()?,
+        })
+    }
+}
+
+impl<'a, const N: usize, const P: usize> TryFrom<Publish<'a>> for HeaplessPublish<N, P> {
+    type Error = Error;
+
+    fn try_from(p: Publish<'a>) -> Result<Self, Error> {
+        Ok(HeaplessPublish {
+            dup: p.dup,
+            qospid: p.qospid,
+            retain: p.retain,
+            topic_name: string(p.topic_name)?,
+            payload: bytes(p.payload)?,
+        })
+    }
+}
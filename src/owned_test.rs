@@ -0,0 +1,69 @@
+use crate::*;
+use core::convert::TryFrom;
+
+fn connect() -> Packet<'static> {
+    Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive: 30,
+        client_id: "test",
+        clean_session: false,
+        last_will: Some(LastWill {
+            topic: "will/topic",
+            message: b"bye",
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }),
+        username: Some("user"),
+        password: Some(b"pass"),
+    }
+    .into()
+}
+
+fn publish() -> Packet<'static> {
+    Publish {
+        dup: true,
+        qospid: QosPid::ExactlyOnce(Pid::try_from(7).unwrap()),
+        retain: false,
+        topic_name: "a/b",
+        payload: b"hello",
+    }
+    .into()
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_owned() {
+    for packet in [connect(), publish(), Packet::Puback(Pid::new()), Packet::Pingreq].iter() {
+        let owned = packet.to_owned();
+        assert_eq!(packet.get_type(), owned.get_type());
+        assert_eq!(packet, &owned.as_borrowed());
+    }
+
+    // The owned packet outlives the decoding buffer.
+    let owned = {
+        let mut buf = [0u8; 64];
+        let len = encode_slice(&publish(), &mut buf).unwrap();
+        decode_slice(&buf[..len]).unwrap().unwrap().to_owned()
+    };
+    match owned {
+        OwnedPacket::Publish(ref p) => {
+            assert_eq!("a/b", p.topic_name);
+            assert_eq!(b"hello".to_vec(), p.payload);
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(publish(), owned.as_borrowed());
+}
+
+#[test]
+fn test_heapless() {
+    for packet in [connect(), publish(), Packet::Puback(Pid::new()), Packet::Pingreq].iter() {
+        let owned = packet.to_heapless::<16, 8>().unwrap();
+        assert_eq!(packet.get_type(), owned.get_type());
+        assert_eq!(packet, &owned.as_borrowed());
+    }
+    assert_eq!(Err(Error::WriteZero), connect().to_heapless::<9, 8>());
+    assert_eq!(Err(Error::WriteZero), connect().to_heapless::<16, 3>());
+    assert_eq!(Err(Error::WriteZero), HeaplessPacket::<2, 8>::try_from(publish()));
+    assert_eq!(Err(Error::WriteZero), HeaplessPacket::<16, 4>::try_from(publish()));
+}
@@ -3,33 +3,16 @@ use defmt::Format;
 
 use crate::*;
 
-/// Base enum for all MQTT packet types.
+/// [Packet] with `Subscribe`, `Suback` and `Unsubscribe` packets holding up to `N` topics of up to
+/// `L` bytes without the `std` feature.
 ///
-/// This is the main type you'll be interacting with, as an output of [`decode_slice()`] and an input of
-/// [`encode()`]. Most variants can be constructed directly without using methods.
+/// Use [`decode()`](#method.decode) and [`encode()`](#method.encode) for other capacities than the
+/// default ones of [Packet].
 ///
-/// ```
-/// # use mqttrs::*;
-/// # use core::convert::TryFrom;
-/// // Simplest form
-/// let pkt = Packet::Connack(Connack { session_present: false,
-///                                     code: ConnectReturnCode::Accepted });
-/// // Using `Into` trait
-/// let publish = Publish { dup: false,
-///                         qospid: QosPid::AtMostOnce,
-///                         retain: false,
-///                         topic_name: "to/pic",
-///                         payload: b"payload" };
-/// let pkt: Packet = publish.into();
-/// // Identifyer-only packets
-/// let pkt = Packet::Puback(Pid::try_from(42).unwrap());
-/// ```
-///
-/// [`encode()`]: fn.encode.html
-/// [`decode_slice()`]: fn.decode_slice.html
+/// [Packet]: type.Packet.html
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, PartialEq)]
-pub enum Packet<'a> {
+pub enum GenericPacket<'a, const N: usize, const L: usize> {
     /// [MQTT 3.1](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718028)
     Connect(Connect<'a>),
     /// [MQTT 3.2](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718033)
@@ -45,11 +28,11 @@ pub enum Packet<'a> {
     /// [MQTT 3.7](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718058)
     Pubcomp(Pid),
     /// [MQTT 3.8](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718063)
-    Subscribe(Subscribe),
+    Subscribe(GenericSubscribe<N, L>),
     /// [MQTT 3.9](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718068)
-    Suback(Suback),
+    Suback(GenericSuback<N>),
     /// [MQTT 3.10](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718072)
-    Unsubscribe(Unsubscribe),
+    Unsubscribe(GenericUnsubscribe<N, L>),
     /// [MQTT 3.11](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718077)
     Unsuback(Pid),
     /// [MQTT 3.12](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718081)
@@ -59,48 +42,160 @@ pub enum Packet<'a> {
     /// [MQTT 3.14](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718090)
     Disconnect,
 }
-impl<'a> Packet<'a> {
+
+/// Base enum for all MQTT packet types.
+///
+/// This is the main type you'll be interacting with, as an output of [`decode_slice()`] and an input of
+/// [`encode()`]. Most variants can be constructed directly without using methods.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use core::convert::TryFrom;
+/// // Simplest form
+/// let pkt = Packet::Connack(Connack { session_present: false,
+///                                     code: ConnectReturnCode::Accepted });
+/// // Using `Into` trait
+/// let publish = Publish { dup: false,
+///                         qospid: QosPid::AtMostOnce,
+///                         retain: false,
+///                         topic_name: "to/pic",
+///                         payload: b"payload" };
+/// let pkt: Packet = publish.into();
+/// // Identifyer-only packets
+/// let pkt = Packet::Puback(Pid::try_from(42).unwrap());
+/// ```
+///
+/// Without the `std` feature, `Subscribe`, `Suback` and `Unsubscribe` packets hold up to 5 topics
+/// of up to 256 bytes. Use [`GenericPacket`] for other capacities.
+///
+/// [`encode()`]: fn.encode.html
+/// [`decode_slice()`]: fn.decode_slice.html
+/// [`GenericPacket`]: enum.GenericPacket.html
+pub type Packet<'a> = GenericPacket<'a, 5, 256>;
+
+impl<'a, const N: usize, const L: usize> GenericPacket<'a, N, L> {
     /// Return the packet type variant.
     ///
     /// This can be used for matching, categorising, debuging, etc. Most users will match directly
     /// on `Packet` instead.
     pub fn get_type(&self) -> PacketType {
         match self {
-            Packet::Connect(_) => PacketType::Connect,
-            Packet::Connack(_) => PacketType::Connack,
-            Packet::Publish(_) => PacketType::Publish,
-            Packet::Puback(_) => PacketType::Puback,
-            Packet::Pubrec(_) => PacketType::Pubrec,
-            Packet::Pubrel(_) => PacketType::Pubrel,
-            Packet::Pubcomp(_) => PacketType::Pubcomp,
-            Packet::Subscribe(_) => PacketType::Subscribe,
-            Packet::Suback(_) => PacketType::Suback,
-            Packet::Unsubscribe(_) => PacketType::Unsubscribe,
-            Packet::Unsuback(_) => PacketType::Unsuback,
-            Packet::Pingreq => PacketType::Pingreq,
-            Packet::Pingresp => PacketType::Pingresp,
-            Packet::Disconnect => PacketType::Disconnect,
+            GenericPacket::Connect(_) => PacketType::Connect,
+            GenericPacket::Connack(_) => PacketType::Connack,
+            GenericPacket::Publish(_) => PacketType::Publish,
+            GenericPacket::Puback(_) => PacketType::Puback,
+            GenericPacket::Pubrec(_) => PacketType::Pubrec,
+            GenericPacket::Pubrel(_) => PacketType::Pubrel,
+            GenericPacket::Pubcomp(_) => PacketType::Pubcomp,
+            GenericPacket::Subscribe(_) => PacketType::Subscribe,
+            GenericPacket::Suback(_) => PacketType::Suback,
+            GenericPacket::Unsubscribe(_) => PacketType::Unsubscribe,
+            GenericPacket::Unsuback(_) => PacketType::Unsuback,
+            GenericPacket::Pingreq => PacketType::Pingreq,
+            GenericPacket::Pingresp => PacketType::Pingresp,
+            GenericPacket::Disconnect => PacketType::Disconnect,
         }
     }
+
+    /// Return the exact number of bytes [encode_slice()] will write for this packet, including
+    /// the fixed header.
+    ///
+    /// ```
+    /// # use mqttrs::*;
+    /// let packet = Packet::from(Publish { dup: false,
+    ///                                     qospid: QosPid::AtMostOnce,
+    ///                                     retain: false,
+    ///                                     topic_name: "a/b",
+    ///                                     payload: &[0; 200] });
+    /// assert_eq!(208, packet.encoded_len());
+    /// let mut buf = [0; 208];
+    /// assert_eq!(Ok(208), encode_slice(&packet, &mut buf));
+    /// ```
+    ///
+    /// [encode_slice()]: fn.encode_slice.html
+    pub fn encoded_len(&self) -> usize {
+        let remaining_len = match self {
+            GenericPacket::Connect(connect) => connect.remaining_len(),
+            GenericPacket::Connack(_) => 2,
+            GenericPacket::Publish(publish) => publish.remaining_len(),
+            GenericPacket::Puback(_)
+            | GenericPacket::Pubrec(_)
+            | GenericPacket::Pubrel(_)
+            | GenericPacket::Pubcomp(_) => 2,
+            GenericPacket::Subscribe(subscribe) => subscribe.remaining_len(),
+            GenericPacket::Suback(suback) => suback.remaining_len(),
+            GenericPacket::Unsubscribe(unsub) => unsub.remaining_len(),
+            GenericPacket::Unsuback(_) => 2,
+            GenericPacket::Pingreq | GenericPacket::Pingresp | GenericPacket::Disconnect => 0,
+        };
+        1 + crate::encoder::varint_len(remaining_len) + remaining_len
+    }
+
+    /// Encode this packet into a slice like [encode_slice()], for any capacity.
+    ///
+    /// [encode_slice()]: fn.encode_slice.html
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        crate::encoder::encode_packet(self, buf)
+    }
+
+    /// Decode a packet like [decode_slice_with_options()], with the capacities of this type.
+    ///
+    /// ```
+    /// # use mqttrs::*;
+    /// // Unsubscribe from 2 topics.
+    /// let buf = [0b10100010, 10, 0, 1, 0, 2, b'a', b'b', 0, 2, b'c', b'd'];
+    /// let opts = DecodeOptions::default();
+    /// let decoded = GenericPacket::<1, 64>::decode(&buf, &opts);
+    /// # #[cfg(not(feature = "std"))]
+    /// assert_eq!(Err(Error::InvalidLength), decoded);
+    /// assert!(matches!(GenericPacket::<2, 64>::decode(&buf, &opts), Ok(Some((12, _)))));
+    /// ```
+    ///
+    /// [decode_slice_with_options()]: fn.decode_slice_with_options.html
+    pub fn decode(buf: &'a [u8], opts: &DecodeOptions) -> Result<Option<(usize, Self)>, Error> {
+        crate::decoder::decode_packet(buf, opts)
+    }
+}
+
+impl<'a> Packet<'a> {
+    /// Copy this packet into an [OwnedPacket], that doesn't borrow from the decoding buffer.
+    ///
+    /// [OwnedPacket]: enum.OwnedPacket.html
+    #[cfg(feature = "std")]
+    pub fn to_owned(&self) -> OwnedPacket {
+        self.clone().into()
+    }
+
+    /// Copy this packet into a [HeaplessPacket], with strings of up to `N` bytes and payloads of
+    /// up to `P` bytes.
+    ///
+    /// Returns `Error::WriteZero` if the packet doesn't fit.
+    ///
+    /// [HeaplessPacket]: enum.HeaplessPacket.html
+    pub fn to_heapless<const N: usize, const P: usize>(
+        &self,
+    ) -> Result<HeaplessPacket<N, P>, Error> {
+        core::convert::TryFrom::try_from(self.clone())
+    }
 }
 
 macro_rules! packet_from_borrowed {
     ($($t:ident),+) => {
         $(
-            impl<'a> From<$t<'a>> for Packet<'a> {
+            impl<'a, const N: usize, const L: usize> From<$t<'a>> for GenericPacket<'a, N, L> {
                 fn from(p: $t<'a>) -> Self {
-                    Packet::$t(p)
+                    GenericPacket::$t(p)
                 }
             }
         )+
     }
 }
 macro_rules! packet_from {
-    ($($t:ident),+) => {
+    ($($v:ident($t:ty)),+) => {
         $(
-            impl<'a> From<$t> for Packet<'a> {
+            impl<'a, const N: usize, const L: usize> From<$t> for GenericPacket<'a, N, L> {
                 fn from(p: $t) -> Self {
-                    Packet::$t(p)
+                    GenericPacket::$v(p)
                 }
             }
         )+
@@ -108,7 +203,52 @@ macro_rules! packet_from {
 }
 
 packet_from_borrowed!(Connect, Publish);
-packet_from!(Suback, Connack, Subscribe, Unsubscribe);
+packet_from!(
+    Suback(GenericSuback<N>),
+    Connack(Connack),
+    Subscribe(GenericSubscribe<N, L>),
+    Unsubscribe(GenericUnsubscribe<N, L>)
+);
+
+/// Packet of either protocol version.
+///
+/// Returned by [`decode_slice_versioned()`] and accepted by [`encode_slice_versioned()`], for
+/// code that handles both MQTT 3.1.1 and MQTT 5 peers.
+///
+/// [`decode_slice_versioned()`]: fn.decode_slice_versioned.html
+/// [`encode_slice_versioned()`]: fn.encode_slice_versioned.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionedPacket<'a> {
+    /// [`Protocol::MQTT311`] or [`Protocol::MQIsdp`] packet.
+    ///
+    /// [`Protocol::MQTT311`]: enum.Protocol.html#variant.MQTT311
+    /// [`Protocol::MQIsdp`]: enum.Protocol.html#variant.MQIsdp
+    V311(Packet<'a>),
+    /// [`Protocol::MQTT5`] packet.
+    ///
+    /// [`Protocol::MQTT5`]: enum.Protocol.html#variant.MQTT5
+    V5(v5::Packet<'a>),
+}
+impl<'a> VersionedPacket<'a> {
+    /// Return the packet type variant.
+    pub fn get_type(&self) -> PacketType {
+        match self {
+            VersionedPacket::V311(p) => p.get_type(),
+            VersionedPacket::V5(p) => p.get_type(),
+        }
+    }
+}
+impl<'a> From<Packet<'a>> for VersionedPacket<'a> {
+    fn from(p: Packet<'a>) -> Self {
+        VersionedPacket::V311(p)
+    }
+}
+impl<'a> From<v5::Packet<'a>> for VersionedPacket<'a> {
+    fn from(p: v5::Packet<'a>) -> Self {
+        VersionedPacket::V5(p)
+    }
+}
 
 /// Packet type variant, without the associated data.
 #[cfg_attr(feature = "defmt",derive(Format))]
@@ -128,4 +268,6 @@ pub enum PacketType {
     Pingreq,
     Pingresp,
     Disconnect,
+    /// MQTT 5 only, see [`v5::Packet::Auth`](v5/enum.Packet.html#variant.Auth).
+    Auth,
 }
@@ -1,6 +1,7 @@
 #[cfg(feature = "defmt")]
 use defmt::Format;
-use crate::{decoder::*, encoder::*, *};
+use crate::{decoder::*, encoder::*, topic::check_topic_name, *};
+use bytes::{buf::Chain, Buf};
 
 /// Publish packet ([MQTT 3.3]).
 ///
@@ -21,9 +22,14 @@ impl<'a> Publish<'a> {
         remaining_len: usize,
         buf: &'a [u8],
         offset: &mut usize,
+        opts: &DecodeOptions,
     ) -> Result<Self, Error> {
         let payload_end = *offset + remaining_len;
         let topic_name = read_str(buf, offset)?;
+        opts.check_topic_len(topic_name)?;
+        if opts.strict {
+            check_topic_name(topic_name)?;
+        }
 
         let qospid = match header.qos {
             QoS::AtMostOnce => QosPid::AtMostOnce,
@@ -31,7 +37,7 @@ impl<'a> Publish<'a> {
             QoS::ExactlyOnce => QosPid::ExactlyOnce(Pid::from_buffer(buf, offset)?),
         };
 
-        let payload = &buf[*offset..payload_end];
+        let payload = buf.get(*offset..payload_end).ok_or(Error::InvalidLength)?;
 
         // update the offset so that it's moved to the end of the payload
         *offset += payload.len();
@@ -44,7 +50,46 @@ impl<'a> Publish<'a> {
             payload,
         })
     }
-    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+    /// Length of the packet after the fixed header.
+    pub(crate) fn remaining_len(&self) -> usize {
+        // Length: topic (2+len) + pid (0/2) + payload (len)
+        self.topic_name.len()
+            + match self.qospid {
+                QosPid::AtMostOnce => 2,
+                _ => 4,
+            }
+            + self.payload.len()
+    }
+
+    /// Number of bytes written by [encode_header()](#method.encode_header): the fixed header,
+    /// topic name and pid.
+    pub fn header_len(&self) -> usize {
+        let remaining_len = self.remaining_len();
+        1 + varint_len(remaining_len) + remaining_len - self.payload.len()
+    }
+
+    /// Encode everything but the payload into `buf`, returning the number of bytes written.
+    ///
+    /// Sending these bytes followed by the payload is the same as sending the whole packet
+    /// encoded by [encode_slice()], without copying the payload. See also
+    /// [encode_vectored()](#method.encode_vectored) and [encode_chain()](#method.encode_chain).
+    ///
+    /// ```
+    /// # use mqttrs::*;
+    /// let publish = Publish { dup: false,
+    ///                         qospid: QosPid::AtMostOnce,
+    ///                         retain: false,
+    ///                         topic_name: "camera/1",
+    ///                         payload: &[0; 200_000] };
+    /// let mut header = [0; 16];
+    /// assert_eq!(Ok(14), publish.encode_header(&mut header));
+    /// assert_eq!(14, publish.header_len());
+    /// ```
+    ///
+    /// [encode_slice()]: fn.encode_slice.html
+    pub fn encode_header(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        check_topic_name(self.topic_name)?;
+
         // Header
         let mut header: u8 = match self.qospid {
             QosPid::AtMostOnce => 0b00110000,
@@ -52,23 +97,19 @@ impl<'a> Publish<'a> {
             QosPid::ExactlyOnce(_) => 0b00110100,
         };
         if self.dup {
-            header |= 0b00001000 as u8;
+            header |= 0b00001000_u8;
         };
         if self.retain {
-            header |= 0b00000001 as u8;
+            header |= 0b00000001_u8;
         };
-        check_remaining(buf, offset, 1)?;
+        let length = self.remaining_len();
+        if length > 268435455 {
+            return Err(Error::InvalidLength);
+        }
+        let offset = &mut 0;
+        check_remaining(buf, offset, self.header_len())?;
         write_u8(buf, offset, header)?;
-
-        // Length: topic (2+len) + pid (0/2) + payload (len)
-        let length = self.topic_name.len()
-            + match self.qospid {
-                QosPid::AtMostOnce => 2,
-                _ => 4,
-            }
-            + self.payload.len();
-
-        let write_len = write_length(buf, offset, length)? + 1;
+        write_varint(buf, offset, length)?;
 
         // Topic
         write_string(buf, offset, self.topic_name)?;
@@ -80,11 +121,57 @@ impl<'a> Publish<'a> {
             QosPid::ExactlyOnce(pid) => pid.to_buffer(buf, offset)?,
         }
 
+        Ok(*offset)
+    }
+
+    /// Encode the header into `buf` like [encode_header()](#method.encode_header), and return it
+    /// with the payload as `IoSlice`s for [`Write::write_vectored()`].
+    ///
+    /// ```
+    /// # use mqttrs::*;
+    /// use std::io::Write;
+    ///
+    /// let publish = Publish { dup: false,
+    ///                         qospid: QosPid::AtMostOnce,
+    ///                         retain: false,
+    ///                         topic_name: "camera/1",
+    ///                         payload: &[0; 200_000] };
+    /// let mut header = [0; 16];
+    /// let mut socket = Vec::new();
+    /// let written = socket.write_vectored(&publish.encode_vectored(&mut header).unwrap());
+    /// assert_eq!(200_014, written.unwrap());
+    /// ```
+    ///
+    /// [`Write::write_vectored()`]: https://doc.rust-lang.org/std/io/trait.Write.html#method.write_vectored
+    #[cfg(feature = "std")]
+    pub fn encode_vectored<'b>(
+        &'b self,
+        buf: &'b mut [u8],
+    ) -> Result<[std::io::IoSlice<'b>; 2], Error> {
+        let len = self.encode_header(buf)?;
+        Ok([std::io::IoSlice::new(&buf[..len]), std::io::IoSlice::new(self.payload)])
+    }
+
+    /// Encode the header into `buf` like [encode_header()](#method.encode_header), and return it
+    /// chained with the payload as a [Buf].
+    ///
+    /// [Buf]: https://docs.rs/bytes/1.0.0/bytes/trait.Buf.html
+    pub fn encode_chain<'b>(&self, buf: &'b mut [u8]) -> Result<Chain<&'b [u8], &'a [u8]>, Error> {
+        let len = self.encode_header(buf)?;
+        let header: &'b [u8] = &buf[..len];
+        Ok(header.chain(self.payload))
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let header_len = self.encode_header(&mut buf[*offset..])?;
+        *offset += header_len;
+
         // Payload
-        for &byte in self.payload {
-            write_u8(buf, offset, byte)?;
-        }
+        let len = self.payload.len();
+        check_remaining(buf, offset, len)?;
+        buf[*offset..*offset + len].copy_from_slice(self.payload);
+        *offset += len;
 
-        Ok(write_len)
+        Ok(header_len + len)
     }
 }
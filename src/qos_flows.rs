@@ -0,0 +1,240 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use crate::*;
+use heapless::{Deque, Vec};
+
+/// Where an outgoing message is in its delivery flow.
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    /// Waiting for `Puback` (QoS 1) or `Pubrec` (QoS 2).
+    Publish,
+    /// Waiting for `Pubcomp`.
+    Pubrel,
+}
+
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+struct InFlight<'a> {
+    publish: Publish<'a>,
+    flow: Flow,
+    /// Whether the current packet of the flow (`Publish` or `Pubrel`) needs to be (re)sent.
+    send: bool,
+}
+
+/// Result of [`QosFlows::receive()`].
+///
+/// [`QosFlows::receive()`]: struct.QosFlows.html#method.receive
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub(crate) enum Received<'p> {
+    /// A message for the application. Duplicate `QoS::ExactlyOnce` messages are filtered out.
+    Publish(Publish<'p>),
+    /// An outgoing message has been fully acknowledged.
+    Published(Pid),
+    /// The packet was part of a flow, there is nothing to report.
+    Handled,
+    /// The packet isn't part of a QoS flow, it is up to the session.
+    Other(Packet<'p>),
+}
+
+/// QoS 1/2 flows and the packet queue, shared by [`ClientSession`] and [`ServerSession`].
+///
+/// Both sides of a connection acknowledge incoming messages and keep outgoing ones until they are
+/// acknowledged in the same way, only connection handling differs.
+///
+/// [`ClientSession`]: struct.ClientSession.html
+/// [`ServerSession`]: struct.ServerSession.html
+#[derive(Debug)]
+pub(crate) struct QosFlows<'a, const N: usize> {
+    next_pid: Pid,
+    in_flight: Vec<InFlight<'a>, N>,
+    /// Pids of incoming `QoS::ExactlyOnce` messages, between `Pubrec` and `Pubrel`.
+    incoming: Vec<Pid, N>,
+    queue: Deque<Packet<'a>, N>,
+}
+
+impl<'a, const N: usize> QosFlows<'a, N> {
+    pub(crate) fn new() -> Self {
+        QosFlows {
+            next_pid: Pid::new(),
+            in_flight: Vec::new(),
+            incoming: Vec::new(),
+            queue: Deque::new(),
+        }
+    }
+
+    /// Forget in-flight messages in both directions.
+    pub(crate) fn clear(&mut self) {
+        self.in_flight.clear();
+        self.incoming.clear();
+    }
+
+    /// Drop queued packets, and send in-flight messages again, with their DUP flag set if they
+    /// were already sent.
+    pub(crate) fn disconnected(&mut self) {
+        self.queue.clear();
+        for msg in self.in_flight.iter_mut() {
+            msg.send = true;
+        }
+    }
+
+    /// Number of outgoing messages waiting for an acknowledgement.
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Get a [`Pid`] that isn't used by an in-flight message, nor by the caller.
+    ///
+    /// [`Pid`]: struct.Pid.html
+    pub(crate) fn next_pid(&mut self, is_used: impl Fn(Pid) -> bool) -> Pid {
+        loop {
+            let pid = self.next_pid;
+            self.next_pid = pid + 1;
+            if !self.in_flight.iter().any(|m| m.publish.qospid.pid() == Some(pid)) && !is_used(pid)
+            {
+                return pid;
+            }
+        }
+    }
+
+    /// Queue a `QoS::AtMostOnce` message, or keep a message in flight until it is acknowledged.
+    ///
+    /// Returns `Error::QueueFull` if `N` messages are already in flight (or queued, for
+    /// `QoS::AtMostOnce`).
+    pub(crate) fn publish(
+        &mut self,
+        topic_name: &'a str,
+        payload: &'a [u8],
+        qos: QoS,
+        retain: bool,
+        is_used: impl Fn(Pid) -> bool,
+    ) -> Result<Option<Pid>, Error> {
+        let mut publish = Publish {
+            dup: false,
+            qospid: QosPid::AtMostOnce,
+            retain,
+            topic_name,
+            payload,
+        };
+        if qos == QoS::AtMostOnce {
+            self.push(publish.into())?;
+            return Ok(None);
+        }
+        if self.in_flight.is_full() {
+            return Err(Error::QueueFull);
+        }
+        let pid = self.next_pid(is_used);
+        publish.qospid = match qos {
+            QoS::AtLeastOnce => QosPid::AtLeastOnce(pid),
+            _ => QosPid::ExactlyOnce(pid),
+        };
+        let msg = InFlight {
+            publish,
+            flow: Flow::Publish,
+            send: true,
+        };
+        self.in_flight.push(msg).map_err(|_| Error::QueueFull)?;
+        Ok(Some(pid))
+    }
+
+    /// Queue a packet, returning `Error::QueueFull` if `N` packets are already queued.
+    pub(crate) fn push(&mut self, packet: Packet<'a>) -> Result<(), Error> {
+        self.queue.push_back(packet).map_err(|_| Error::QueueFull)
+    }
+
+    /// Process an incoming `Publish` or acknowledgement, answering it if needed.
+    ///
+    /// Acknowledgements for unknown [`Pid`]s return `Error::UnexpectedPacket`.
+    ///
+    /// [`Pid`]: struct.Pid.html
+    pub(crate) fn receive<'p>(&mut self, packet: Packet<'p>) -> Result<Received<'p>, Error> {
+        let typ = packet.get_type();
+        match packet {
+            Packet::Publish(publish) => match publish.qospid {
+                QosPid::AtMostOnce => Ok(Received::Publish(publish)),
+                QosPid::AtLeastOnce(pid) => {
+                    self.push(Packet::Puback(pid))?;
+                    Ok(Received::Publish(publish))
+                }
+                QosPid::ExactlyOnce(pid) => {
+                    // Make sure that we can answer before accepting the message.
+                    if self.queue.is_full() {
+                        return Err(Error::QueueFull);
+                    }
+                    let received = if self.incoming.contains(&pid) {
+                        Received::Handled
+                    } else {
+                        self.incoming.push(pid).map_err(|_| Error::QueueFull)?;
+                        Received::Publish(publish)
+                    };
+                    self.push(Packet::Pubrec(pid))?;
+                    Ok(received)
+                }
+            },
+            Packet::Pubrel(pid) => {
+                // A Pubrel for an unknown pid is a re-send, the message was already delivered.
+                self.incoming.retain(|p| *p != pid);
+                self.push(Packet::Pubcomp(pid))?;
+                Ok(Received::Handled)
+            }
+            Packet::Puback(pid) => {
+                self.remove(pid, QoS::AtLeastOnce, Flow::Publish, typ)?;
+                Ok(Received::Published(pid))
+            }
+            Packet::Pubrec(pid) => {
+                let msg = self.find(pid, QoS::ExactlyOnce, Flow::Publish, typ)?;
+                msg.flow = Flow::Pubrel;
+                msg.send = true;
+                Ok(Received::Handled)
+            }
+            Packet::Pubcomp(pid) => {
+                self.remove(pid, QoS::ExactlyOnce, Flow::Pubrel, typ)?;
+                Ok(Received::Published(pid))
+            }
+            packet => Ok(Received::Other(packet)),
+        }
+    }
+
+    /// Get the next queued packet, if any.
+    pub(crate) fn poll_queue(&mut self) -> Option<Packet<'a>> {
+        self.queue.pop_front()
+    }
+
+    /// Get the next queued packet, or else the next in-flight `Publish` or `Pubrel` to send.
+    pub(crate) fn poll_transmit(&mut self) -> Option<Packet<'a>> {
+        if let Some(packet) = self.poll_queue() {
+            return Some(packet);
+        }
+        let msg = self.in_flight.iter_mut().find(|m| m.send)?;
+        msg.send = false;
+        Some(match msg.flow {
+            Flow::Publish => {
+                let packet = Packet::Publish(msg.publish.clone());
+                msg.publish.dup = true;
+                packet
+            }
+            Flow::Pubrel => Packet::Pubrel(msg.publish.qospid.pid()?),
+        })
+    }
+
+    fn find(
+        &mut self,
+        pid: Pid,
+        qos: QoS,
+        flow: Flow,
+        typ: PacketType,
+    ) -> Result<&mut InFlight<'a>, Error> {
+        self.in_flight
+            .iter_mut()
+            .find(|m| m.publish.qospid.pid() == Some(pid))
+            .filter(|m| m.publish.qospid.qos() == qos && m.flow == flow)
+            .ok_or(Error::UnexpectedPacket(typ))
+    }
+
+    fn remove(&mut self, pid: Pid, qos: QoS, flow: Flow, typ: PacketType) -> Result<(), Error> {
+        self.find(pid, qos, flow, typ)?;
+        self.in_flight.retain(|m| m.publish.qospid.pid() != Some(pid));
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::*;
+use std::{collections::BTreeMap, string::String, vec::Vec};
+
+/// Storage of retained messages, the latest one for each topic name ([MQTT 3.3]).
+///
+/// Implementations provide [`insert()`], [`remove()`] and [`matching()`], and the provided
+/// methods apply the rules of the specification on top of them:
+///
+/// * [`store()`] keeps the messages published with the retain flag, and deletes the retained
+///   message of the topic when such a message has an empty payload (MQTT-3.3.1-10/11).
+/// * [`retained()`] returns the messages to send for a new subscription, with the retain flag
+///   set (MQTT-3.3.1-8) and the `QoS` of the subscription if it is lower.
+///
+/// Messages sent to existing subscriptions must have the retain flag cleared (MQTT-3.3.1-9),
+/// which is left to the caller.
+///
+/// ```
+/// # use mqttrs::*;
+/// let mut store = MemoryRetainedStore::new();
+/// let publish = |topic_name, payload| Publish { dup: false,
+///                                                qospid: QosPid::AtMostOnce,
+///                                                retain: true,
+///                                                topic_name,
+///                                                payload };
+/// store.store(&publish("sensors/1", b"21.5"));
+/// store.store(&publish("sensors/2", b"19.0"));
+/// store.store(&publish("sensors/2", b""));
+///
+/// let topic = SubscribeTopic { topic_path: "sensors/+".into(), qos: QoS::AtMostOnce };
+/// let retained = store.retained(&topic).unwrap();
+/// assert_eq!(1, retained.len());
+/// assert_eq!(("sensors/1", true), (&retained[0].topic_name[..], retained[0].retain));
+/// ```
+///
+/// [MQTT 3.3]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718037
+/// [`insert()`]: #tymethod.insert
+/// [`remove()`]: #tymethod.remove
+/// [`matching()`]: #tymethod.matching
+/// [`store()`]: #method.store
+/// [`retained()`]: #method.retained
+pub trait RetainedStore {
+    /// Store `publish`, replacing the message previously stored for its topic name.
+    fn insert(&mut self, publish: OwnedPublish);
+
+    /// Delete the message stored for `topic_name`, if any.
+    fn remove(&mut self, topic_name: &str);
+
+    /// Get the stored messages whose topic name matches `filter`, as they were inserted.
+    fn matching(&self, filter: &TopicFilter<'_>) -> Vec<OwnedPublish>;
+
+    /// Process a published message: keep it if its retain flag is set, or delete the retained
+    /// message of its topic if its payload is also empty. Other messages are ignored.
+    fn store(&mut self, publish: &Publish<'_>) {
+        if !publish.retain {
+            return;
+        }
+        if publish.payload.is_empty() {
+            self.remove(publish.topic_name);
+        } else {
+            let mut publish = OwnedPublish::from(publish.clone());
+            publish.dup = false;
+            self.insert(publish);
+        }
+    }
+
+    /// Get the retained messages to send for a new subscription to `topic`.
+    ///
+    /// Their retain flag is set, and their `QoS` is lowered to `topic.qos` if needed. Pids are
+    /// those of the stored messages, and should be replaced before sending.
+    ///
+    /// Returns `Error::InvalidTopicFilter` if `topic` isn't a valid filter.
+    fn retained(&self, topic: &SubscribeTopic) -> Result<Vec<OwnedPublish>, Error> {
+        let filter = topic.topic_filter()?;
+        let mut messages = self.matching(&filter);
+        for publish in messages.iter_mut() {
+            publish.dup = false;
+            publish.retain = true;
+            publish.qospid = match (publish.qospid, topic.qos) {
+                (_, QoS::AtMostOnce) => QosPid::AtMostOnce,
+                (QosPid::ExactlyOnce(pid), QoS::AtLeastOnce) => QosPid::AtLeastOnce(pid),
+                (qospid, _) => qospid,
+            };
+        }
+        Ok(messages)
+    }
+}
+
+/// In-memory [RetainedStore], keeping messages in a `BTreeMap` by topic name.
+///
+/// [RetainedStore]: trait.RetainedStore.html
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryRetainedStore {
+    messages: BTreeMap<String, OwnedPublish>,
+}
+
+impl MemoryRetainedStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the message stored for `topic_name`.
+    pub fn get(&self, topic_name: &str) -> Option<&OwnedPublish> {
+        self.messages.get(topic_name)
+    }
+
+    /// Number of stored messages.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl RetainedStore for MemoryRetainedStore {
+    fn insert(&mut self, publish: OwnedPublish) {
+        self.messages.insert(publish.topic_name.clone(), publish);
+    }
+
+    fn remove(&mut self, topic_name: &str) {
+        self.messages.remove(topic_name);
+    }
+
+    fn matching(&self, filter: &TopicFilter<'_>) -> Vec<OwnedPublish> {
+        self.messages
+            .values()
+            .filter(|publish| filter.matches(&publish.topic_name))
+            .cloned()
+            .collect()
+    }
+}
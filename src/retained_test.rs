@@ -0,0 +1,89 @@
+use crate::{
+    test_util::{pid, topic},
+    *,
+};
+
+fn publish<'a>(
+    topic_name: &'a str,
+    payload: &'a [u8],
+    qospid: QosPid,
+    retain: bool,
+) -> Publish<'a> {
+    Publish {
+        dup: true,
+        qospid,
+        retain,
+        topic_name,
+        payload,
+    }
+}
+
+#[test]
+fn test_store() {
+    let mut store = MemoryRetainedStore::new();
+
+    // Only retained messages are stored, without their DUP flag.
+    store.store(&publish("a", b"0", QosPid::AtMostOnce, false));
+    assert!(store.is_empty());
+    store.store(&publish("a", b"1", QosPid::AtLeastOnce(pid(1)), true));
+    let expected = OwnedPublish {
+        dup: false,
+        ..publish("a", b"1", QosPid::AtLeastOnce(pid(1)), true).into()
+    };
+    assert_eq!(Some(&expected), store.get("a"));
+
+    // The latest message replaces the previous one.
+    store.store(&publish("a", b"2", QosPid::AtMostOnce, true));
+    store.store(&publish("b", b"3", QosPid::AtMostOnce, true));
+    assert_eq!(2, store.len());
+    assert_eq!(b"2".to_vec(), store.get("a").unwrap().payload);
+
+    // An empty retained message deletes the stored one, and isn't stored itself.
+    store.store(&publish("a", b"", QosPid::AtMostOnce, false));
+    assert_eq!(2, store.len());
+    store.store(&publish("a", b"", QosPid::AtMostOnce, true));
+    store.store(&publish("c", b"", QosPid::AtMostOnce, true));
+    assert_eq!(None, store.get("a"));
+    assert_eq!(None, store.get("c"));
+    assert_eq!(1, store.len());
+}
+
+#[test]
+fn test_retained() {
+    let mut store = MemoryRetainedStore::new();
+    store.store(&publish("a/0", b"0", QosPid::AtMostOnce, true));
+    store.store(&publish("a/1", b"1", QosPid::AtLeastOnce(pid(1)), true));
+    store.store(&publish("a/2", b"2", QosPid::ExactlyOnce(pid(2)), true));
+    store.store(&publish("a/b/c", b"3", QosPid::ExactlyOnce(pid(3)), true));
+    store.store(&publish("$SYS/a", b"4", QosPid::AtMostOnce, true));
+
+    let qos = |path, qos| -> Vec<_> {
+        let retained = store.retained(&topic(path, qos)).unwrap();
+        assert!(retained.iter().all(|p| p.retain && !p.dup));
+        retained.iter().map(|p| (p.topic_name.clone(), p.qospid)).collect()
+    };
+    // Messages are sent with the lowest QoS of the message and the subscription.
+    assert_eq!(
+        vec![
+            ("a/0".into(), QosPid::AtMostOnce),
+            ("a/1".into(), QosPid::AtLeastOnce(pid(1))),
+            ("a/2".into(), QosPid::AtLeastOnce(pid(2))),
+        ],
+        qos("a/+", QoS::AtLeastOnce)
+    );
+    assert_eq!(
+        vec![
+            ("a/0".into(), QosPid::AtMostOnce),
+            ("a/1".into(), QosPid::AtLeastOnce(pid(1))),
+            ("a/2".into(), QosPid::ExactlyOnce(pid(2))),
+            ("a/b/c".into(), QosPid::ExactlyOnce(pid(3))),
+        ],
+        qos("#", QoS::ExactlyOnce)
+    );
+    assert_eq!(vec![("a/b/c".into(), QosPid::AtMostOnce)], qos("a/b/c", QoS::AtMostOnce));
+    assert_eq!(vec![("$SYS/a".into(), QosPid::AtMostOnce)], qos("$SYS/#", QoS::AtMostOnce));
+    assert_eq!(Vec::<(String, QosPid)>::new(), qos("b/#", QoS::AtMostOnce));
+
+    let res = store.retained(&topic("a/#/b", QoS::AtMostOnce));
+    assert_eq!(Err(Error::InvalidTopicFilter), res);
+}
@@ -0,0 +1,386 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use crate::{
+    qos_flows::{QosFlows, Received},
+    subscribe::{limited_push, limited_string, LimitedString, LimitedVec},
+    *,
+};
+use core::time::Duration;
+
+/// Event returned by [`ServerSession::handle()`].
+///
+/// [`ServerSession::handle()`]: struct.ServerSession.html#method.handle
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerEvent<'a> {
+    /// A client wants to connect. Answer with [`ServerSession::accept()`] or
+    /// [`ServerSession::reject()`].
+    ///
+    /// [`ServerSession::accept()`]: struct.ServerSession.html#method.accept
+    /// [`ServerSession::reject()`]: struct.ServerSession.html#method.reject
+    Connect(Connect<'a>),
+    /// A message from the client. Duplicate `QoS::ExactlyOnce` messages are filtered out.
+    Publish(Publish<'a>),
+    /// An outgoing message has been fully acknowledged by the client.
+    Published(Pid),
+    /// Answer with [`ServerSession::suback()`].
+    ///
+    /// [`ServerSession::suback()`]: struct.ServerSession.html#method.suback
+    Subscribe(Subscribe),
+    /// The `Unsuback` is sent automatically.
+    Unsubscribe(Unsubscribe),
+    /// The client disconnected cleanly, its Last Will is discarded.
+    Disconnected,
+}
+
+/// Copy of the client's [`LastWill`], which outlives the `Connect` packet.
+///
+/// [`LastWill`]: struct.LastWill.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+struct Will {
+    topic: LimitedString,
+    message: LimitedVec<u8, 256>,
+    qos: QoS,
+    retain: bool,
+}
+
+impl Will {
+    fn new(will: &LastWill) -> Result<Self, Error> {
+        let mut message = LimitedVec::new();
+        for b in will.message {
+            limited_push(&mut message, *b)?;
+        }
+        Ok(Will {
+            topic: limited_string(will.topic)?,
+            message,
+            qos: will.qos,
+            retain: will.retain,
+        })
+    }
+
+    fn as_borrowed(&self) -> LastWill<'_> {
+        LastWill {
+            topic: &self.topic,
+            message: &self.message,
+            qos: self.qos,
+            retain: self.retain,
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the `Connect` packet.
+    Disconnected,
+    /// The `Connect` packet was received, waiting for `accept()` or `reject()`.
+    Connecting,
+    Connected,
+    /// The connection was refused, waiting for the `Connack` to be sent.
+    Rejected,
+}
+
+/// Server-side session state machine, for one client.
+///
+/// `ServerSession` is the counterpart of [`ClientSession`], and doesn't do any IO either:
+///
+/// * It checks that the first packet is a `Connect` and that no other `Connect` follows. Nothing
+///   else is sent or processed before the application accepts the connection ([MQTT 3.1]).
+/// * It answers incoming messages with `Puback`, or `Pubrec`/`Pubcomp`, and filters out duplicate
+///   `QoS::ExactlyOnce` messages until their `Pubrel`.
+/// * It keeps outgoing `QoS::AtLeastOnce` and `QoS::ExactlyOnce` messages until they are
+///   acknowledged. If the client connected with `clean_session = false`, messages published while
+///   it is offline are queued and sent on the next connection.
+/// * It detects keep-alive timeouts, after one and a half times [`Connect::keep_alive`] without
+///   any packet from the client.
+/// * It returns the client's [`LastWill`] when the connection is lost without a `Disconnect`.
+///
+/// Feed it the decoded packets received from the client with [`handle()`], and send the packets
+/// returned by [`poll_transmit()`] until it returns `None`. Times are monotonic durations since an
+/// arbitrary point chosen by the caller. The session must outlive the connections of a client that
+/// doesn't use a clean session.
+///
+/// Storage is fixed-size, using [heapless]: `N` is the maximum number of in-flight outgoing
+/// messages, of incoming `QoS::ExactlyOnce` messages waiting for `Pubrel`, and of queued packets.
+/// Outgoing messages borrow their topic and payload for the lifetime `'a`. Incoming packets are
+/// only borrowed by [`handle()`], the Last Will is copied into the session: without the `std`
+/// feature, its topic and message hold up to 256 bytes each.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use core::time::Duration;
+/// let mut session = ServerSession::<8>::new();
+/// let will = LastWill { topic: "status",
+///                        message: b"offline",
+///                        qos: QoS::AtMostOnce,
+///                        retain: true };
+/// let connect = Connect { protocol: Protocol::MQTT311,
+///                         keep_alive: 30,
+///                         client_id: "client",
+///                         clean_session: false,
+///                         last_will: Some(will.clone()),
+///                         username: None,
+///                         password: None };
+///
+/// let now = Duration::from_secs(0);
+/// let event = session.handle(Packet::Connect(connect), now).unwrap();
+/// assert!(matches!(event, Some(ServerEvent::Connect(_))));
+/// session.accept().unwrap();
+/// let connack = Connack { session_present: false, code: ConnectReturnCode::Accepted };
+/// assert_eq!(Some(Packet::Connack(connack)), session.poll_transmit());
+///
+/// let pid = session.publish("to/pic", b"payload", QoS::AtLeastOnce, false).unwrap().unwrap();
+/// assert!(matches!(session.poll_transmit(), Some(Packet::Publish(_))));
+/// assert_eq!(Ok(Some(ServerEvent::Published(pid))), session.handle(Packet::Puback(pid), now));
+///
+/// // The client went silent for 45 seconds, publish its Last Will.
+/// assert!(session.is_timed_out(Duration::from_secs(45)));
+/// assert_eq!(Some(will), session.disconnected());
+/// ```
+///
+/// [`ClientSession`]: struct.ClientSession.html
+/// [MQTT 3.1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718028
+/// [`Connect::keep_alive`]: struct.Connect.html#structfield.keep_alive
+/// [`LastWill`]: struct.LastWill.html
+/// [`handle()`]: #method.handle
+/// [`poll_transmit()`]: #method.poll_transmit
+/// [heapless]: https://docs.rs/heapless
+#[derive(Debug)]
+pub struct ServerSession<'a, const N: usize> {
+    state: State,
+    /// Whether the session outlives the connection (`clean_session = false`).
+    persistent: bool,
+    /// Whether the client asked for a new session in the current `Connect`.
+    clean_session: bool,
+    keep_alive: Duration,
+    last_will: Option<Will>,
+    flows: QosFlows<'a, N>,
+    last_received: Duration,
+}
+
+impl<'a, const N: usize> ServerSession<'a, N> {
+    pub fn new() -> Self {
+        ServerSession {
+            state: State::Disconnected,
+            persistent: false,
+            clean_session: true,
+            keep_alive: Duration::from_secs(0),
+            last_will: None,
+            flows: QosFlows::new(),
+            last_received: Duration::from_secs(0),
+        }
+    }
+
+    /// Accept the connection, the `Connack` will be the next packet returned by
+    /// [`poll_transmit()`].
+    ///
+    /// Returns `Error::UnexpectedPacket` if no `Connect` is pending.
+    ///
+    /// [`poll_transmit()`]: #method.poll_transmit
+    pub fn accept(&mut self) -> Result<(), Error> {
+        if self.state != State::Connecting {
+            return Err(Error::UnexpectedPacket(PacketType::Connack));
+        }
+        let session_present = self.persistent && !self.clean_session;
+        if self.clean_session {
+            self.flows.clear();
+        }
+        self.persistent = !self.clean_session;
+        self.state = State::Connected;
+        self.push(Packet::Connack(Connack {
+            session_present,
+            code: ConnectReturnCode::Accepted,
+        }))
+    }
+
+    /// Refuse the connection with `code`, which shouldn't be `ConnectReturnCode::Accepted`.
+    ///
+    /// The `Connack` will be the last packet returned by [`poll_transmit()`], the caller should
+    /// then close the connection. The existing session, if any, is left untouched.
+    ///
+    /// [`poll_transmit()`]: #method.poll_transmit
+    pub fn reject(&mut self, code: ConnectReturnCode) -> Result<(), Error> {
+        if self.state != State::Connecting {
+            return Err(Error::UnexpectedPacket(PacketType::Connack));
+        }
+        self.state = State::Rejected;
+        self.last_will = None;
+        self.push(Packet::Connack(Connack {
+            session_present: false,
+            code,
+        }))
+    }
+
+    /// Notify the session that the connection was lost or closed, returning the client's Last
+    /// Will if it should be published.
+    ///
+    /// The Last Will is returned unless the client sent a `Disconnect` packet, so call this after
+    /// a keep-alive timeout, a network error or a protocol error ([MQTT 3.1]). Queued packets
+    /// are dropped. Unless the session is clean, in-flight messages will be sent again after the
+    /// next connection. The Last Will borrows the session, and is only returned once.
+    ///
+    /// [MQTT 3.1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718028
+    pub fn disconnected(&mut self) -> Option<LastWill<'_>> {
+        // A connection that was never accepted has no Last Will.
+        let connected = self.state == State::Connected;
+        self.state = State::Disconnected;
+        self.flows.disconnected();
+        if !self.persistent {
+            self.flows.clear();
+        }
+        // The will is kept until the next Connect, but the session is now disconnected.
+        self.last_will.as_ref().filter(|_| connected).map(Will::as_borrowed)
+    }
+
+    /// Whether the client is connected and was accepted.
+    pub fn is_connected(&self) -> bool {
+        self.state == State::Connected
+    }
+
+    /// Whether the session outlives the connection (the client connected with
+    /// `clean_session = false`).
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Number of outgoing messages waiting for an acknowledgement, or for the client to reconnect.
+    pub fn in_flight(&self) -> usize {
+        self.flows.in_flight()
+    }
+
+    /// Get a [`Pid`] that isn't used by an in-flight message.
+    ///
+    /// [`Pid`]: struct.Pid.html
+    pub fn next_pid(&mut self) -> Pid {
+        self.flows.next_pid(|_| false)
+    }
+
+    /// Queue a message for the client, returning its [`Pid`] if `qos` requires an
+    /// acknowledgement.
+    ///
+    /// While the client is offline, `QoS::AtLeastOnce` and `QoS::ExactlyOnce` messages are kept
+    /// for persistent sessions, other messages are dropped. Returns `Error::QueueFull` if `N`
+    /// messages are already in flight (or queued, for `QoS::AtMostOnce`).
+    ///
+    /// [`Pid`]: struct.Pid.html
+    pub fn publish(
+        &mut self,
+        topic_name: &'a str,
+        payload: &'a [u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<Option<Pid>, Error> {
+        if self.state != State::Connected && (qos == QoS::AtMostOnce || !self.persistent) {
+            return Ok(None);
+        }
+        self.flows.publish(topic_name, payload, qos, retain, |_| false)
+    }
+
+    /// Queue the `Suback` answering a [`ServerEvent::Subscribe`].
+    ///
+    /// [`ServerEvent::Subscribe`]: enum.ServerEvent.html#variant.Subscribe
+    pub fn suback(
+        &mut self,
+        pid: Pid,
+        return_codes: LimitedVec<SubscribeReturnCodes>,
+    ) -> Result<(), Error> {
+        self.push(Packet::Suback(Suback { pid, return_codes }))
+    }
+
+    /// Process a packet received from the client.
+    ///
+    /// Packets received out of order (anything but a single `Connect` before [`accept()`]),
+    /// acknowledgements for unknown [`Pid`]s and packets that a client shouldn't send return
+    /// `Error::UnexpectedPacket`. The caller should then close the connection and call
+    /// [`disconnected()`]. Without the `std` feature, a Last Will that doesn't fit in the session
+    /// returns `Error::InvalidLength`.
+    ///
+    /// [`accept()`]: #method.accept
+    /// [`Pid`]: struct.Pid.html
+    /// [`disconnected()`]: #method.disconnected
+    pub fn handle<'p>(
+        &mut self,
+        packet: Packet<'p>,
+        now: Duration,
+    ) -> Result<Option<ServerEvent<'p>>, Error> {
+        let typ = packet.get_type();
+        self.last_received = now;
+        match (self.state, packet) {
+            (State::Disconnected, Packet::Connect(connect)) => {
+                self.last_will = connect.last_will.as_ref().map(Will::new).transpose()?;
+                self.state = State::Connecting;
+                self.clean_session = connect.clean_session;
+                self.keep_alive = Duration::from_secs(connect.keep_alive.into());
+                Ok(Some(ServerEvent::Connect(connect)))
+            }
+            (State::Connected, packet) => match self.flows.receive(packet)? {
+                Received::Publish(publish) => Ok(Some(ServerEvent::Publish(publish))),
+                Received::Published(pid) => Ok(Some(ServerEvent::Published(pid))),
+                Received::Handled => Ok(None),
+                Received::Other(Packet::Subscribe(subscribe)) => {
+                    Ok(Some(ServerEvent::Subscribe(subscribe)))
+                }
+                Received::Other(Packet::Unsubscribe(unsubscribe)) => {
+                    self.push(Packet::Unsuback(unsubscribe.pid))?;
+                    Ok(Some(ServerEvent::Unsubscribe(unsubscribe)))
+                }
+                Received::Other(Packet::Pingreq) => {
+                    self.push(Packet::Pingresp)?;
+                    Ok(None)
+                }
+                Received::Other(Packet::Disconnect) => {
+                    self.last_will = None;
+                    self.disconnected();
+                    Ok(Some(ServerEvent::Disconnected))
+                }
+                Received::Other(_) => Err(Error::UnexpectedPacket(typ)),
+            },
+            _ => Err(Error::UnexpectedPacket(typ)),
+        }
+    }
+
+    /// Get the next packet to send, if any.
+    pub fn poll_transmit(&mut self) -> Option<Packet<'a>> {
+        match self.state {
+            State::Disconnected | State::Connecting => None,
+            State::Rejected => self.flows.poll_queue(),
+            State::Connected => self.flows.poll_transmit(),
+        }
+    }
+
+    /// Time after which the client is considered dead, if it doesn't send any packet.
+    ///
+    /// Returns `None` if keep-alive is disabled or the client isn't connected.
+    pub fn poll_timeout(&self) -> Option<Duration> {
+        match self.state {
+            State::Connected if self.keep_alive > Duration::from_secs(0) => {
+                Some(self.last_received + self.keep_alive * 3 / 2)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the client exceeded its keep-alive period ([MQTT 3.1]).
+    ///
+    /// The caller should then close the connection and call [`disconnected()`].
+    ///
+    /// [MQTT 3.1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718028
+    /// [`disconnected()`]: #method.disconnected
+    pub fn is_timed_out(&self, now: Duration) -> bool {
+        match self.poll_timeout() {
+            Some(timeout) => now >= timeout,
+            None => false,
+        }
+    }
+
+    fn push(&mut self, packet: Packet<'a>) -> Result<(), Error> {
+        self.flows.push(packet)
+    }
+}
+
+impl<'a, const N: usize> Default for ServerSession<'a, N> {
+    fn default() -> Self {
+        ServerSession::new()
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{subscribe::LimitedVec, *};
+use core::convert::TryFrom;
+use core::time::Duration;
+
+fn secs(s: u64) -> Duration {
+    Duration::from_secs(s)
+}
+
+const WILL: LastWill<'static> = LastWill {
+    topic: "status",
+    message: b"offline",
+    qos: QoS::AtLeastOnce,
+    retain: true,
+};
+
+fn connect(clean_session: bool) -> Connect<'static> {
+    Connect {
+        protocol: Protocol::MQTT311,
+        keep_alive: 10,
+        client_id: "test",
+        clean_session,
+        last_will: Some(WILL),
+        username: None,
+        password: None,
+    }
+}
+
+fn connack(session_present: bool) -> Packet<'static> {
+    Packet::Connack(Connack {
+        session_present,
+        code: ConnectReturnCode::Accepted,
+    })
+}
+
+fn connect_to<'a>(session: &mut ServerSession<'a, 4>, clean_session: bool, present: bool) {
+    assert_eq!(
+        Ok(Some(ServerEvent::Connect(connect(clean_session)))),
+        session.handle(Packet::Connect(connect(clean_session)), secs(0))
+    );
+    assert_eq!(None, session.poll_transmit());
+    session.accept().unwrap();
+    assert_eq!(Some(connack(present)), session.poll_transmit());
+}
+
+fn connected<'a>(clean_session: bool) -> ServerSession<'a, 4> {
+    let mut session = ServerSession::new();
+    connect_to(&mut session, clean_session, false);
+    session
+}
+
+fn publish(dup: bool, qospid: QosPid) -> Packet<'static> {
+    Packet::Publish(Publish {
+        dup,
+        qospid,
+        retain: false,
+        topic_name: "a/b",
+        payload: b"hi",
+    })
+}
+
+#[test]
+fn test_connect_order() {
+    let mut session = ServerSession::<4>::new();
+    // The first packet must be a Connect.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Pingreq)),
+        session.handle(Packet::Pingreq, secs(0))
+    );
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Connack)),
+        session.accept()
+    );
+    session.handle(Packet::Connect(connect(true)), secs(0)).unwrap();
+    // Nothing is processed before the connection is accepted.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Pingreq)),
+        session.handle(Packet::Pingreq, secs(0))
+    );
+    session.accept().unwrap();
+    assert_eq!(Some(connack(false)), session.poll_transmit());
+    // A second Connect is a protocol violation.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Connect)),
+        session.handle(Packet::Connect(connect(true)), secs(0))
+    );
+    assert_eq!(Some(WILL), session.disconnected());
+
+    session.handle(Packet::Connect(connect(true)), secs(0)).unwrap();
+    session.reject(ConnectReturnCode::NotAuthorized).unwrap();
+    let refused = Packet::Connack(Connack {
+        session_present: false,
+        code: ConnectReturnCode::NotAuthorized,
+    });
+    assert_eq!(Some(refused), session.poll_transmit());
+    assert_eq!(None, session.poll_transmit());
+    assert!(!session.is_connected());
+    assert_eq!(None, session.disconnected());
+}
+
+#[test]
+fn test_incoming() {
+    let mut session = connected(true);
+    let pid = Pid::try_from(7).unwrap();
+
+    let pkt = publish(false, QosPid::AtMostOnce);
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ServerEvent::Publish(_)))));
+    assert_eq!(None, session.poll_transmit());
+
+    let pkt = publish(false, QosPid::AtLeastOnce(pid));
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ServerEvent::Publish(_)))));
+    assert_eq!(Some(Packet::Puback(pid)), session.poll_transmit());
+
+    // Duplicate QoS 2 messages are acknowledged but only delivered once.
+    let pkt = publish(false, QosPid::ExactlyOnce(pid));
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ServerEvent::Publish(_)))));
+    assert_eq!(Some(Packet::Pubrec(pid)), session.poll_transmit());
+    let pkt = publish(true, QosPid::ExactlyOnce(pid));
+    assert_eq!(Ok(None), session.handle(pkt, secs(0)));
+    assert_eq!(Some(Packet::Pubrec(pid)), session.poll_transmit());
+    assert_eq!(Ok(None), session.handle(Packet::Pubrel(pid), secs(0)));
+    assert_eq!(Some(Packet::Pubcomp(pid)), session.poll_transmit());
+    let pkt = publish(false, QosPid::ExactlyOnce(pid));
+    assert!(matches!(session.handle(pkt, secs(0)), Ok(Some(ServerEvent::Publish(_)))));
+
+    let subscribe = Subscribe::new(pid, LimitedVec::new());
+    assert_eq!(
+        Ok(Some(ServerEvent::Subscribe(subscribe.clone()))),
+        session.handle(Packet::Subscribe(subscribe), secs(0))
+    );
+    let unsubscribe = Unsubscribe {
+        pid,
+        topics: LimitedVec::new(),
+    };
+    assert_eq!(
+        Ok(Some(ServerEvent::Unsubscribe(unsubscribe.clone()))),
+        session.handle(Packet::Unsubscribe(unsubscribe), secs(0))
+    );
+    assert_eq!(Some(Packet::Pubrec(pid)), session.poll_transmit());
+    assert_eq!(Some(Packet::Unsuback(pid)), session.poll_transmit());
+}
+
+#[test]
+fn test_outgoing() {
+    let mut session = connected(true);
+    let pid1 = session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap().unwrap();
+    let pid2 = session.publish("a/b", b"hi", QoS::ExactlyOnce, false).unwrap().unwrap();
+    assert_eq!(Some(publish(false, QosPid::AtLeastOnce(pid1))), session.poll_transmit());
+    assert_eq!(Some(publish(false, QosPid::ExactlyOnce(pid2))), session.poll_transmit());
+    assert_eq!(
+        Ok(Some(ServerEvent::Published(pid1))),
+        session.handle(Packet::Puback(pid1), secs(0))
+    );
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Pubcomp)),
+        session.handle(Packet::Pubcomp(pid2), secs(0))
+    );
+    assert_eq!(Ok(None), session.handle(Packet::Pubrec(pid2), secs(0)));
+    assert_eq!(Some(Packet::Pubrel(pid2)), session.poll_transmit());
+    assert_eq!(
+        Ok(Some(ServerEvent::Published(pid2))),
+        session.handle(Packet::Pubcomp(pid2), secs(0))
+    );
+    assert_eq!(0, session.in_flight());
+}
+
+#[test]
+fn test_offline_queue() {
+    let mut session = connected(false);
+    let pid1 = session.publish("a/b", b"hi", QoS::AtLeastOnce, false).unwrap().unwrap();
+    assert_eq!(Some(publish(false, QosPid::AtLeastOnce(pid1))), session.poll_transmit());
+    assert_eq!(Some(WILL), session.disconnected());
+    assert!(session.is_persistent());
+
+    // Messages are kept for the offline client, except QoS 0.
+    assert_eq!(Ok(None), session.publish("a/b", b"hi", QoS::AtMostOnce, false));
+    let pid2 = session.publish("a/b", b"hi", QoS::ExactlyOnce, false).unwrap().unwrap();
+    assert_eq!(None, session.poll_transmit());
+    assert_eq!(2, session.in_flight());
+
+    connect_to(&mut session, false, true);
+    assert_eq!(Some(publish(true, QosPid::AtLeastOnce(pid1))), session.poll_transmit());
+    assert_eq!(Some(publish(false, QosPid::ExactlyOnce(pid2))), session.poll_transmit());
+    assert_eq!(None, session.poll_transmit());
+
+    // A clean session drops everything, and isn't kept after the connection.
+    assert_eq!(
+        Ok(Some(ServerEvent::Disconnected)),
+        session.handle(Packet::Disconnect, secs(0))
+    );
+    connect_to(&mut session, true, false);
+    assert_eq!(0, session.in_flight());
+    assert_eq!(Some(WILL), session.disconnected());
+    assert!(!session.is_persistent());
+    assert_eq!(Ok(None), session.publish("a/b", b"hi", QoS::AtLeastOnce, false));
+    assert_eq!(0, session.in_flight());
+}
+
+#[test]
+fn test_keep_alive() {
+    let mut session = connected(true);
+    assert_eq!(Some(secs(15)), session.poll_timeout());
+    assert!(!session.is_timed_out(secs(14)));
+    assert_eq!(Ok(None), session.handle(Packet::Pingreq, secs(14)));
+    assert_eq!(Some(Packet::Pingresp), session.poll_transmit());
+    assert!(!session.is_timed_out(secs(28)));
+    assert!(session.is_timed_out(secs(29)));
+    assert_eq!(Some(WILL), session.disconnected());
+    assert_eq!(None, session.poll_timeout());
+}
+
+#[test]
+fn test_clean_disconnect() {
+    let mut session = connected(true);
+    assert_eq!(
+        Ok(Some(ServerEvent::Disconnected)),
+        session.handle(Packet::Disconnect, secs(0))
+    );
+    assert!(!session.is_connected());
+    // No Last Will after a Disconnect packet.
+    assert_eq!(None, session.disconnected());
+}
+
+#[test]
+fn test_borrowed_packets() {
+    let mut session = ServerSession::<4>::new();
+    {
+        // Packets only need to live for the duration of `handle()`.
+        let mut buf = [0; 64];
+        let len = encode_slice(&Packet::Connect(connect(true)), &mut buf).unwrap();
+        let packet = decode_slice(&buf[..len]).unwrap().unwrap();
+        session.handle(packet, secs(0)).unwrap();
+    }
+    session.accept().unwrap();
+    assert_eq!(Some(WILL), session.disconnected());
+    assert_eq!(None, session.disconnected());
+}
@@ -0,0 +1,187 @@
+use crate::{decoder::read_frame_len, *};
+use std::{
+    io::{ErrorKind, Read, Write},
+    vec::Vec,
+};
+
+/// Number of bytes requested from the reader when the size of the next packet isn't known yet.
+const READ_CHUNK: usize = 4096;
+
+/// Blocking reader of MQTT 3.1.1 packets, for a `TcpStream` or any other [Read] implementation.
+///
+/// Data is buffered internally, and each packet borrows from that buffer until the next call to
+/// [read_packet()](#method.read_packet). Packets bigger than the `max_packet_size` of the
+/// [DecodeOptions] are rejected before they are buffered.
+///
+/// ```
+/// # use mqttrs::*;
+/// let data: &[u8] = &[0b11000000, 0, 0b00110000, 5, 0, 3, b'a', b'/', b'b', 0b11100000];
+/// let mut reader = PacketReader::new(data);
+/// assert_eq!(Ok(Some(Packet::Pingreq)), reader.read_packet());
+/// assert!(matches!(reader.read_packet(), Ok(Some(Packet::Publish(_)))));
+/// // The last packet is truncated.
+/// assert_eq!(Err(Error::UnexpectedEof), reader.read_packet());
+/// ```
+///
+/// [Read]: https://doc.rust-lang.org/std/io/trait.Read.html
+/// [DecodeOptions]: struct.DecodeOptions.html
+#[derive(Debug)]
+pub struct PacketReader<R> {
+    inner: R,
+    options: DecodeOptions,
+    buf: Vec<u8>,
+    /// Length of the packet returned by the last call to `read_packet()`, at the start of `buf`.
+    consumed: usize,
+}
+
+impl<R: Read> PacketReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_options(inner, DecodeOptions::default())
+    }
+
+    /// Create a reader decoding with the given [DecodeOptions].
+    ///
+    /// [DecodeOptions]: struct.DecodeOptions.html
+    pub fn with_options(inner: R, options: DecodeOptions) -> Self {
+        PacketReader {
+            inner,
+            options,
+            buf: Vec::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Read the next packet, blocking until it has been fully received.
+    ///
+    /// Returns `Ok(None)` when the reader reaches end of file between two packets, and
+    /// `Error::UnexpectedEof` when it does in the middle of a packet.
+    pub fn read_packet(&mut self) -> Result<Option<Packet<'_>>, Error> {
+        self.buf.drain(..self.consumed);
+        self.consumed = 0;
+
+        // Read until the buffer holds a whole packet.
+        loop {
+            let wanted = match read_frame_len(&self.buf)? {
+                Some((header_len, remaining_len)) => {
+                    let frame_len = header_len + remaining_len;
+                    self.options.check_packet_size(frame_len)?;
+                    if self.buf.len() >= frame_len {
+                        break;
+                    }
+                    frame_len - self.buf.len()
+                }
+                None => READ_CHUNK,
+            };
+            if self.fill(wanted)? == 0 {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(Error::UnexpectedEof);
+            }
+        }
+
+        match decode_slice_with_options(&self.buf, &self.options)? {
+            Some((len, packet)) => {
+                self.consumed = len;
+                Ok(Some(packet))
+            }
+            None => Err(Error::InvalidLength),
+        }
+    }
+
+    /// Read up to `len` more bytes into the buffer, returning 0 at end of file.
+    fn fill(&mut self, len: usize) -> Result<usize, Error> {
+        let start = self.buf.len();
+        self.buf.resize(start + len, 0);
+        loop {
+            match self.inner.read(&mut self.buf[start..]) {
+                Ok(n) => {
+                    self.buf.truncate(start + n);
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buf.truncate(start);
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+
+    /// Get a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader.
+    ///
+    /// Reading from it directly would desynchronize the packet stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the underlying reader. Data that was buffered but not returned yet is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Blocking writer of MQTT 3.1.1 packets, for a `TcpStream` or any other [Write] implementation.
+///
+/// Packets are encoded with [encode_slice()] into an internal buffer, which grows to the size of
+/// the biggest packet written, and written whole.
+///
+/// ```
+/// # use mqttrs::*;
+/// let mut writer = PacketWriter::new(Vec::new());
+/// writer.write_packet(&Packet::Pingreq).unwrap();
+/// writer.write_packet(&Packet::Disconnect).unwrap();
+/// assert_eq!(&[0b11000000, 0, 0b11100000, 0], &writer.into_inner()[..]);
+/// ```
+///
+/// [Write]: https://doc.rust-lang.org/std/io/trait.Write.html
+/// [encode_slice()]: fn.encode_slice.html
+#[derive(Debug)]
+pub struct PacketWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> PacketWriter<W> {
+    pub fn new(inner: W) -> Self {
+        PacketWriter {
+            inner,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Encode and write a whole packet.
+    pub fn write_packet(&mut self, packet: &Packet) -> Result<(), Error> {
+        let len = packet.encoded_len();
+        if self.buf.len() < len {
+            self.buf.resize(len, 0);
+        }
+        let len = encode_slice(packet, &mut self.buf)?;
+        Ok(self.inner.write_all(&self.buf[..len])?)
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.inner.flush()?)
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{test_util::publish, *};
+use std::io::{self, ErrorKind, Read};
+
+/// Reader returning at most `chunk` bytes per call, interrupted before each of them.
+struct SlowReader<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    interrupt: bool,
+}
+
+impl Read for SlowReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.interrupt = !self.interrupt;
+        if self.interrupt {
+            return Err(ErrorKind::Interrupted.into());
+        }
+        let len = buf.len().min(self.chunk).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_roundtrip() {
+    let payload = [7; 5000];
+    let mut writer = PacketWriter::new(Vec::new());
+    writer.write_packet(&Packet::Pingreq).unwrap();
+    writer.write_packet(&publish(&payload)).unwrap();
+    writer.write_packet(&publish(b"hello")).unwrap();
+    writer.flush().unwrap();
+    let data = writer.into_inner();
+
+    for &chunk in &[1, 3, 10_000] {
+        let mut reader = PacketReader::new(SlowReader {
+            data: &data,
+            chunk,
+            interrupt: false,
+        });
+        assert_eq!(Ok(Some(Packet::Pingreq)), reader.read_packet());
+        assert_eq!(Ok(Some(publish(&payload))), reader.read_packet());
+        assert_eq!(Ok(Some(publish(b"hello"))), reader.read_packet());
+        assert_eq!(Ok(None), reader.read_packet());
+        assert_eq!(Ok(None), reader.read_packet());
+    }
+}
+
+#[test]
+fn test_eof() {
+    let data = encode_to_vec(&publish(b"hello")).unwrap();
+    assert_eq!(Ok(None), PacketReader::new(&[][..]).read_packet());
+    // Truncated in the fixed header, and in the payload.
+    for len in &[1, data.len() - 1] {
+        let mut reader = PacketReader::new(&data[..*len]);
+        assert_eq!(Err(Error::UnexpectedEof), reader.read_packet());
+    }
+}
+
+#[test]
+fn test_errors() {
+    let opts = DecodeOptions {
+        max_packet_size: Some(8),
+        ..DecodeOptions::default()
+    };
+    let data = encode_to_vec(&publish(b"hello")).unwrap();
+    let mut reader = PacketReader::with_options(&data[..], opts);
+    assert_eq!(Err(Error::PacketTooLarge), reader.read_packet());
+
+    let mut reader = PacketReader::new(&[0b11000000, 1, 0][..]);
+    let err = Error::InvalidRemainingLength(PacketType::Pingreq, 1);
+    assert_eq!(Err(err), reader.read_packet());
+}
@@ -0,0 +1,162 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+
+use crate::{decoder::*, topic::check_topic_name, *};
+
+/// Fixed and variable header of a [Publish] packet, returned by [StreamingDecoder] before the
+/// payload.
+///
+/// [Publish]: struct.Publish.html
+/// [StreamingDecoder]: struct.StreamingDecoder.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishHeader<'a> {
+    pub dup: bool,
+    pub qospid: QosPid,
+    pub retain: bool,
+    pub topic_name: &'a str,
+    /// Total length of the payload that will follow.
+    pub payload_len: usize,
+}
+
+/// Item returned by [StreamingDecoder::decode()].
+///
+/// [StreamingDecoder::decode()]: struct.StreamingDecoder.html#method.decode
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+// Packets can't be boxed without `std`, and are returned by value like `decode_slice()` does.
+#[allow(clippy::large_enum_variant)]
+pub enum StreamEvent<'a> {
+    /// A complete packet other than `Publish`.
+    Packet(Packet<'a>),
+    /// The start of a `Publish` packet. Its payload follows as `Payload` chunks.
+    PublishHeader(PublishHeader<'a>),
+    /// A chunk of the current `Publish` payload.
+    Payload(&'a [u8]),
+}
+
+/// Incremental decoder, that doesn't need a whole `Publish` packet in memory.
+///
+/// A `Publish` is returned as a [PublishHeader] as soon as its variable header is received,
+/// followed by its payload in chunks, as they arrive. Other packets are returned whole, like
+/// [decode_slice()] does.
+///
+/// ```
+/// # use mqttrs::*;
+/// let mut decoder = StreamingDecoder::new();
+/// // The start of a Publish with a 5-byte payload, of which 2 bytes were received.
+/// let buf = [0b00110000, 10, 0, 3, b'a', b'/', b'b', b'h', b'e'];
+///
+/// let (n, event) = decoder.decode(&buf).unwrap().unwrap();
+/// match event {
+///     StreamEvent::PublishHeader(h) => assert_eq!(("a/b", 5), (h.topic_name, h.payload_len)),
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// assert_eq!(Ok(Some((2, StreamEvent::Payload(b"he")))), decoder.decode(&buf[n..]));
+/// assert_eq!(3, decoder.remaining_payload());
+///
+/// // The rest of the payload, and the next packet.
+/// let buf = [b'l', b'l', b'o', 0b11000000, 0];
+/// assert_eq!(Ok(Some((3, StreamEvent::Payload(b"llo")))), decoder.decode(&buf));
+/// assert_eq!(Ok(Some((2, StreamEvent::Packet(Packet::Pingreq)))), decoder.decode(&buf[3..]));
+/// ```
+///
+/// [PublishHeader]: struct.PublishHeader.html
+/// [decode_slice()]: fn.decode_slice.html
+#[derive(Debug, Clone, Default)]
+pub struct StreamingDecoder {
+    options: DecodeOptions,
+    remaining_payload: usize,
+}
+
+impl StreamingDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a decoder with the given [DecodeOptions].
+    ///
+    /// [DecodeOptions]: struct.DecodeOptions.html
+    pub fn with_options(options: DecodeOptions) -> Self {
+        StreamingDecoder {
+            options,
+            remaining_payload: 0,
+        }
+    }
+
+    /// Number of payload bytes of the current `Publish` that haven't been returned yet.
+    pub fn remaining_payload(&self) -> usize {
+        self.remaining_payload
+    }
+
+    /// Decode the next item from the start of `buf`, returning the number of bytes consumed with
+    /// it.
+    ///
+    /// Returns `Ok(None)` if more data is needed. The consumed bytes must be removed from the
+    /// buffer before the next call, the unconsumed ones must be kept.
+    pub fn decode<'a>(
+        &mut self,
+        buf: &'a [u8],
+    ) -> Result<Option<(usize, StreamEvent<'a>)>, Error> {
+        if self.remaining_payload > 0 {
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            let len = buf.len().min(self.remaining_payload);
+            self.remaining_payload -= len;
+            return Ok(Some((len, StreamEvent::Payload(&buf[..len]))));
+        }
+
+        let (header_len, remaining_len) = match read_frame_len(buf)? {
+            Some(lens) => lens,
+            None => return Ok(None),
+        };
+        let header = Header::new(buf[0])?;
+        self.options.check_packet_size(header_len + remaining_len)?;
+        if header.typ != PacketType::Publish {
+            let decoded = decode_slice_with_options(buf, &self.options)?;
+            return Ok(decoded.map(|(len, packet)| (len, StreamEvent::Packet(packet))));
+        }
+
+        // Topic length (2) and pid (0/2).
+        let pid_len = match header.qos {
+            QoS::AtMostOnce => 0,
+            _ => 2,
+        };
+        if remaining_len < 2 + pid_len {
+            return Err(Error::InvalidLength);
+        }
+        if buf.len() < header_len + 2 {
+            return Ok(None);
+        }
+        let topic_len = ((buf[header_len] as usize) << 8) | buf[header_len + 1] as usize;
+        let variable_len = 2 + topic_len + pid_len;
+        if variable_len > remaining_len {
+            return Err(Error::InvalidLength);
+        }
+        if buf.len() < header_len + variable_len {
+            return Ok(None);
+        }
+
+        let mut offset = header_len;
+        let topic_name = read_str(buf, &mut offset)?;
+        self.options.check_topic_len(topic_name)?;
+        if self.options.strict {
+            check_topic_name(topic_name)?;
+        }
+        let qospid = match header.qos {
+            QoS::AtMostOnce => QosPid::AtMostOnce,
+            QoS::AtLeastOnce => QosPid::AtLeastOnce(Pid::from_buffer(buf, &mut offset)?),
+            QoS::ExactlyOnce => QosPid::ExactlyOnce(Pid::from_buffer(buf, &mut offset)?),
+        };
+        self.remaining_payload = remaining_len - variable_len;
+        let publish = PublishHeader {
+            dup: header.dup,
+            qospid,
+            retain: header.retain,
+            topic_name,
+            payload_len: self.remaining_payload,
+        };
+        Ok(Some((offset, StreamEvent::PublishHeader(publish))))
+    }
+}
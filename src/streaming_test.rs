@@ -0,0 +1,75 @@
+use crate::*;
+use core::convert::TryFrom;
+
+#[test]
+fn test_byte_by_byte() {
+    let payload = [9u8; 300];
+    let publish = Publish {
+        dup: false,
+        qospid: QosPid::AtLeastOnce(Pid::try_from(5).unwrap()),
+        retain: true,
+        topic_name: "fw/image",
+        payload: &payload,
+    };
+    let mut slice = [0u8; 512];
+    let mut len = encode_slice(&Packet::Pingresp, &mut slice).unwrap();
+    len += encode_slice(&publish.clone().into(), &mut slice[len..]).unwrap();
+    len += encode_slice(&Packet::Puback(Pid::new()), &mut slice[len..]).unwrap();
+
+    // Feed the decoder one byte at a time, keeping unconsumed bytes in `pending`.
+    let mut decoder = StreamingDecoder::new();
+    let mut pending = [0u8; 16];
+    let mut pending_len = 0;
+    let mut packets = 0;
+    let mut headers = 0;
+    let mut received = [0u8; 300];
+    let mut received_len = 0;
+    for byte in &slice[..len] {
+        pending[pending_len] = *byte;
+        pending_len += 1;
+        while let Some((n, event)) = decoder.decode(&pending[..pending_len]).unwrap() {
+            match event {
+                StreamEvent::Packet(_) => packets += 1,
+                StreamEvent::PublishHeader(h) => {
+                    assert_eq!("fw/image", h.topic_name);
+                    assert_eq!(publish.qospid, h.qospid);
+                    assert!(h.retain && !h.dup);
+                    assert_eq!(300, h.payload_len);
+                    headers += 1;
+                }
+                StreamEvent::Payload(chunk) => {
+                    received[received_len..received_len + chunk.len()].copy_from_slice(chunk);
+                    received_len += chunk.len();
+                }
+            }
+            pending.copy_within(n..pending_len, 0);
+            pending_len -= n;
+        }
+    }
+    assert_eq!((2, 1, 0), (packets, headers, pending_len));
+    assert_eq!(&payload[..], &received[..]);
+    assert_eq!(0, decoder.remaining_payload());
+}
+
+#[test]
+fn test_empty_payload() {
+    let mut decoder = StreamingDecoder::new();
+    let buf = [0b00110000, 3, 0, 1, b'a', 0b11100000, 0];
+    match decoder.decode(&buf) {
+        Ok(Some((5, StreamEvent::PublishHeader(h)))) => assert_eq!(0, h.payload_len),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(Ok(Some((2, StreamEvent::Packet(Packet::Disconnect)))), decoder.decode(&buf[5..]));
+}
+
+#[test]
+fn test_invalid() {
+    let mut decoder = StreamingDecoder::new();
+    // Topic longer than the remaining length.
+    assert_eq!(Err(Error::InvalidLength), decoder.decode(&[0b00110000, 3, 0, 5]));
+    // No room for the pid.
+    assert_eq!(Err(Error::InvalidLength), decoder.decode(&[0b00110010, 3, 0, 1]));
+
+    let mut decoder = StreamingDecoder::with_options(DecodeOptions::strict());
+    assert_eq!(Err(Error::InvalidTopicName), decoder.decode(&[0b00110000, 3, 0, 1, b'#']));
+}
@@ -4,37 +4,86 @@ use crate::{decoder::*, encoder::*, *};
 #[cfg(feature = "derive")]
 use serde::{Deserialize, Serialize};
 
+/// Vec of up to `N` items without the `std` feature, unlimited with it.
 #[cfg(feature = "std")]
-pub type LimitedVec<T> = std::vec::Vec<T>;
+pub type LimitedVec<T, const N: usize = 5> = std::vec::Vec<T>;
 #[cfg(not(feature = "std"))]
-pub type LimitedVec<T> = heapless::Vec<T, 5>;
+pub type LimitedVec<T, const N: usize = 5> = heapless::Vec<T, N>;
 
+/// String of up to `L` bytes without the `std` feature, unlimited with it.
 #[cfg(feature = "std")]
-pub type LimitedString = std::string::String;
+pub type LimitedString<const L: usize = 256> = std::string::String;
 #[cfg(not(feature = "std"))]
-pub type LimitedString = heapless::String<256>;
+pub type LimitedString<const L: usize = 256> = heapless::String<L>;
 
 use core::str::FromStr;
 
-/// Subscribe topic.
-///
-/// [Subscribe] packets contain a `Vec` of those.
+/// A [LimitedVec] of any capacity.
+pub(crate) trait LimitedPush<T> {
+    fn limited_push(&mut self, item: T) -> Result<(), Error>;
+}
+
+#[cfg(feature = "std")]
+impl<T> LimitedPush<T> for std::vec::Vec<T> {
+    fn limited_push(&mut self, item: T) -> Result<(), Error> {
+        self.push(item);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T, const N: usize> LimitedPush<T> for heapless::Vec<T, N> {
+    fn limited_push(&mut self, item: T) -> Result<(), Error> {
+        self.push(item).map_err(|_| Error::InvalidLength)
+    }
+}
+
+/// Push onto a [LimitedVec], returning `Error::InvalidLength` if a no_std vec is full.
+pub(crate) fn limited_push<T>(vec: &mut impl LimitedPush<T>, item: T) -> Result<(), Error> {
+    vec.limited_push(item)
+}
+
+/// Copy into a [LimitedString], returning `Error::InvalidLength` if a no_std string is too short.
+pub(crate) fn limited_string<S: FromStr>(s: &str) -> Result<S, Error> {
+    S::from_str(s).map_err(|_| Error::InvalidLength)
+}
+
+/// [SubscribeTopic] whose `topic_path` holds up to `L` bytes without the `std` feature.
 ///
-/// [Subscribe]: struct.Subscribe.html
+/// [SubscribeTopic]: type.SubscribeTopic.html
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "derive", derive(Serialize, Deserialize))]
-pub struct SubscribeTopic {
-    pub topic_path: LimitedString,
+pub struct GenericSubscribeTopic<const L: usize> {
+    pub topic_path: LimitedString<L>,
     pub qos: QoS,
 }
 
-impl SubscribeTopic {
-    pub(crate) fn from_buffer(buf: &[u8], offset: &mut usize) -> Result<Self, Error> {
-        let topic_path = LimitedString::from_str(read_str(buf, offset)?).unwrap();
-        let qos = QoS::from_u8(buf[*offset])?;
-        *offset += 1;
-        Ok(SubscribeTopic { topic_path, qos })
+/// Subscribe topic.
+///
+/// [Subscribe] packets contain a `Vec` of those.
+///
+/// [Subscribe]: type.Subscribe.html
+pub type SubscribeTopic = GenericSubscribeTopic<256>;
+
+impl<const L: usize> GenericSubscribeTopic<L> {
+    pub(crate) fn from_buffer(
+        buf: &[u8],
+        offset: &mut usize,
+        opts: &DecodeOptions,
+    ) -> Result<Self, Error> {
+        let topic_path = read_str(buf, offset)?;
+        opts.check_topic_len(topic_path)?;
+        let topic_path = limited_string(topic_path)?;
+        let qos = QoS::from_u8(read_u8(buf, offset)?)?;
+        Ok(GenericSubscribeTopic { topic_path, qos })
+    }
+
+    /// Validate `topic_path` as a [TopicFilter], to match it against topic names.
+    ///
+    /// [TopicFilter]: struct.TopicFilter.html
+    pub fn topic_filter(&self) -> Result<TopicFilter<'_>, Error> {
+        TopicFilter::new(&self.topic_path)
     }
 }
 
@@ -42,7 +91,7 @@ impl SubscribeTopic {
 ///
 /// [Suback] packets contain a `Vec` of those.
 ///
-/// [Suback]: struct.Subscribe.html
+/// [Suback]: type.Suback.html
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SubscribeReturnCodes {
@@ -51,9 +100,8 @@ pub enum SubscribeReturnCodes {
 }
 
 impl SubscribeReturnCodes {
-    pub(crate) fn from_buffer<'a>(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
-        let code = buf[*offset];
-        *offset += 1;
+    pub(crate) fn from_buffer(buf: &[u8], offset: &mut usize) -> Result<Self, Error> {
+        let code = read_u8(buf, offset)?;
 
         if code == 0x80 {
             Ok(SubscribeReturnCodes::Failure)
@@ -62,78 +110,119 @@ impl SubscribeReturnCodes {
         }
     }
 
-    pub(crate) fn to_u8(&self) -> u8 {
-        match *self {
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
             SubscribeReturnCodes::Failure => 0x80,
             SubscribeReturnCodes::Success(qos) => qos.to_u8(),
         }
     }
 }
 
-/// Subscribe packet ([MQTT 3.8]).
+/// [Subscribe] holding up to `N` topics of up to `L` bytes without the `std` feature.
 ///
-/// [MQTT 3.8]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718063
+/// [Subscribe]: type.Subscribe.html
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, PartialEq)]
-pub struct Subscribe {
+pub struct GenericSubscribe<const N: usize, const L: usize> {
     pub pid: Pid,
-    pub topics: LimitedVec<SubscribeTopic>,
+    pub topics: LimitedVec<GenericSubscribeTopic<L>, N>,
 }
 
-/// Subsack packet ([MQTT 3.9]).
+/// [Suback] holding up to `N` return codes without the `std` feature.
 ///
-/// [MQTT 3.9]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718068
+/// [Suback]: type.Suback.html
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, PartialEq)]
-pub struct Suback {
+pub struct GenericSuback<const N: usize> {
     pub pid: Pid,
-    pub return_codes: LimitedVec<SubscribeReturnCodes>,
+    pub return_codes: LimitedVec<SubscribeReturnCodes, N>,
 }
 
-/// Unsubscribe packet ([MQTT 3.10]).
+/// [Unsubscribe] holding up to `N` topics of up to `L` bytes without the `std` feature.
 ///
-/// [MQTT 3.10]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718072
+/// [Unsubscribe]: type.Unsubscribe.html
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, PartialEq)]
-pub struct Unsubscribe {
+pub struct GenericUnsubscribe<const N: usize, const L: usize> {
     pub pid: Pid,
-    pub topics: LimitedVec<LimitedString>,
+    pub topics: LimitedVec<LimitedString<L>, N>,
 }
 
+/// Subscribe packet ([MQTT 3.8]).
+///
+/// Without the `std` feature, it holds up to 5 topics of up to 256 bytes, see [GenericSubscribe]
+/// for other capacities.
+///
+/// [MQTT 3.8]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718063
+/// [GenericSubscribe]: struct.GenericSubscribe.html
+pub type Subscribe = GenericSubscribe<5, 256>;
+
+/// Subsack packet ([MQTT 3.9]).
+///
+/// Without the `std` feature, it holds up to 5 return codes, see [GenericSuback] for other
+/// capacities.
+///
+/// [MQTT 3.9]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718068
+/// [GenericSuback]: struct.GenericSuback.html
+pub type Suback = GenericSuback<5>;
+
+/// Unsubscribe packet ([MQTT 3.10]).
+///
+/// Without the `std` feature, it holds up to 5 topics of up to 256 bytes, see
+/// [GenericUnsubscribe] for other capacities.
+///
+/// [MQTT 3.10]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718072
+/// [GenericUnsubscribe]: struct.GenericUnsubscribe.html
+pub type Unsubscribe = GenericUnsubscribe<5, 256>;
+
 impl Subscribe {
     pub fn new(pid: Pid, topics: LimitedVec<SubscribeTopic>) -> Self {
         Subscribe { pid, topics }
     }
+}
 
+impl<const N: usize, const L: usize> GenericSubscribe<N, L> {
     pub(crate) fn from_buffer(
         remaining_len: usize,
         buf: &[u8],
         offset: &mut usize,
+        opts: &DecodeOptions,
     ) -> Result<Self, Error> {
         let payload_end = *offset + remaining_len;
         let pid = Pid::from_buffer(buf, offset)?;
 
         let mut topics = LimitedVec::new();
         while *offset < payload_end {
-            let _res = topics.push(SubscribeTopic::from_buffer(buf, offset)?);
-
-            #[cfg(not(feature = "std"))]
-            _res.map_err(|_| Error::InvalidLength)?;
+            let topic = GenericSubscribeTopic::from_buffer(buf, offset, opts)?;
+            if opts.strict {
+                topic.topic_filter()?;
+            }
+            opts.check_topic_count(topics.len() + 1)?;
+            limited_push(&mut topics, topic)?;
         }
 
-        Ok(Subscribe { pid, topics })
+        Ok(GenericSubscribe { pid, topics })
     }
 
-    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
-        let header: u8 = 0b10000010;
-        check_remaining(buf, offset, 1)?;
-        write_u8(buf, offset, header)?;
-
+    /// Length of the packet after the fixed header.
+    pub(crate) fn remaining_len(&self) -> usize {
         // Length: pid(2) + topic.for_each(2+len + qos(1))
         let mut length = 2;
         for topic in &self.topics {
             length += topic.topic_path.len() + 2 + 1;
         }
+        length
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        for topic in &self.topics {
+            topic.topic_filter()?;
+        }
+        let header: u8 = 0b10000010;
+        check_remaining(buf, offset, 1)?;
+        write_u8(buf, offset, header)?;
+
+        let length = self.remaining_len();
         let write_len = write_length(buf, offset, length)? + 1;
 
         // Pid
@@ -153,32 +242,47 @@ impl Unsubscribe {
     pub fn new(pid: Pid, topics: LimitedVec<LimitedString>) -> Self {
         Unsubscribe { pid, topics }
     }
+}
 
+impl<const N: usize, const L: usize> GenericUnsubscribe<N, L> {
     pub(crate) fn from_buffer(
         remaining_len: usize,
         buf: &[u8],
         offset: &mut usize,
+        opts: &DecodeOptions,
     ) -> Result<Self, Error> {
         let payload_end = *offset + remaining_len;
         let pid = Pid::from_buffer(buf, offset)?;
 
         let mut topics = LimitedVec::new();
         while *offset < payload_end {
-            let _res = topics.push(LimitedString::from_str(read_str(buf, offset)?).unwrap());
-
-            #[cfg(not(feature = "std"))]
-            _res.map_err(|_| Error::InvalidLength)?;
+            let topic = read_str(buf, offset)?;
+            opts.check_topic_len(topic)?;
+            if opts.strict {
+                TopicFilter::new(topic)?;
+            }
+            opts.check_topic_count(topics.len() + 1)?;
+            limited_push(&mut topics, limited_string(topic)?)?;
         }
 
-        Ok(Unsubscribe { pid, topics })
+        Ok(GenericUnsubscribe { pid, topics })
     }
 
-    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
-        let header: u8 = 0b10100010;
+    /// Length of the packet after the fixed header.
+    pub(crate) fn remaining_len(&self) -> usize {
         let mut length = 2;
         for topic in &self.topics {
             length += 2 + topic.len();
         }
+        length
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let header: u8 = 0b10100010;
+        for topic in &self.topics {
+            TopicFilter::new(topic)?;
+        }
+        let length = self.remaining_len();
         check_remaining(buf, offset, 1)?;
         write_u8(buf, offset, header)?;
 
@@ -195,7 +299,9 @@ impl Suback {
     pub fn new(pid: Pid, return_codes: LimitedVec<SubscribeReturnCodes>) -> Self {
         Suback { pid, return_codes }
     }
+}
 
+impl<const N: usize> GenericSuback<N> {
     pub(crate) fn from_buffer(
         remaining_len: usize,
         buf: &[u8],
@@ -206,18 +312,20 @@ impl Suback {
 
         let mut return_codes = LimitedVec::new();
         while *offset < payload_end {
-            let _res = return_codes.push(SubscribeReturnCodes::from_buffer(buf, offset)?);
-
-            #[cfg(not(feature = "std"))]
-            _res.map_err(|_| Error::InvalidLength)?;
+            limited_push(&mut return_codes, SubscribeReturnCodes::from_buffer(buf, offset)?)?;
         }
 
-        Ok(Suback { pid, return_codes })
+        Ok(GenericSuback { pid, return_codes })
+    }
+
+    /// Length of the packet after the fixed header.
+    pub(crate) fn remaining_len(&self) -> usize {
+        2 + self.return_codes.len()
     }
 
     pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
         let header: u8 = 0b10010000;
-        let length = 2 + self.return_codes.len();
+        let length = self.remaining_len();
         check_remaining(buf, offset, 1)?;
         write_u8(buf, offset, header)?;
 
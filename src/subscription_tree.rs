@@ -0,0 +1,533 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use crate::{
+    subscribe::{limited_push, LimitedString, LimitedVec},
+    *,
+};
+use core::str::{FromStr, Split};
+#[cfg(feature = "std")]
+use std::collections::{BTreeMap, HashMap};
+
+/// Storage used by [SubscriptionTree].
+///
+/// This is a `Vec` that grows as needed with the `std` feature, otherwise a `heapless::Vec` of
+/// capacity `N`.
+///
+/// [SubscriptionTree]: struct.SubscriptionTree.html
+#[cfg(feature = "std")]
+pub type TreeVec<T, const N: usize> = std::vec::Vec<T>;
+#[cfg(not(feature = "std"))]
+pub type TreeVec<T, const N: usize> = heapless::Vec<T, N>;
+
+/// Push onto a [TreeVec], returning `Error::QueueFull` if a no_std vec is full.
+fn tree_push<T, const N: usize>(vec: &mut TreeVec<T, N>, item: T) -> Result<(), Error> {
+    #[cfg(feature = "std")]
+    {
+        vec.push(item);
+        Ok(())
+    }
+    #[cfg(not(feature = "std"))]
+    vec.push(item).map_err(|_| Error::QueueFull)
+}
+
+/// Store `item` in the first free slot, returning its index.
+fn alloc<T, const N: usize>(vec: &mut TreeVec<Option<T>, N>, item: T) -> Result<usize, Error> {
+    match vec.iter().position(Option::is_none) {
+        Some(i) => {
+            vec[i] = Some(item);
+            Ok(i)
+        }
+        None => {
+            tree_push::<_, N>(vec, Some(item))?;
+            Ok(vec.len() - 1)
+        }
+    }
+}
+
+/// One level of a topic filter. Subscriptions are a linked list of slot indexes, and so are
+/// children without `std`.
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    level: LimitedString,
+    parent: usize,
+    #[cfg(feature = "std")]
+    children: BTreeMap<LimitedString, usize>,
+    #[cfg(not(feature = "std"))]
+    first_child: Option<usize>,
+    #[cfg(not(feature = "std"))]
+    next_sibling: Option<usize>,
+    first_sub: Option<usize>,
+}
+
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+struct Sub {
+    /// Slot of the subscribed value.
+    value: usize,
+    qos: QoS,
+    next: Option<usize>,
+}
+
+/// A subscribed value, stored once for all its subscriptions so that lookups can tell values
+/// apart by slot.
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+struct Entry<V> {
+    value: V,
+    subs: usize,
+}
+
+/// Values matched by a lookup, by slot, with their maximum `QoS`.
+struct Matches<const N: usize> {
+    found: TreeVec<(usize, QoS), N>,
+    /// Index of each value in `found`.
+    #[cfg(feature = "std")]
+    index: HashMap<usize, usize>,
+}
+
+const ROOT: usize = 0;
+
+/// Topic filters of many subscribers, organized as a trie of topic levels.
+///
+/// Each subscription associates a [TopicFilter] with a value of type `V` (typically a client id
+/// or a connection handle) and a maximum [QoS]. [`lookup()`] walks the trie to find the values
+/// whose filters match a topic name, without comparing it to each filter.
+///
+/// A value subscribed with several filters that match the same topic name is returned once, with
+/// the maximum `QoS` of these subscriptions ([MQTT 3.3.5]).
+///
+/// With the `std` feature, storage grows as needed. Without it, `N` is the maximum number of
+/// subscriptions, of distinct values and of trie nodes (one per distinct filter level), and
+/// subscribing returns `Error::QueueFull` when it is reached.
+///
+/// ```
+/// # use mqttrs::*;
+/// # use core::convert::TryFrom;
+/// let mut tree = SubscriptionTree::<&str, 64>::new();
+/// let topic = |path: &str, qos| SubscribeTopic { topic_path: path.parse().unwrap(), qos };
+/// let topics = [topic("sensors/+/temp", QoS::AtLeastOnce), topic("sensors/b+", QoS::AtMostOnce)];
+/// let subscribe = Subscribe::new(Pid::try_from(1).unwrap(),
+///                                IntoIterator::into_iter(topics).collect());
+/// let return_codes = tree.subscribe(&subscribe, "alice");
+/// assert_eq!(&[SubscribeReturnCodes::Success(QoS::AtLeastOnce),
+///              SubscribeReturnCodes::Failure],
+///            &return_codes[..]);
+///
+/// tree.insert(&topic("#", QoS::ExactlyOnce), "bob").unwrap();
+///
+/// let matches = tree.lookup("sensors/kitchen/temp");
+/// assert_eq!(2, matches.len());
+/// assert!(matches.contains(&(&"alice", QoS::AtLeastOnce)));
+/// assert_eq!(&[(&"bob", QoS::ExactlyOnce)], &tree.lookup("sensors/kitchen/humidity")[..]);
+/// ```
+///
+/// [TopicFilter]: struct.TopicFilter.html
+/// [QoS]: enum.QoS.html
+/// [`lookup()`]: #method.lookup
+/// [MQTT 3.3.5]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718042
+#[derive(Debug, Clone)]
+pub struct SubscriptionTree<V, const N: usize> {
+    /// Slot `ROOT` is the parent of the first level. Free slots are `None`.
+    nodes: TreeVec<Option<Node>, N>,
+    subs: TreeVec<Option<Sub>, N>,
+    values: TreeVec<Option<Entry<V>>, N>,
+    len: usize,
+}
+
+impl<V: PartialEq, const N: usize> SubscriptionTree<V, N> {
+    pub fn new() -> Self {
+        SubscriptionTree {
+            nodes: TreeVec::new(),
+            subs: TreeVec::new(),
+            values: TreeVec::new(),
+            len: 0,
+        }
+    }
+
+    /// Number of subscriptions.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Subscribe `value` to a topic filter.
+    ///
+    /// If `value` is already subscribed to the same filter, its `QoS` is replaced
+    /// ([MQTT 3.8.4]). Returns `Error::InvalidTopicFilter` if the filter isn't valid.
+    ///
+    /// [MQTT 3.8.4]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718066
+    pub fn insert(&mut self, topic: &SubscribeTopic, value: V) -> Result<(), Error> {
+        let filter = topic.topic_filter()?;
+        if self.nodes.is_empty() {
+            tree_push::<_, N>(&mut self.nodes, Some(Node::new("", ROOT)?))?;
+        }
+        let mut node = ROOT;
+        for level in filter.as_str().split('/') {
+            node = match self.child(node, level) {
+                Some(child) => child,
+                None => self.add_child(node, level)?,
+            };
+        }
+        let value = match self.find_value(&value) {
+            Some(value) => value,
+            None => match alloc::<_, N>(&mut self.values, Entry { value, subs: 0 }) {
+                Ok(value) => value,
+                Err(e) => {
+                    self.prune(node);
+                    return Err(e);
+                }
+            },
+        };
+        if let Some(sub) = self.find_sub(node, value) {
+            self.sub_mut(sub).qos = topic.qos;
+            return Ok(());
+        }
+        let sub = Sub {
+            value,
+            qos: topic.qos,
+            next: self.node(node).first_sub,
+        };
+        match alloc::<_, N>(&mut self.subs, sub) {
+            Ok(sub) => {
+                self.node_mut(node).first_sub = Some(sub);
+                self.entry_mut(value).subs += 1;
+                self.len += 1;
+                Ok(())
+            }
+            Err(e) => {
+                if self.entry_mut(value).subs == 0 {
+                    self.values[value] = None;
+                }
+                self.prune(node);
+                Err(e)
+            }
+        }
+    }
+
+    /// Subscribe `value` to all the topics of a `Subscribe` packet, returning the return codes
+    /// of the `Suback` answer.
+    pub fn subscribe(
+        &mut self,
+        subscribe: &Subscribe,
+        value: V,
+    ) -> LimitedVec<SubscribeReturnCodes>
+    where
+        V: Clone,
+    {
+        let mut return_codes = LimitedVec::new();
+        for topic in subscribe.topics.iter() {
+            let code = match self.insert(topic, value.clone()) {
+                Ok(()) => SubscribeReturnCodes::Success(topic.qos),
+                Err(_) => SubscribeReturnCodes::Failure,
+            };
+            // There are as many topics as return codes, this can't fail.
+            let _ = limited_push(&mut return_codes, code);
+        }
+        return_codes
+    }
+
+    /// Unsubscribe `value` from a topic filter, returning whether it was subscribed.
+    pub fn remove(&mut self, topic_filter: &str, value: &V) -> bool {
+        let value = match self.find_value(value) {
+            Some(value) => value,
+            None => return false,
+        };
+        let node = match self.find_node(topic_filter) {
+            Some(node) => node,
+            None => return false,
+        };
+        let sub = match self.find_sub(node, value) {
+            Some(sub) => sub,
+            None => return false,
+        };
+        self.remove_sub(node, sub);
+        self.prune(node);
+        true
+    }
+
+    /// Unsubscribe `value` from all the topics of an `Unsubscribe` packet.
+    pub fn unsubscribe(&mut self, unsubscribe: &Unsubscribe, value: &V) {
+        for topic in unsubscribe.topics.iter() {
+            self.remove(topic, value);
+        }
+    }
+
+    /// Remove all the subscriptions of `value`, for example when its session ends.
+    pub fn remove_all(&mut self, value: &V) {
+        let value = match self.find_value(value) {
+            Some(value) => value,
+            None => return,
+        };
+        for node in 0..self.nodes.len() {
+            if self.nodes[node].is_none() {
+                continue;
+            }
+            while let Some(sub) = self.find_sub(node, value) {
+                self.remove_sub(node, sub);
+            }
+            self.prune(node);
+        }
+    }
+
+    /// Get the values subscribed to a topic name, each with the maximum `QoS` of its matching
+    /// subscriptions.
+    ///
+    /// The topic name isn't validated, a name containing wildcards only matches filters with the
+    /// same wildcards at the same levels.
+    pub fn lookup(&self, topic_name: &str) -> TreeVec<(&V, QoS), N> {
+        let mut matches = Matches::new();
+        if !self.nodes.is_empty() {
+            self.walk(ROOT, topic_name.split('/'), topic_name.starts_with('$'), &mut matches);
+        }
+        let mut values = TreeVec::new();
+        for (value, qos) in matches.found {
+            let entry = self.values[value].as_ref().expect("linked value");
+            // There are at most N values.
+            let _ = tree_push::<_, N>(&mut values, (&entry.value, qos));
+        }
+        values
+    }
+
+    fn walk(
+        &self,
+        node: usize,
+        mut levels: Split<'_, char>,
+        dollar: bool,
+        matches: &mut Matches<N>,
+    ) {
+        // Topics starting with `$` aren't matched by a wildcard in the first level.
+        let wildcards = !(dollar && node == ROOT);
+        let level = levels.next();
+        if wildcards {
+            if let Some(child) = self.child(node, "#") {
+                self.collect(child, matches);
+            }
+            if let (Some(_), Some(child)) = (level, self.child(node, "+")) {
+                self.walk(child, levels.clone(), dollar, matches);
+            }
+        }
+        match level {
+            // Already matched by the wildcard children above.
+            Some("+") | Some("#") if wildcards => {}
+            Some(level) => {
+                if let Some(child) = self.child(node, level) {
+                    self.walk(child, levels, dollar, matches);
+                }
+            }
+            None => self.collect(node, matches),
+        }
+    }
+
+    fn collect(&self, node: usize, matches: &mut Matches<N>) {
+        let mut sub = self.node(node).first_sub;
+        while let Some(s) = sub {
+            let s = self.subs[s].as_ref().expect("linked sub");
+            matches.add(s.value, s.qos);
+            sub = s.next;
+        }
+    }
+
+    fn node(&self, node: usize) -> &Node {
+        self.nodes[node].as_ref().expect("linked node")
+    }
+
+    fn node_mut(&mut self, node: usize) -> &mut Node {
+        self.nodes[node].as_mut().expect("linked node")
+    }
+
+    fn sub_mut(&mut self, sub: usize) -> &mut Sub {
+        self.subs[sub].as_mut().expect("linked sub")
+    }
+
+    fn entry_mut(&mut self, value: usize) -> &mut Entry<V> {
+        self.values[value].as_mut().expect("linked value")
+    }
+
+    #[cfg(feature = "std")]
+    fn child(&self, node: usize, level: &str) -> Option<usize> {
+        self.node(node).children.get(level).copied()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn child(&self, node: usize, level: &str) -> Option<usize> {
+        let mut child = self.node(node).first_child;
+        while let Some(c) = child {
+            let n = self.node(c);
+            if n.level == level {
+                return Some(c);
+            }
+            child = n.next_sibling;
+        }
+        None
+    }
+
+    #[cfg(feature = "std")]
+    fn add_child(&mut self, node: usize, level: &str) -> Result<usize, Error> {
+        let child = alloc::<_, N>(&mut self.nodes, Node::new(level, node)?)?;
+        self.node_mut(node).children.insert(level.into(), child);
+        Ok(child)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn add_child(&mut self, node: usize, level: &str) -> Result<usize, Error> {
+        let mut child = Node::new(level, node)?;
+        child.next_sibling = self.node(node).first_child;
+        let child = alloc::<_, N>(&mut self.nodes, child)?;
+        self.node_mut(node).first_child = Some(child);
+        Ok(child)
+    }
+
+    fn find_node(&self, topic_filter: &str) -> Option<usize> {
+        let mut node = ROOT;
+        if self.nodes.is_empty() {
+            return None;
+        }
+        for level in topic_filter.split('/') {
+            node = self.child(node, level)?;
+        }
+        Some(node)
+    }
+
+    fn find_value(&self, value: &V) -> Option<usize> {
+        self.values
+            .iter()
+            .position(|entry| matches!(entry, Some(entry) if entry.value == *value))
+    }
+
+    fn find_sub(&self, node: usize, value: usize) -> Option<usize> {
+        let mut sub = self.node(node).first_sub;
+        while let Some(s) = sub {
+            let entry = self.subs[s].as_ref().expect("linked sub");
+            if entry.value == value {
+                return Some(s);
+            }
+            sub = entry.next;
+        }
+        None
+    }
+
+    fn remove_sub(&mut self, node: usize, sub: usize) {
+        let Sub { value, next, .. } = self.subs[sub].take().expect("linked sub");
+        self.len -= 1;
+        self.entry_mut(value).subs -= 1;
+        if self.entry_mut(value).subs == 0 {
+            self.values[value] = None;
+        }
+        if self.node(node).first_sub == Some(sub) {
+            self.node_mut(node).first_sub = next;
+            return;
+        }
+        let mut prev = self.node(node).first_sub;
+        while let Some(p) = prev {
+            let entry = self.sub_mut(p);
+            if entry.next == Some(sub) {
+                entry.next = next;
+                return;
+            }
+            prev = entry.next;
+        }
+    }
+
+    /// Free `node` and its ancestors while they have no subscription nor children.
+    fn prune(&mut self, mut node: usize) {
+        while node != ROOT {
+            let n = self.node(node);
+            if n.first_sub.is_some() || n.has_children() {
+                return;
+            }
+            let n = self.nodes[node].take().expect("linked node");
+            self.unlink(node, &n);
+            node = n.parent;
+        }
+    }
+
+    /// Remove the freed `node` from the children of its parent.
+    #[cfg(feature = "std")]
+    fn unlink(&mut self, _node: usize, n: &Node) {
+        self.node_mut(n.parent).children.remove(&n.level);
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn unlink(&mut self, node: usize, n: &Node) {
+        if self.node(n.parent).first_child == Some(node) {
+            self.node_mut(n.parent).first_child = n.next_sibling;
+            return;
+        }
+        let mut prev = self.node(n.parent).first_child;
+        while let Some(p) = prev {
+            let sibling = self.node_mut(p);
+            if sibling.next_sibling == Some(node) {
+                sibling.next_sibling = n.next_sibling;
+                return;
+            }
+            prev = sibling.next_sibling;
+        }
+    }
+}
+
+impl Node {
+    fn new(level: &str, parent: usize) -> Result<Self, Error> {
+        Ok(Node {
+            level: LimitedString::from_str(level).map_err(|_| Error::InvalidLength)?,
+            parent,
+            #[cfg(feature = "std")]
+            children: BTreeMap::new(),
+            #[cfg(not(feature = "std"))]
+            first_child: None,
+            #[cfg(not(feature = "std"))]
+            next_sibling: None,
+            first_sub: None,
+        })
+    }
+
+    #[cfg(feature = "std")]
+    fn has_children(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn has_children(&self) -> bool {
+        self.first_child.is_some()
+    }
+}
+
+impl<const N: usize> Matches<N> {
+    fn new() -> Self {
+        Matches {
+            found: TreeVec::new(),
+            #[cfg(feature = "std")]
+            index: HashMap::new(),
+        }
+    }
+
+    /// Add a matching subscription of `value`, keeping the maximum `QoS` of each value.
+    fn add(&mut self, value: usize, qos: QoS) {
+        #[cfg(feature = "std")]
+        let found = match self.index.get(&value) {
+            Some(&i) => Some(&mut self.found[i].1),
+            None => None,
+        };
+        #[cfg(not(feature = "std"))]
+        let found = self.found.iter_mut().find(|(v, _)| *v == value).map(|(_, q)| q);
+        match found {
+            Some(max) if max.to_u8() < qos.to_u8() => *max = qos,
+            Some(_) => {}
+            None => {
+                #[cfg(feature = "std")]
+                self.index.insert(value, self.found.len());
+                // Each value is pushed at most once, and there are at most N values.
+                let _ = tree_push::<_, N>(&mut self.found, (value, qos));
+            }
+        }
+    }
+}
+
+impl<V: PartialEq, const N: usize> Default for SubscriptionTree<V, N> {
+    fn default() -> Self {
+        SubscriptionTree::new()
+    }
+}
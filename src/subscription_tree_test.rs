@@ -0,0 +1,138 @@
+use crate::{
+    subscribe::{LimitedString, LimitedVec},
+    test_util::topic,
+    *,
+};
+use core::convert::TryFrom;
+use core::str::FromStr;
+
+fn lookup<const N: usize>(tree: &SubscriptionTree<u32, N>, topic_name: &str) -> Vec<(u32, QoS)> {
+    let mut matches: Vec<_> = tree.lookup(topic_name).iter().map(|(v, q)| (**v, *q)).collect();
+    matches.sort_by_key(|(v, _)| *v);
+    matches
+}
+
+#[test]
+fn test_lookup() {
+    let mut tree = SubscriptionTree::<u32, 16>::new();
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 1).unwrap();
+    tree.insert(&topic("a/+", QoS::AtLeastOnce), 2).unwrap();
+    tree.insert(&topic("a/#", QoS::ExactlyOnce), 3).unwrap();
+    tree.insert(&topic("+/+/c", QoS::AtMostOnce), 4).unwrap();
+    tree.insert(&topic("#", QoS::AtMostOnce), 5).unwrap();
+    tree.insert(&topic("$SYS/#", QoS::AtMostOnce), 6).unwrap();
+    assert_eq!(6, tree.len());
+
+    use QoS::*;
+    assert_eq!(vec![(3, ExactlyOnce), (5, AtMostOnce)], lookup(&tree, "a"));
+    assert_eq!(
+        vec![(1, AtMostOnce), (2, AtLeastOnce), (3, ExactlyOnce), (5, AtMostOnce)],
+        lookup(&tree, "a/b")
+    );
+    assert_eq!(
+        vec![(3, ExactlyOnce), (4, AtMostOnce), (5, AtMostOnce)],
+        lookup(&tree, "a/b/c")
+    );
+    assert_eq!(vec![(4, AtMostOnce), (5, AtMostOnce)], lookup(&tree, "/b/c"));
+    assert_eq!(vec![(5, AtMostOnce)], lookup(&tree, "b"));
+    // Leading wildcards don't match `$` topics.
+    assert_eq!(vec![(6, AtMostOnce)], lookup(&tree, "$SYS/uptime"));
+}
+
+#[test]
+fn test_wildcard_topic_name() {
+    let mut tree = SubscriptionTree::<u32, 16>::new();
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 1).unwrap();
+    tree.insert(&topic("a/+", QoS::AtMostOnce), 2).unwrap();
+    tree.insert(&topic("+/#", QoS::AtMostOnce), 3).unwrap();
+    tree.insert(&topic("$SYS/+", QoS::AtMostOnce), 4).unwrap();
+    use QoS::*;
+    assert_eq!(vec![(2, AtMostOnce), (3, AtMostOnce)], lookup(&tree, "a/+"));
+    assert_eq!(vec![(2, AtMostOnce), (3, AtMostOnce)], lookup(&tree, "a/#"));
+    assert_eq!(vec![(3, AtMostOnce)], lookup(&tree, "+/b"));
+    assert_eq!(vec![(4, AtMostOnce)], lookup(&tree, "$SYS/+"));
+}
+
+#[test]
+fn test_max_qos() {
+    let mut tree = SubscriptionTree::<u32, 16>::new();
+    tree.insert(&topic("a/+", QoS::AtLeastOnce), 1).unwrap();
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 1).unwrap();
+    tree.insert(&topic("#", QoS::AtMostOnce), 1).unwrap();
+    assert_eq!(vec![(1, QoS::AtLeastOnce)], lookup(&tree, "a/b"));
+    // Subscribing again to the same filter replaces the QoS.
+    tree.insert(&topic("a/b", QoS::ExactlyOnce), 1).unwrap();
+    assert_eq!(3, tree.len());
+    assert_eq!(vec![(1, QoS::ExactlyOnce)], lookup(&tree, "a/b"));
+    tree.insert(&topic("a/+", QoS::AtMostOnce), 1).unwrap();
+    assert_eq!(vec![(1, QoS::AtMostOnce)], lookup(&tree, "a/c"));
+}
+
+#[test]
+fn test_remove() {
+    let mut tree = SubscriptionTree::<u32, 16>::new();
+    assert!(!tree.remove("a/b", &1));
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 1).unwrap();
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 2).unwrap();
+    tree.insert(&topic("a/b/c", QoS::AtMostOnce), 1).unwrap();
+    tree.insert(&topic("x/#", QoS::AtMostOnce), 1).unwrap();
+    assert!(!tree.remove("a/b", &3));
+    assert!(!tree.remove("a", &1));
+    assert!(tree.remove("a/b", &1));
+    assert!(!tree.remove("a/b", &1));
+    assert_eq!(vec![(2, QoS::AtMostOnce)], lookup(&tree, "a/b"));
+    assert_eq!(vec![(1, QoS::AtMostOnce)], lookup(&tree, "a/b/c"));
+
+    tree.remove_all(&1);
+    assert_eq!(1, tree.len());
+    assert_eq!(vec![(2, QoS::AtMostOnce)], lookup(&tree, "a/b"));
+    assert_eq!(Vec::<(u32, QoS)>::new(), lookup(&tree, "x/y"));
+    assert!(tree.remove("a/b", &2));
+    assert!(tree.is_empty());
+    assert_eq!(Vec::<(u32, QoS)>::new(), lookup(&tree, "a/b"));
+}
+
+#[test]
+fn test_packets() {
+    let mut tree = SubscriptionTree::<u32, 16>::new();
+    let pid = Pid::try_from(1).unwrap();
+    let topics = [topic("a/+", QoS::AtLeastOnce), topic("a/#b", QoS::AtMostOnce)];
+    let subscribe = Subscribe::new(pid, IntoIterator::into_iter(topics).collect());
+    let return_codes: LimitedVec<_> = IntoIterator::into_iter([
+        SubscribeReturnCodes::Success(QoS::AtLeastOnce),
+        SubscribeReturnCodes::Failure,
+    ])
+    .collect();
+    assert_eq!(return_codes, tree.subscribe(&subscribe, 1));
+    assert_eq!(vec![(1, QoS::AtLeastOnce)], lookup(&tree, "a/b"));
+
+    let topics = IntoIterator::into_iter([LimitedString::from_str("a/+").unwrap()]).collect();
+    tree.unsubscribe(&Unsubscribe { pid, topics }, &1);
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn test_invalid() {
+    let mut tree = SubscriptionTree::<u32, 16>::new();
+    assert_eq!(
+        Err(Error::InvalidTopicFilter),
+        tree.insert(&topic("a/b#", QoS::AtMostOnce), 1)
+    );
+    assert!(tree.is_empty());
+}
+
+#[cfg(not(feature = "std"))]
+#[test]
+fn test_capacity() {
+    // The root and two levels.
+    let mut tree = SubscriptionTree::<u32, 3>::new();
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 1).unwrap();
+    tree.insert(&topic("a/b", QoS::AtMostOnce), 2).unwrap();
+    tree.insert(&topic("a", QoS::AtMostOnce), 3).unwrap();
+    assert_eq!(Err(Error::QueueFull), tree.insert(&topic("c", QoS::AtMostOnce), 4));
+    assert_eq!(Err(Error::QueueFull), tree.insert(&topic("a", QoS::AtMostOnce), 4));
+    assert_eq!(3, tree.len());
+    // Freed slots are reused.
+    assert!(tree.remove("a/b", &1));
+    tree.insert(&topic("a", QoS::AtMostOnce), 4).unwrap();
+}
@@ -0,0 +1,35 @@
+//! Fixtures shared by the test modules.
+
+// Not every fixture is used with every set of features.
+#![allow(dead_code)]
+
+use crate::{subscribe::LimitedString, *};
+use core::{convert::TryFrom, str::FromStr};
+
+pub(crate) fn pid(n: u16) -> Pid {
+    Pid::try_from(n).unwrap()
+}
+
+/// A `Publish` to `a/b`.
+pub(crate) fn publish_qos(qospid: QosPid, payload: &[u8]) -> Packet<'_> {
+    Publish {
+        dup: false,
+        qospid,
+        retain: false,
+        topic_name: "a/b",
+        payload,
+    }
+    .into()
+}
+
+/// A `QoS::AtLeastOnce` `Publish` to `a/b`.
+pub(crate) fn publish(payload: &[u8]) -> Packet<'_> {
+    publish_qos(QosPid::AtLeastOnce(pid(10)), payload)
+}
+
+pub(crate) fn topic(path: &str, qos: QoS) -> SubscribeTopic {
+    SubscribeTopic {
+        topic_path: LimitedString::from_str(path).unwrap(),
+        qos,
+    }
+}
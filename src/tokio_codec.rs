@@ -0,0 +1,98 @@
+use crate::{
+    decoder::read_frame_len, decode_slice_with_options, encode_bytes_mut, DecodeOptions, Error,
+    OwnedPacket, Packet,
+};
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A [tokio_util codec] for MQTT 3.1.1 packets.
+///
+/// Decoded packets are returned as [OwnedPacket], as they can't borrow from the read buffer. Both
+/// `Packet` and `OwnedPacket` can be encoded. Packets bigger than the `max_packet_size` of its
+/// [DecodeOptions] are rejected with `Error::PacketTooLarge`, before they are buffered when
+/// decoding.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), mqttrs::Error> {
+/// use futures::{SinkExt, StreamExt};
+/// use mqttrs::*;
+/// use tokio::net::TcpStream;
+/// use tokio_util::codec::Framed;
+///
+/// let stream = TcpStream::connect("127.0.0.1:1883").await?;
+/// let mut framed = Framed::new(stream, MqttCodec::new(64 * 1024));
+/// framed.send(Packet::Pingreq).await?;
+/// assert_eq!(Some(OwnedPacket::Pingresp), framed.next().await.transpose()?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [tokio_util codec]: https://docs.rs/tokio-util/0.7/tokio_util/codec/index.html
+/// [OwnedPacket]: enum.OwnedPacket.html
+/// [DecodeOptions]: struct.DecodeOptions.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MqttCodec {
+    /// Options used when decoding packets. Their `max_packet_size` also applies to encoding, and
+    /// defaults to no limit other than the biggest size MQTT can encode.
+    pub options: DecodeOptions,
+}
+
+impl MqttCodec {
+    /// Create a codec with the given maximum packet size and otherwise default [DecodeOptions].
+    ///
+    /// [DecodeOptions]: struct.DecodeOptions.html
+    pub fn new(max_packet_size: usize) -> Self {
+        MqttCodec::with_options(DecodeOptions {
+            max_packet_size: Some(max_packet_size),
+            ..DecodeOptions::default()
+        })
+    }
+
+    /// Create a codec with the given [DecodeOptions].
+    ///
+    /// [DecodeOptions]: struct.DecodeOptions.html
+    pub fn with_options(options: DecodeOptions) -> Self {
+        MqttCodec { options }
+    }
+}
+
+impl Decoder for MqttCodec {
+    type Item = OwnedPacket;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<OwnedPacket>, Error> {
+        let frame_len = match read_frame_len(src)? {
+            Some((header_len, len)) => header_len + len,
+            None => return Ok(None),
+        };
+        self.options.check_packet_size(frame_len)?;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+        let packet = match decode_slice_with_options(&src[..frame_len], &self.options)? {
+            Some((_, packet)) => OwnedPacket::from(packet),
+            None => return Ok(None),
+        };
+        src.advance(frame_len);
+        Ok(Some(packet))
+    }
+}
+
+impl Encoder<OwnedPacket> for MqttCodec {
+    type Error = Error;
+
+    fn encode(&mut self, packet: OwnedPacket, dst: &mut BytesMut) -> Result<(), Error> {
+        self.encode(packet.as_borrowed(), dst)
+    }
+}
+
+impl<'a> Encoder<Packet<'a>> for MqttCodec {
+    type Error = Error;
+
+    fn encode(&mut self, packet: Packet<'a>, dst: &mut BytesMut) -> Result<(), Error> {
+        self.options.check_packet_size(packet.encoded_len())?;
+        encode_bytes_mut(&packet, dst)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{test_util::publish, *};
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+#[test]
+fn test_decode_partial() {
+    let mut codec = MqttCodec::default();
+    let mut buf = BytesMut::new();
+    codec.encode(publish(b"hello"), &mut buf).unwrap();
+    codec.encode(Packet::Pingreq, &mut buf).unwrap();
+    assert_eq!(16, buf.len());
+
+    let mut src = BytesMut::new();
+    for byte in &buf[..13] {
+        src.extend_from_slice(&[*byte]);
+        assert_eq!(Ok(None), codec.decode(&mut src));
+    }
+    src.extend_from_slice(&buf[13..]);
+    assert_eq!(Ok(Some(OwnedPacket::from(publish(b"hello")))), codec.decode(&mut src));
+    assert_eq!(Ok(Some(OwnedPacket::Pingreq)), codec.decode(&mut src));
+    assert_eq!(Ok(None), codec.decode(&mut src));
+    assert!(src.is_empty());
+}
+
+#[test]
+fn test_max_packet_size() {
+    let mut codec = MqttCodec::new(16);
+    let mut buf = BytesMut::new();
+    codec.encode(publish(&[0; 7]), &mut buf).unwrap();
+    assert_eq!(16, buf.len());
+    assert_eq!(Err(Error::PacketTooLarge), codec.encode(publish(&[0; 8]), &mut buf));
+    assert_eq!(16, buf.len());
+
+    // Rejected as soon as the length is known.
+    let mut src = BytesMut::from(&[0b00110010, 15][..]);
+    assert_eq!(Err(Error::PacketTooLarge), codec.decode(&mut src));
+}
+
+#[test]
+fn test_encode_growing() {
+    let mut codec = MqttCodec::default();
+    let mut buf = BytesMut::new();
+    let payload = [42; 1000];
+    codec.encode(publish(&payload), &mut buf).unwrap();
+    assert_eq!(1010, buf.len());
+    assert_eq!(Ok(Some(publish(&payload))), decode_slice(&buf));
+}
+
+#[tokio::test]
+async fn test_framed() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut client = Framed::new(client, MqttCodec::default());
+    let mut server = Framed::new(server, MqttCodec::default());
+
+    let payload = [1; 300];
+    client.send(publish(&payload)).await.unwrap();
+    client.send(Packet::Disconnect).await.unwrap();
+    let expected = OwnedPacket::from(publish(&payload));
+    assert_eq!(Some(expected), server.next().await.transpose().unwrap());
+    assert_eq!(Some(OwnedPacket::Disconnect), server.next().await.transpose().unwrap());
+    server.send(OwnedPacket::Pingresp).await.unwrap();
+    assert_eq!(Some(OwnedPacket::Pingresp), client.next().await.transpose().unwrap());
+    drop(client);
+    assert_eq!(None, server.next().await.transpose().unwrap());
+}
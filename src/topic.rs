@@ -0,0 +1,113 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use crate::Error;
+use core::fmt;
+
+/// Validated topic filter, as used in [Subscribe] and [Unsubscribe] packets ([MQTT 4.7]).
+///
+/// A filter is split into levels by `/`. The single-level wildcard `+` matches exactly one level,
+/// and the multi-level wildcard `#` matches the parent level and any number of child levels.
+/// Wildcards must occupy a whole level, and `#` must be the last level.
+///
+/// Topic names starting with `$` (such as `$SYS/...`) are reserved for the server, and aren't
+/// matched by filters starting with a wildcard.
+///
+/// Matching doesn't allocate, so it can be used to route incoming [Publish] packets locally on
+/// `no_std` devices.
+///
+/// ```
+/// # use mqttrs::*;
+/// let filter = TopicFilter::new("sensors/+/temperature/#").unwrap();
+/// assert!(filter.matches("sensors/kitchen/temperature"));
+/// assert!(filter.matches("sensors/kitchen/temperature/celsius"));
+/// assert!(!filter.matches("sensors/kitchen/humidity"));
+///
+/// assert!(TopicFilter::new("#").unwrap().matches("sensors"));
+/// assert!(!TopicFilter::new("#").unwrap().matches("$SYS/uptime"));
+///
+/// assert_eq!(Err(Error::InvalidTopicFilter), TopicFilter::new("sensors/#/temperature"));
+/// assert_eq!(Err(Error::InvalidTopicFilter), TopicFilter::new("sensors+"));
+/// ```
+///
+/// [Subscribe]: type.Subscribe.html
+/// [Unsubscribe]: type.Unsubscribe.html
+/// [Publish]: struct.Publish.html
+/// [MQTT 4.7]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718106
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TopicFilter<'a>(&'a str);
+
+impl<'a> TopicFilter<'a> {
+    /// Validate a topic filter, returning `Error::InvalidTopicFilter` if it is empty, contains a
+    /// null character, or misuses a wildcard.
+    pub fn new(filter: &'a str) -> Result<Self, Error> {
+        if filter.is_empty() || filter.contains('\0') {
+            return Err(Error::InvalidTopicFilter);
+        }
+        let mut levels = filter.split('/').peekable();
+        while let Some(level) = levels.next() {
+            let valid = match level {
+                "#" => levels.peek().is_none(),
+                "+" => true,
+                _ => !level.contains(['+', '#']),
+            };
+            if !valid {
+                return Err(Error::InvalidTopicFilter);
+            }
+        }
+        Ok(TopicFilter(filter))
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    /// Whether the filter contains a `+` or `#` wildcard.
+    pub fn has_wildcards(&self) -> bool {
+        self.0.contains(['+', '#'])
+    }
+
+    /// Whether a topic name, such as [`Publish::topic_name`], matches this filter.
+    ///
+    /// [`Publish::topic_name`]: struct.Publish.html#structfield.topic_name
+    pub fn matches(&self, topic_name: &str) -> bool {
+        if topic_name.starts_with('$') && self.0.starts_with(['+', '#']) {
+            return false;
+        }
+        let mut filter = self.0.split('/');
+        let mut topic = topic_name.split('/');
+        loop {
+            match (filter.next(), topic.next()) {
+                // `#` also matches the parent level, so "a/#" matches "a".
+                (Some("#"), _) => return true,
+                (Some("+"), Some(_)) => {}
+                (Some(f), Some(t)) if f == t => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Check that a topic name, as found in [Publish] packets, is valid.
+///
+/// It must not be empty ([MQTT-4.7.3-1]), nor contain wildcards ([MQTT-3.3.2-2]) or null
+/// characters ([MQTT-1.5.3-2]).
+///
+/// [Publish]: struct.Publish.html
+/// [MQTT-4.7.3-1]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718109
+/// [MQTT-3.3.2-2]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718039
+/// [MQTT-1.5.3-2]: http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718016
+pub(crate) fn check_topic_name(topic_name: &str) -> Result<(), Error> {
+    if topic_name.is_empty() || topic_name.contains(['\0', '+', '#']) {
+        Err(Error::InvalidTopicName)
+    } else {
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for TopicFilter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
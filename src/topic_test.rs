@@ -0,0 +1,68 @@
+use crate::{subscribe::LimitedString, *};
+use core::str::FromStr;
+
+fn filter(s: &str) -> TopicFilter<'_> {
+    TopicFilter::new(s).unwrap()
+}
+
+#[test]
+fn test_valid() {
+    for s in &["a", "/", "a/b", "+", "#", "+/+", "a/+/b", "a/#", "/#", "+/#", "$SYS/#", "a//b"] {
+        assert_eq!(Ok(filter(s)), TopicFilter::new(s), "{}", s);
+    }
+    for s in &["", "#/", "a/#/b", "a#", "a/b#", "a+", "+a/b", "a/+b", "##", "a/\0"] {
+        assert_eq!(Err(Error::InvalidTopicFilter), TopicFilter::new(s), "{:?}", s);
+    }
+    assert!(filter("a/+").has_wildcards());
+    assert!(!filter("a/b").has_wildcards());
+    assert_eq!("a/b", filter("a/b").as_str());
+}
+
+#[test]
+fn test_matches() {
+    let cases = [
+        ("a/b", "a/b", true),
+        ("a/b", "a/c", false),
+        ("a/b", "a/b/c", false),
+        ("a/b", "a", false),
+        ("a/+", "a/b", true),
+        ("a/+", "a/", true),
+        ("a/+", "a", false),
+        ("a/+", "a/b/c", false),
+        ("+/+", "/a", true),
+        ("+", "/a", false),
+        ("a/#", "a", true),
+        ("a/#", "a/b/c", true),
+        ("a/#", "b/c", false),
+        ("#", "a/b/c", true),
+        ("#", "/", true),
+        ("+/b/#", "a/b", true),
+        ("+/b/#", "a/c", false),
+        ("/#", "/a", true),
+        ("/#", "a", false),
+    ];
+    for (f, t, m) in cases.iter() {
+        assert_eq!(*m, filter(f).matches(t), "{} {}", f, t);
+    }
+}
+
+#[test]
+fn test_dollar() {
+    assert!(!filter("#").matches("$SYS/uptime"));
+    assert!(!filter("+/uptime").matches("$SYS/uptime"));
+    assert!(filter("$SYS/#").matches("$SYS/uptime"));
+    assert!(filter("$SYS/+").matches("$SYS/uptime"));
+    // Only the first level is special.
+    assert!(filter("a/+").matches("a/$b"));
+}
+
+#[test]
+fn test_subscribe_topic() {
+    let mut topic = SubscribeTopic {
+        topic_path: LimitedString::from_str("a/+").unwrap(),
+        qos: QoS::AtMostOnce,
+    };
+    assert!(topic.topic_filter().unwrap().matches("a/b"));
+    topic.topic_path = LimitedString::from_str("a/+b").unwrap();
+    assert_eq!(Err(Error::InvalidTopicFilter), topic.topic_filter());
+}
@@ -2,7 +2,7 @@
 use defmt::{Format};
 
 
-use crate::encoder::write_u16;
+use crate::{decoder::read_u16, encoder::write_u16, ConnectReturnCode, PacketType};
 use core::{convert::TryFrom, fmt, num::NonZeroU16};
 
 #[cfg(feature = "derive")]
@@ -19,8 +19,6 @@ use std::{
 ///
 /// [`encode()`]: fn.encode.html
 /// [`decode()`]: fn.decode.html
-
-
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
@@ -34,11 +32,43 @@ pub enum Error {
     InvalidQos(u8),
     /// Tried to decode a ConnectReturnCode > 5.
     InvalidConnectReturnCode(u8),
-    /// Tried to decode an unknown protocol.
+    /// Tried to decode MQTT 5 subscription options with reserved bits set.
+    InvalidSubscriptionOptions(u8),
+    /// Tried to encode or decode an unknown MQTT 5 reason code, or one that isn't allowed in this
+    /// packet.
+    InvalidReasonCode(u8),
+    /// Tried to encode or decode an unknown MQTT 5 property identifier, or an invalid value.
+    InvalidProperty(u8),
+    /// Tried to encode or decode an MQTT 5 property that isn't allowed in this packet.
+    DisallowedProperty(u8),
+    /// Tried to encode or decode an MQTT 5 property more than once in the same packet.
+    DuplicateProperty(u8),
+    /// Received a packet that isn't valid at this stage of the protocol.
+    UnexpectedPacket(PacketType),
+    /// The server answered with a different enhanced authentication method than the client's.
+    InvalidAuthMethod,
+    /// The server refused the connection.
+    ConnectionRefused(ConnectReturnCode),
+    /// A fixed-capacity queue or table is full.
+    QueueFull,
+    /// Tried to encode or decode an empty topic name, or one with a null character or a wildcard.
+    InvalidTopicName,
+    /// Tried to use an empty topic filter, or one with a null character or a misplaced wildcard.
+    InvalidTopicFilter,
+    /// Tried to decode an unknown protocol, with its name and level.
+    ///
+    /// Without `std`, the name is truncated to the first 10 bytes that end on a `char` boundary,
+    /// so longer names are not reported in full.
     #[cfg(feature = "std")]
     InvalidProtocol(std::string::String, u8),
     #[cfg(not(feature = "std"))]
     InvalidProtocol(heapless::String<10>, u8),
+    /// Tried to decode or validate a `Connect` with a reserved flag set, will flags without a
+    /// will, or a password without a username.
+    InvalidConnectFlags(u8),
+    /// Tried to decode or validate a `Connect` with an empty client id but without a clean
+    /// session.
+    InvalidClientId,
     /// Tried to decode an invalid fixed header (packet type, flags, or remaining_length).
     InvalidHeader,
     /// Trying to encode/decode an invalid length.
@@ -46,8 +76,28 @@ pub enum Error {
     /// The difference with `WriteZero`/`UnexpectedEof` is that it refers to an invalid/corrupt
     /// length rather than a buffer size issue.
     InvalidLength,
+    /// Tried to decode a packet whose content doesn't match its remaining length, or a fixed-size
+    /// packet with the wrong remaining length.
+    InvalidRemainingLength(PacketType, usize),
+    /// Tried to encode or decode a packet bigger than the configured maximum size.
+    PacketTooLarge,
+    /// Tried to decode a `Subscribe` or `Unsubscribe` with more topics than the configured
+    /// maximum.
+    TooManyTopics,
+    /// Tried to decode a topic name or filter longer than the configured maximum.
+    TopicTooLong,
+    /// Tried to decode a client id longer than the configured maximum.
+    ClientIdTooLong,
     /// Trying to decode a non-utf8 string.
     InvalidString(#[cfg_attr(feature = "defmt",defmt(Debug2Format))] core::str::Utf8Error),
+    /// The transport was closed in the middle of a packet. `std::io` errors of kind
+    /// `UnexpectedEof` convert to it, for all transports.
+    UnexpectedEof,
+    /// Error of an [embedded_io_async] transport.
+    ///
+    /// [embedded_io_async]: https://docs.rs/embedded-io-async/0.6/embedded_io_async/
+    #[cfg(feature = "embedded-io-async")]
+    Transport(embedded_io_async::ErrorKind),
     /// Catch-all error when converting from `std::io::Error`.
     ///
     /// Note: Only available when std is available.
@@ -70,6 +120,7 @@ impl From<Error> for IoError {
     fn from(err: Error) -> IoError {
         match err {
             Error::WriteZero => IoError::new(ErrorKind::WriteZero, err),
+            Error::UnexpectedEof => IoError::new(ErrorKind::UnexpectedEof, err),
             _ => IoError::new(ErrorKind::InvalidData, err),
         }
     }
@@ -80,6 +131,7 @@ impl From<IoError> for Error {
     fn from(err: IoError) -> Error {
         match err.kind() {
             ErrorKind::WriteZero => Error::WriteZero,
+            ErrorKind::UnexpectedEof => Error::UnexpectedEof,
             k => Error::IoError(k, format!("{}", err)),
         }
     }
@@ -113,7 +165,6 @@ impl From<IoError> for Error {
 /// [`QoS::AtLeastOne` or `QoS::ExactlyOnce`]: enum.QoS.html
 /// [MQTT-2.3.1-1]: https://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html#_Toc398718025
 /// [MQTT-2.2.1-3]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901026
-
 #[cfg_attr(feature = "defmt",derive(Format))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "derive", derive(Serialize, Deserialize))]
@@ -129,10 +180,8 @@ impl Pid {
         self.0.get()
     }
 
-    pub(crate) fn from_buffer<'a>(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
-        let pid = ((buf[*offset] as u16) << 8) | buf[*offset + 1] as u16;
-        *offset += 2;
-        Self::try_from(pid)
+    pub(crate) fn from_buffer(buf: &[u8], offset: &mut usize) -> Result<Self, Error> {
+        Self::try_from(read_u16(buf, offset)?)
     }
 
     pub(crate) fn to_buffer(self, buf: &mut [u8], offset: &mut usize) -> Result<(), Error> {
@@ -165,7 +214,7 @@ impl core::ops::Sub<u16> for Pid {
     /// Adding a `u16` to a `Pid` will wrap around and avoid 0.
     fn sub(self, u: u16) -> Pid {
         let n = match self.get().overflowing_sub(u) {
-            (0, _) => core::u16::MAX,
+            (0, _) => u16::MAX,
             (n, false) => n,
             (n, true) => n - 1,
         };
@@ -208,8 +257,8 @@ pub enum QoS {
 }
 
 impl QoS {
-    pub(crate) fn to_u8(&self) -> u8 {
-        match *self {
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
             QoS::AtMostOnce => 0,
             QoS::AtLeastOnce => 1,
             QoS::ExactlyOnce => 2,
@@ -283,6 +332,7 @@ mod test {
     use std::vec;
 
     #[test]
+    #[allow(clippy::legacy_numeric_constants)]
     fn pid_add_sub() {
         let t: Vec<(u16, u16, u16, u16)> = vec![
             (2, 1, 1, 3),
@@ -0,0 +1,211 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use super::{property::Location, Properties, ReasonCode};
+use crate::{encoder::*, Error, PacketType, Pid};
+
+/// Puback, Pubrec, Pubrel and Pubcomp packets ([MQTT 3.4] to [MQTT 3.7]).
+///
+/// Those four packets share the same layout: the [`Pid`] of the publish flow they belong to, a
+/// [ReasonCode], and properties.
+///
+/// When encoding, the properties are omitted if empty, and the reason code is omitted as well if
+/// it is `ReasonCode::Success`. The same goes for [Disconnect] and [Auth].
+///
+/// [ReasonCode]: enum.ReasonCode.html
+/// [Disconnect]: struct.Disconnect.html
+/// [Auth]: struct.Auth.html
+/// [MQTT 3.4]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901121
+/// [MQTT 3.7]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901151
+/// [`Pid`]: ../struct.Pid.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ack<'a> {
+    pub pid: Pid,
+    pub reason_code: ReasonCode,
+    pub properties: Properties<'a>,
+}
+
+/// Disconnect packet ([MQTT 3.14]).
+///
+/// [MQTT 3.14]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901205
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disconnect<'a> {
+    pub reason_code: ReasonCode,
+    pub properties: Properties<'a>,
+}
+
+/// Auth packet ([MQTT 3.15]).
+///
+/// Used for [enhanced authentication], which lets the client and server exchange challenges
+/// instead of sending a password in the `Connect` packet. The method and data are carried by the
+/// properties, see [`Properties::authentication_method()`] and
+/// [`Properties::authentication_data()`]. [EnhancedAuth] drives the exchange on the client side.
+///
+/// [enhanced authentication]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901256
+/// [`Properties::authentication_method()`]: struct.Properties.html#method.authentication_method
+/// [`Properties::authentication_data()`]: struct.Properties.html#method.authentication_data
+/// [EnhancedAuth]: struct.EnhancedAuth.html
+/// [MQTT 3.15]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901217
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Auth<'a> {
+    pub reason_code: ReasonCode,
+    pub properties: Properties<'a>,
+}
+
+impl<'a> Ack<'a> {
+    pub fn new(pid: Pid) -> Self {
+        Ack {
+            pid,
+            reason_code: ReasonCode::Success,
+            properties: Properties::new(),
+        }
+    }
+
+    /// The reason code and properties are omitted when the remaining length is 2 or 3.
+    pub(crate) fn from_buffer(
+        typ: PacketType,
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let pid = Pid::from_buffer(buf, offset)?;
+        let (reason_code, properties) =
+            read_reason(typ, remaining_len.saturating_sub(2), buf, offset)?;
+        Ok(Ack {
+            pid,
+            reason_code,
+            properties,
+        })
+    }
+
+    pub(crate) fn to_buffer(
+        &self,
+        typ: PacketType,
+        buf: &mut [u8],
+        offset: &mut usize,
+    ) -> Result<usize, Error> {
+        let header = match typ {
+            PacketType::Puback => 0b01000000,
+            PacketType::Pubrec => 0b01010000,
+            PacketType::Pubrel => 0b01100010,
+            _ => 0b01110000,
+        };
+        write_reason(typ, header, Some(self.pid), self.reason_code, &self.properties, buf, offset)
+    }
+}
+
+impl<'a> Disconnect<'a> {
+    pub fn new(reason_code: ReasonCode) -> Self {
+        Disconnect {
+            reason_code,
+            properties: Properties::new(),
+        }
+    }
+
+    pub(crate) fn from_buffer(
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let (reason_code, properties) =
+            read_reason(PacketType::Disconnect, remaining_len, buf, offset)?;
+        Ok(Disconnect {
+            reason_code,
+            properties,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        write_reason(
+            PacketType::Disconnect,
+            0b11100000,
+            None,
+            self.reason_code,
+            &self.properties,
+            buf,
+            offset,
+        )
+    }
+}
+
+impl<'a> Auth<'a> {
+    pub(crate) fn from_buffer(
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let (reason_code, properties) = read_reason(PacketType::Auth, remaining_len, buf, offset)?;
+        Ok(Auth {
+            reason_code,
+            properties,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        write_reason(
+            PacketType::Auth,
+            0b11110000,
+            None,
+            self.reason_code,
+            &self.properties,
+            buf,
+            offset,
+        )
+    }
+}
+
+/// Read an optional reason code followed by optional properties, `len` being the number of bytes
+/// left in the packet. Missing values default to success (0x00) and no properties.
+fn read_reason<'a>(
+    typ: PacketType,
+    len: usize,
+    buf: &'a [u8],
+    offset: &mut usize,
+) -> Result<(ReasonCode, Properties<'a>), Error> {
+    let reason_code = if len > 0 {
+        ReasonCode::from_buffer(typ, buf, offset)?
+    } else {
+        ReasonCode::Success
+    };
+    let properties = if len > 1 {
+        Properties::from_buffer(buf, offset, Location::Packet(typ))?
+    } else {
+        Properties::new()
+    };
+    Ok((reason_code, properties))
+}
+
+/// Write the packet using the shortest form: the properties are omitted when empty, and the
+/// reason code is omitted as well when it is `Success`.
+fn write_reason(
+    typ: PacketType,
+    header: u8,
+    pid: Option<Pid>,
+    reason_code: ReasonCode,
+    properties: &Properties,
+    buf: &mut [u8],
+    offset: &mut usize,
+) -> Result<usize, Error> {
+    let mut length = if pid.is_some() { 2 } else { 0 };
+    if !properties.is_empty() {
+        length += 1 + properties.len();
+    } else if reason_code != ReasonCode::Success {
+        length += 1;
+    }
+    check_remaining(buf, offset, 1)?;
+    write_u8(buf, offset, header)?;
+
+    let write_len = write_length(buf, offset, length)? + 1;
+    if let Some(pid) = pid {
+        pid.to_buffer(buf, offset)?;
+    }
+    if !properties.is_empty() || reason_code != ReasonCode::Success {
+        reason_code.to_buffer(typ, buf, offset)?;
+    }
+    if !properties.is_empty() {
+        properties.to_buffer(Location::Packet(typ), buf, offset)?;
+    }
+    Ok(write_len)
+}
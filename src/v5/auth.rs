@@ -0,0 +1,254 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use super::{Packet, Properties, Property, ReasonCode};
+use crate::{Error, PacketType};
+
+/// Authentication method and data properties, to be sent in a `Connect` or `Auth` packet.
+///
+/// Returned by [EnhancedAuth] methods. The properties borrow from this struct, use
+/// [`properties()`] to put them in a packet.
+///
+/// [EnhancedAuth]: struct.EnhancedAuth.html
+/// [`properties()`]: #method.properties
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthProperties<'a> {
+    props: [Property<'a>; 2],
+    len: usize,
+}
+
+impl<'a> AuthProperties<'a> {
+    pub fn new(method: &'a str, data: Option<&'a [u8]>) -> Self {
+        match data {
+            Some(data) => AuthProperties {
+                props: [
+                    Property::AuthenticationMethod(method),
+                    Property::AuthenticationData(data),
+                ],
+                len: 2,
+            },
+            None => AuthProperties {
+                props: [Property::AuthenticationMethod(method); 2],
+                len: 1,
+            },
+        }
+    }
+
+    pub fn properties(&self) -> Properties<'_> {
+        Properties::from(&self.props[..self.len])
+    }
+}
+
+/// State of an [EnhancedAuth] exchange.
+///
+/// [EnhancedAuth]: struct.EnhancedAuth.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthState {
+    /// The `Connect` packet hasn't been sent yet.
+    Initial,
+    /// Waiting for the server's `Auth` or `Connack` packet.
+    Waiting,
+    /// The server sent a challenge, waiting for the client's response.
+    Challenged,
+    /// The server accepted the client.
+    Authenticated,
+    /// The server rejected the client, with this reason code.
+    Failed(ReasonCode),
+}
+
+/// Outcome of a packet received from the server, returned by [`EnhancedAuth::handle()`].
+///
+/// [`EnhancedAuth::handle()`]: struct.EnhancedAuth.html#method.handle
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthEvent<'a> {
+    /// The server sent a challenge. Answer it with [`EnhancedAuth::respond()`].
+    ///
+    /// [`EnhancedAuth::respond()`]: struct.EnhancedAuth.html#method.respond
+    Challenge(Option<&'a [u8]>),
+    /// The server accepted the client, optionally with some final data (for example a SCRAM
+    /// server signature, that the client should verify).
+    Success(Option<&'a [u8]>),
+    /// The server rejected the client, with this reason code.
+    Failure(ReasonCode),
+}
+
+/// Client side of an [enhanced authentication] exchange, such as SCRAM.
+///
+/// This is a sans-IO state machine: it doesn't compute any authentication data itself, but checks
+/// that the `Connect`, `Auth` and `Connack` packets are exchanged in a valid order and with a
+/// consistent authentication method, and extracts the authentication data for the caller.
+///
+/// ```
+/// # use mqttrs::v5::*;
+/// let mut auth = EnhancedAuth::new("SCRAM-SHA-256");
+///
+/// // Send the client-first message in the Connect packet.
+/// let props = auth.start(Some(b"n,,n=user,r=nonce")).unwrap();
+/// let connect = Connect { keep_alive: 30,
+///                         client_id: "client",
+///                         clean_start: true,
+///                         last_will: None,
+///                         username: None,
+///                         password: None,
+///                         properties: props.properties() };
+///
+/// // The server answers with a challenge.
+/// let list = [Property::AuthenticationMethod("SCRAM-SHA-256"),
+///             Property::AuthenticationData(b"r=nonce+server,s=salt,i=4096")];
+/// let challenge = Packet::Auth(Auth { reason_code: ReasonCode::ContinueAuthentication,
+///                                   properties: Properties::from(&list[..]) });
+/// assert_eq!(Ok(AuthEvent::Challenge(Some(&b"r=nonce+server,s=salt,i=4096"[..]))),
+///            auth.handle(&challenge));
+///
+/// // Send the client-final message.
+/// let props = auth.respond(Some(b"c=biws,r=nonce+server,p=proof")).unwrap();
+/// let response = Packet::Auth(Auth { reason_code: ReasonCode::ContinueAuthentication,
+///                                  properties: props.properties() });
+///
+/// // The server accepts the client.
+/// let list = [Property::AuthenticationMethod("SCRAM-SHA-256"),
+///             Property::AuthenticationData(b"v=signature")];
+/// let connack = Packet::Connack(Connack { session_present: false,
+///                                         reason_code: ReasonCode::Success,
+///                                         properties: Properties::from(&list[..]) });
+/// assert_eq!(Ok(AuthEvent::Success(Some(&b"v=signature"[..]))), auth.handle(&connack));
+/// assert_eq!(AuthState::Authenticated, auth.state());
+/// ```
+///
+/// Once authenticated, the client can [`reauthenticate()`] at any time.
+///
+/// Unexpected packets return `Error::UnexpectedPacket`, and an authentication method that differs
+/// from the one the exchange started with returns `Error::InvalidAuthMethod`.
+///
+/// [enhanced authentication]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901256
+/// [`reauthenticate()`]: #method.reauthenticate
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnhancedAuth<'m> {
+    method: &'m str,
+    state: AuthState,
+    reauthenticating: bool,
+}
+
+impl<'m> EnhancedAuth<'m> {
+    pub fn new(method: &'m str) -> Self {
+        EnhancedAuth {
+            method,
+            state: AuthState::Initial,
+            reauthenticating: false,
+        }
+    }
+
+    pub fn method(&self) -> &'m str {
+        self.method
+    }
+
+    pub fn state(&self) -> AuthState {
+        self.state
+    }
+
+    /// Start the exchange, returning the properties to send in the `Connect` packet.
+    pub fn start<'a>(&mut self, data: Option<&'a [u8]>) -> Result<AuthProperties<'a>, Error>
+    where
+        'm: 'a,
+    {
+        self.expect(AuthState::Initial, PacketType::Connect)?;
+        self.state = AuthState::Waiting;
+        Ok(AuthProperties::new(self.method, data))
+    }
+
+    /// Answer a challenge, returning the properties to send in an `Auth` packet with
+    /// `ReasonCode::ContinueAuthentication`.
+    pub fn respond<'a>(&mut self, data: Option<&'a [u8]>) -> Result<AuthProperties<'a>, Error>
+    where
+        'm: 'a,
+    {
+        self.expect(AuthState::Challenged, PacketType::Auth)?;
+        self.state = AuthState::Waiting;
+        Ok(AuthProperties::new(self.method, data))
+    }
+
+    /// Start a re-authentication, returning the properties to send in an `Auth` packet with
+    /// `ReasonCode::ReAuthenticate`.
+    pub fn reauthenticate<'a>(
+        &mut self,
+        data: Option<&'a [u8]>,
+    ) -> Result<AuthProperties<'a>, Error>
+    where
+        'm: 'a,
+    {
+        self.expect(AuthState::Authenticated, PacketType::Auth)?;
+        self.state = AuthState::Waiting;
+        self.reauthenticating = true;
+        Ok(AuthProperties::new(self.method, data))
+    }
+
+    /// Process a packet received from the server.
+    ///
+    /// Only `Auth`, `Connack` and `Disconnect` packets are relevant to the exchange, the caller
+    /// should handle other packets itself once authenticated. A `Disconnect` fails the exchange
+    /// even before the client responded to a challenge.
+    pub fn handle<'a>(&mut self, packet: &Packet<'a>) -> Result<AuthEvent<'a>, Error> {
+        if let Packet::Disconnect(disconnect) = packet {
+            if self.state == AuthState::Challenged {
+                self.state = AuthState::Failed(disconnect.reason_code);
+                return Ok(AuthEvent::Failure(disconnect.reason_code));
+            }
+        }
+        self.expect(AuthState::Waiting, packet.get_type())?;
+        let (event, properties) = match packet {
+            Packet::Auth(auth) => match auth.reason_code {
+                ReasonCode::ContinueAuthentication => {
+                    (AuthEvent::Challenge(None), &auth.properties)
+                }
+                ReasonCode::Success if self.reauthenticating => {
+                    (AuthEvent::Success(None), &auth.properties)
+                }
+                _ => return Err(Error::UnexpectedPacket(PacketType::Auth)),
+            },
+            Packet::Connack(connack) if !self.reauthenticating => match connack.reason_code {
+                ReasonCode::Success => (AuthEvent::Success(None), &connack.properties),
+                code => (AuthEvent::Failure(code), &connack.properties),
+            },
+            Packet::Disconnect(disconnect) => {
+                (AuthEvent::Failure(disconnect.reason_code), &disconnect.properties)
+            }
+            other => return Err(Error::UnexpectedPacket(other.get_type())),
+        };
+        let event = match event {
+            AuthEvent::Failure(code) => {
+                self.state = AuthState::Failed(code);
+                return Ok(event);
+            }
+            AuthEvent::Challenge(_) => {
+                self.check_method(properties)?;
+                self.state = AuthState::Challenged;
+                AuthEvent::Challenge(properties.authentication_data())
+            }
+            AuthEvent::Success(_) => {
+                self.check_method(properties)?;
+                self.state = AuthState::Authenticated;
+                self.reauthenticating = false;
+                AuthEvent::Success(properties.authentication_data())
+            }
+        };
+        Ok(event)
+    }
+
+    fn expect(&self, state: AuthState, typ: PacketType) -> Result<(), Error> {
+        if self.state == state {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedPacket(typ))
+        }
+    }
+
+    fn check_method(&self, properties: &Properties) -> Result<(), Error> {
+        match properties.authentication_method() {
+            Some(method) if method == self.method => Ok(()),
+            _ => Err(Error::InvalidAuthMethod),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::v5::*;
+use crate::{Error, PacketType};
+
+const METHOD: &str = "SCRAM-SHA-1";
+
+/// Encode and decode the packet, as if it went through the network.
+fn roundtrip<'a>(pkt: Packet, buf: &'a mut [u8]) -> Packet<'a> {
+    let len = encode_slice(&pkt, buf).unwrap();
+    decode_slice(&buf[..len]).unwrap().unwrap()
+}
+
+fn auth_packet<'a>(reason_code: ReasonCode, props: &'a [Property<'a>]) -> Packet<'a> {
+    Packet::Auth(Auth {
+        reason_code,
+        properties: Properties::from(props),
+    })
+}
+
+#[test]
+fn test_exchange() {
+    let mut auth = EnhancedAuth::new(METHOD);
+    assert_eq!(AuthState::Initial, auth.state());
+
+    let props = auth.start(Some(b"client-first")).unwrap();
+    let mut buf = [0u8; 128];
+    let connect = Connect {
+        keep_alive: 30,
+        client_id: "c",
+        clean_start: true,
+        last_will: None,
+        username: None,
+        password: None,
+        properties: props.properties(),
+    };
+    match roundtrip(connect.into(), &mut buf) {
+        Packet::Connect(c) => {
+            assert_eq!(c.password, None);
+            assert_eq!(c.properties.authentication_method(), Some(METHOD));
+            assert_eq!(c.properties.authentication_data(), Some(&b"client-first"[..]));
+        }
+        other => panic!("Unexpected {:?}", other),
+    }
+    assert_eq!(AuthState::Waiting, auth.state());
+
+    let props = [
+        Property::AuthenticationMethod(METHOD),
+        Property::AuthenticationData(b"server-first"),
+    ];
+    let challenge = auth_packet(ReasonCode::ContinueAuthentication, &props);
+    let challenge = roundtrip(challenge, &mut buf);
+    assert_eq!(
+        Ok(AuthEvent::Challenge(Some(&b"server-first"[..]))),
+        auth.handle(&challenge)
+    );
+    assert_eq!(AuthState::Challenged, auth.state());
+
+    let props = auth.respond(Some(b"client-final")).unwrap();
+    assert_eq!(
+        props.properties(),
+        Properties::from(
+            &[
+                Property::AuthenticationMethod(METHOD),
+                Property::AuthenticationData(b"client-final"),
+            ][..]
+        )
+    );
+
+    let props = [Property::AuthenticationMethod(METHOD)];
+    let connack = Packet::Connack(Connack {
+        session_present: false,
+        reason_code: ReasonCode::Success,
+        properties: Properties::from(&props[..]),
+    });
+    assert_eq!(Ok(AuthEvent::Success(None)), auth.handle(&connack));
+    assert_eq!(AuthState::Authenticated, auth.state());
+
+    // Re-authentication ends with an Auth packet instead of a Connack.
+    let props = auth.reauthenticate(None).unwrap();
+    assert_eq!(props.properties().authentication_data(), None);
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Connack)),
+        auth.handle(&connack)
+    );
+    let props = [Property::AuthenticationMethod(METHOD)];
+    assert_eq!(
+        Ok(AuthEvent::Success(None)),
+        auth.handle(&auth_packet(ReasonCode::Success, &props))
+    );
+    assert_eq!(AuthState::Authenticated, auth.state());
+}
+
+#[test]
+fn test_failure() {
+    let mut auth = EnhancedAuth::new(METHOD);
+    auth.start(None).unwrap();
+    let connack = Packet::Connack(Connack {
+        session_present: false,
+        reason_code: ReasonCode::BadAuthenticationMethod,
+        properties: Properties::new(),
+    });
+    let code = ReasonCode::BadAuthenticationMethod;
+    assert_eq!(Ok(AuthEvent::Failure(code)), auth.handle(&connack));
+    assert_eq!(AuthState::Failed(code), auth.state());
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Connect)),
+        auth.start(None).map(|_| ())
+    );
+
+    // The server can give up while the client works on its response to a challenge.
+    let mut auth = EnhancedAuth::new(METHOD);
+    auth.start(None).unwrap();
+    let props = [Property::AuthenticationMethod(METHOD)];
+    auth.handle(&auth_packet(ReasonCode::ContinueAuthentication, &props)).unwrap();
+    assert_eq!(AuthState::Challenged, auth.state());
+    let code = ReasonCode::NotAuthorized;
+    let disconnect = Packet::Disconnect(Disconnect {
+        reason_code: code,
+        properties: Properties::new(),
+    });
+    assert_eq!(Ok(AuthEvent::Failure(code)), auth.handle(&disconnect));
+    assert_eq!(AuthState::Failed(code), auth.state());
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Auth)),
+        auth.respond(None).map(|_| ())
+    );
+}
+
+#[test]
+fn test_unexpected() {
+    let mut auth = EnhancedAuth::new(METHOD);
+    let props = [Property::AuthenticationMethod(METHOD)];
+    // Nothing is expected before the Connect is sent.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Auth)),
+        auth.handle(&auth_packet(ReasonCode::ContinueAuthentication, &props))
+    );
+    // Can't respond without a challenge.
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Auth)),
+        auth.respond(None).map(|_| ())
+    );
+    auth.start(None).unwrap();
+    assert_eq!(
+        Err(Error::UnexpectedPacket(PacketType::Pingresp)),
+        auth.handle(&Packet::Pingresp)
+    );
+    // The server can't switch methods.
+    let props = [Property::AuthenticationMethod("PLAIN")];
+    assert_eq!(
+        Err(Error::InvalidAuthMethod),
+        auth.handle(&auth_packet(ReasonCode::ContinueAuthentication, &props))
+    );
+}
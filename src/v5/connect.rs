@@ -0,0 +1,193 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use super::{property::Location, Properties, ReasonCode};
+use crate::{decoder::*, encoder::*, Error, PacketType, Protocol, QoS};
+
+/// Message that the server should publish when the client disconnects.
+///
+/// Sent by the client in the [Connect] packet. [MQTT 3.1.3.2].
+///
+/// [Connect]: struct.Connect.html
+/// [MQTT 3.1.3.2]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901060
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastWill<'a> {
+    pub topic: &'a str,
+    pub message: &'a [u8],
+    pub qos: QoS,
+    pub retain: bool,
+    pub properties: Properties<'a>,
+}
+
+/// Connect packet ([MQTT 3.1]).
+///
+/// The protocol is always [`Protocol::MQTT5`].
+///
+/// [MQTT 3.1]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901033
+/// [`Protocol::MQTT5`]: ../enum.Protocol.html#variant.MQTT5
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connect<'a> {
+    pub keep_alive: u16,
+    pub client_id: &'a str,
+    pub clean_start: bool,
+    pub last_will: Option<LastWill<'a>>,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a [u8]>,
+    pub properties: Properties<'a>,
+}
+
+/// Connack packet ([MQTT 3.2]).
+///
+/// [MQTT 3.2]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901074
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connack<'a> {
+    pub session_present: bool,
+    pub reason_code: ReasonCode,
+    pub properties: Properties<'a>,
+}
+
+impl<'a> Connect<'a> {
+    pub(crate) fn from_buffer(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
+        let protocol = Protocol::from_buffer(buf, offset)?;
+        if protocol != Protocol::MQTT5 {
+            return Err(protocol.unsupported());
+        }
+
+        let connect_flags = read_u8(buf, offset)?;
+        let keep_alive = read_u16(buf, offset)?;
+        let location = Location::Packet(PacketType::Connect);
+        let properties = Properties::from_buffer(buf, offset, location)?;
+
+        let client_id = read_str(buf, offset)?;
+
+        let last_will = if connect_flags & 0b100 != 0 {
+            let will_properties = Properties::from_buffer(buf, offset, Location::Will)?;
+            let will_topic = read_str(buf, offset)?;
+            let will_message = read_bytes(buf, offset)?;
+            let will_qos = QoS::from_u8((connect_flags & 0b11000) >> 3)?;
+            Some(LastWill {
+                topic: will_topic,
+                message: will_message,
+                qos: will_qos,
+                retain: (connect_flags & 0b00100000) != 0,
+                properties: will_properties,
+            })
+        } else {
+            None
+        };
+
+        let username = if connect_flags & 0b10000000 != 0 {
+            Some(read_str(buf, offset)?)
+        } else {
+            None
+        };
+
+        let password = if connect_flags & 0b01000000 != 0 {
+            Some(read_bytes(buf, offset)?)
+        } else {
+            None
+        };
+
+        let clean_start = (connect_flags & 0b10) != 0;
+
+        Ok(Connect {
+            keep_alive,
+            client_id,
+            clean_start,
+            last_will,
+            username,
+            password,
+            properties,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let header: u8 = 0b00010000;
+        // protocol name and level + flags(1) + keep alive(2) + properties
+        let mut length: usize = Protocol::MQTT5.len() + 1 + 2 + self.properties.len();
+        let mut connect_flags: u8 = 0b00000000;
+        if self.clean_start {
+            connect_flags |= 0b10;
+        };
+        length += 2 + self.client_id.len();
+        if let Some(username) = self.username {
+            connect_flags |= 0b10000000;
+            length += 2 + username.len();
+        };
+        if let Some(password) = self.password {
+            connect_flags |= 0b01000000;
+            length += 2 + password.len();
+        };
+        if let Some(last_will) = &self.last_will {
+            connect_flags |= 0b00000100;
+            connect_flags |= last_will.qos.to_u8() << 3;
+            if last_will.retain {
+                connect_flags |= 0b00100000;
+            };
+            length += last_will.properties.len();
+            length += 2 + last_will.topic.len();
+            length += 2 + last_will.message.len();
+        };
+        check_remaining(buf, offset, length + 1)?;
+
+        write_u8(buf, offset, header)?;
+        let write_len = write_length(buf, offset, length)? + 1;
+
+        Protocol::MQTT5.to_buffer(buf, offset)?;
+        write_u8(buf, offset, connect_flags)?;
+        write_u16(buf, offset, self.keep_alive)?;
+        self.properties
+            .to_buffer(Location::Packet(PacketType::Connect), buf, offset)?;
+
+        write_string(buf, offset, self.client_id)?;
+
+        if let Some(last_will) = &self.last_will {
+            last_will.properties.to_buffer(Location::Will, buf, offset)?;
+            write_string(buf, offset, last_will.topic)?;
+            write_bytes(buf, offset, last_will.message)?;
+        };
+
+        if let Some(username) = self.username {
+            write_string(buf, offset, username)?;
+        };
+        if let Some(password) = self.password {
+            write_bytes(buf, offset, password)?;
+        };
+        Ok(write_len)
+    }
+}
+
+impl<'a> Connack<'a> {
+    pub(crate) fn from_buffer(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
+        let flags = read_u8(buf, offset)?;
+        let reason_code = ReasonCode::from_buffer(PacketType::Connack, buf, offset)?;
+        let location = Location::Packet(PacketType::Connack);
+        let properties = Properties::from_buffer(buf, offset, location)?;
+        Ok(Connack {
+            session_present: (flags & 0b1 == 1),
+            reason_code,
+            properties,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let header: u8 = 0b00100000;
+        let length = 2 + self.properties.len();
+        let mut flags: u8 = 0b00000000;
+        if self.session_present {
+            flags |= 0b1;
+        };
+        check_remaining(buf, offset, 1)?;
+        write_u8(buf, offset, header)?;
+
+        let write_len = write_length(buf, offset, length)? + 1;
+        write_u8(buf, offset, flags)?;
+        self.reason_code
+            .to_buffer(PacketType::Connack, buf, offset)?;
+        self.properties
+            .to_buffer(Location::Packet(PacketType::Connack), buf, offset)?;
+        Ok(write_len)
+    }
+}
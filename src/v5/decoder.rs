@@ -0,0 +1,94 @@
+use super::*;
+use crate::{decoder::*, Error, PacketType};
+
+/// Decode bytes from a slice as an MQTT 5 [Packet] enum.
+///
+/// Returns `Ok(None)` if the buffer doesn't contain a full packet yet.
+///
+/// ```
+/// # use mqttrs::*;
+/// let buf = [0b11100000, 1, 0x04]; // Disconnect with Will Message
+/// match v5::decode_slice(&buf) {
+///     Ok(Some(v5::Packet::Disconnect(d))) => {
+///         assert_eq!(d.reason_code, v5::ReasonCode::DisconnectWithWillMessage)
+///     }
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// ```
+///
+/// [Packet]: enum.Packet.html
+pub fn decode_slice<'a>(buf: &'a [u8]) -> Result<Option<Packet<'a>>, Error> {
+    if let Some((_, r)) = decode_slice_with_len(buf)? {
+        Ok(Some(r))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Decode bytes from a slice as an MQTT 5 [Packet] enum, returning a tuple containing the number
+/// of bytes read from the buffer and the [Packet].
+///
+/// [Packet]: enum.Packet.html
+pub fn decode_slice_with_len<'a>(buf: &'a [u8]) -> Result<Option<(usize, Packet<'a>)>, Error> {
+    let mut offset = 0;
+    if let Some((header, remaining_len)) = read_header(buf, &mut offset)? {
+        check_remaining_len(header.typ, remaining_len)?;
+        // Make sure that parsers can't read past the end of this packet.
+        let end = offset + remaining_len;
+        let buf = &buf[..end];
+        let r = read_packet(header, remaining_len, buf, &mut offset)?;
+        // Or stop before it, which would desynchronize the stream.
+        if offset != end {
+            return Err(Error::InvalidRemainingLength(header.typ, remaining_len));
+        }
+        Ok(Some((offset, r)))
+    } else {
+        // Don't have a full packet
+        Ok(None)
+    }
+}
+
+fn read_packet<'a>(
+    header: Header,
+    remaining_len: usize,
+    buf: &'a [u8],
+    offset: &mut usize,
+) -> Result<Packet<'a>, Error> {
+    Ok(match header.typ {
+        PacketType::Pingreq => Packet::Pingreq,
+        PacketType::Pingresp => Packet::Pingresp,
+        PacketType::Disconnect => Disconnect::from_buffer(remaining_len, buf, offset)?.into(),
+        PacketType::Auth => Auth::from_buffer(remaining_len, buf, offset)?.into(),
+        PacketType::Connect => Connect::from_buffer(buf, offset)?.into(),
+        PacketType::Connack => Connack::from_buffer(buf, offset)?.into(),
+        PacketType::Publish => Publish::from_buffer(&header, remaining_len, buf, offset)?.into(),
+        PacketType::Puback => {
+            Packet::Puback(Ack::from_buffer(header.typ, remaining_len, buf, offset)?)
+        }
+        PacketType::Pubrec => {
+            Packet::Pubrec(Ack::from_buffer(header.typ, remaining_len, buf, offset)?)
+        }
+        PacketType::Pubrel => {
+            Packet::Pubrel(Ack::from_buffer(header.typ, remaining_len, buf, offset)?)
+        }
+        PacketType::Pubcomp => {
+            Packet::Pubcomp(Ack::from_buffer(header.typ, remaining_len, buf, offset)?)
+        }
+        PacketType::Subscribe => Subscribe::from_buffer(remaining_len, buf, offset)?.into(),
+        PacketType::Suback => Suback::from_buffer(remaining_len, buf, offset)?.into(),
+        PacketType::Unsubscribe => Unsubscribe::from_buffer(remaining_len, buf, offset)?.into(),
+        PacketType::Unsuback => Unsuback::from_buffer(remaining_len, buf, offset)?.into(),
+    })
+}
+
+/// Check the remaining_len of fixed-size packets. Unlike MQTT 3.1.1, acknowledgements and
+/// `Disconnect` have optional reason codes and properties, so only their minimum size is fixed,
+/// which their parsers check.
+fn check_remaining_len(typ: PacketType, remaining_len: usize) -> Result<(), Error> {
+    match typ {
+        PacketType::Pingreq | PacketType::Pingresp if remaining_len != 0 => {
+            Err(Error::InvalidRemainingLength(typ, remaining_len))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,283 @@
+use crate::v5::*;
+use crate::{Error, Pid, QoS, QosPid};
+use core::convert::TryFrom;
+
+#[test]
+fn test_connect() {
+    let data: &[u8] = &[
+        0b00010000, 46, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x05,
+        0b11001110, // +username, +password, -will retain, will qos=1, +last_will, +clean_start
+        0x00, 0x0a, // 10 sec
+        0x03, 0x21, 0x00, 0x0a, // properties: receive maximum = 10
+        0x00, 0x04, b't', b'e', b's', b't', // client_id
+        0x02, 0x01, 0x01, // will properties: payload format indicator = 1
+        0x00, 0x02, b'/', b'a', // will topic = '/a'
+        0x00, 0x07, b'o', b'f', b'f', b'l', b'i', b'n', b'e', // will msg = 'offline'
+        0x00, 0x04, b'r', b'u', b's', b't', // username = 'rust'
+        0x00, 0x02, b'm', b'q', // password = 'mq'
+    ];
+    let pkt = Connect {
+        keep_alive: 10,
+        client_id: "test",
+        clean_start: true,
+        last_will: Some(LastWill {
+            topic: "/a",
+            message: b"offline",
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            properties: Properties::from(&[Property::PayloadFormatIndicator(1)][..]),
+        }),
+        username: Some("rust"),
+        password: Some(b"mq"),
+        properties: Properties::from(&[Property::ReceiveMaximum(10)][..]),
+    };
+    assert_eq!(Ok(Some((48, pkt.into()))), decode_slice_with_len(data));
+}
+
+#[test]
+fn test_connect_wrong_version() {
+    let data: &[u8] = &[
+        0b00010000, 17, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0b00000010, 0x00, 0x0a, 0x00,
+        0x00, 0x04, b't', b'e', b's', b't',
+    ];
+    assert!(matches!(
+        decode_slice(data),
+        Err(Error::InvalidProtocol(_, 4))
+    ));
+}
+
+#[test]
+fn test_connack() {
+    let data: &[u8] = &[0b00100000, 3, 0b00000001, 0x87, 0];
+    let pkt = Connack {
+        session_present: true,
+        reason_code: ReasonCode::NotAuthorized,
+        properties: Properties::new(),
+    };
+    assert_eq!(Ok(Some(pkt.into())), decode_slice(data));
+}
+
+#[test]
+fn test_publish() {
+    let data: &[u8] = &[
+        0b00111101, 16, 0x00, 0x03, b'a', b'/', b'b', 0, 10, // topic, pid
+        3, 0x23, 0x00, 0x05, // properties: topic alias = 5
+        b'h', b'e', b'l', b'l', b'o',
+    ];
+    match decode_slice(data) {
+        Ok(Some(Packet::Publish(p))) => {
+            assert!(p.dup);
+            assert!(p.retain);
+            assert_eq!(p.qospid, QosPid::from_u8u16(2, 10));
+            assert_eq!(p.topic_name, "a/b");
+            assert_eq!(p.properties.get(0x23), Some(Property::TopicAlias(5)));
+            assert_eq!(p.payload, b"hello");
+        }
+        other => panic!("Failed decode: {:?}", other),
+    }
+}
+
+#[test]
+fn test_ack_short_forms() {
+    let pid = Pid::try_from(10).unwrap();
+    // Remaining length 2: reason code and properties omitted.
+    match decode_slice(&[0b01000000, 2, 0, 10]) {
+        Ok(Some(Packet::Puback(a))) => {
+            assert_eq!(a.pid, pid);
+            assert_eq!(a.reason_code, ReasonCode::Success);
+            assert!(a.properties.is_empty());
+        }
+        other => panic!("Failed decode: {:?}", other),
+    };
+    // Remaining length 3: properties omitted.
+    match decode_slice(&[0b01010000, 3, 0, 10, 0x80]) {
+        Ok(Some(Packet::Pubrec(a))) => {
+            assert_eq!(a.pid, pid);
+            assert_eq!(a.reason_code, ReasonCode::UnspecifiedError);
+            assert!(a.properties.is_empty());
+        }
+        other => panic!("Failed decode: {:?}", other),
+    };
+    // Full form.
+    match decode_slice(&[0b01100010, 7, 0, 10, 0x92, 3, 0x1F, 0, 0]) {
+        Ok(Some(Packet::Pubrel(a))) => {
+            assert_eq!(a.pid, pid);
+            assert_eq!(a.reason_code, ReasonCode::PacketIdentifierNotFound);
+            assert_eq!(a.properties.get(0x1F), Some(Property::ReasonString("")));
+        }
+        other => panic!("Failed decode: {:?}", other),
+    };
+    // Disconnect with remaining length 0 is a normal disconnection.
+    match decode_slice(&[0b11100000, 0]) {
+        Ok(Some(Packet::Disconnect(d))) => assert_eq!(d.reason_code, ReasonCode::Success),
+        other => panic!("Failed decode: {:?}", other),
+    };
+}
+
+#[test]
+fn test_properties_too_long() {
+    // Property length goes past the end of the packet.
+    let data: &[u8] = &[0b01000000, 4, 0, 10, 0x00, 5, 0, 0, 0, 0, 0];
+    assert_eq!(Err(Error::InvalidLength), decode_slice(data));
+    // Property length with 5 continuation bytes.
+    let data: &[u8] = &[0b11100000, 6, 0x00, 0x80, 0x80, 0x80, 0x80, 0x01];
+    assert_eq!(Err(Error::InvalidLength), decode_slice(data));
+}
+
+#[test]
+fn test_subscribe() {
+    let data: &[u8] = &[
+        0b10000010, 9, 0, 10, 0, 0, 3, b'a', b'/', b'b', 0b00101110,
+    ];
+    match decode_slice(data) {
+        Ok(Some(Packet::Subscribe(s))) => {
+            assert_eq!(s.pid.get(), 10);
+            let options = SubscriptionOptions {
+                qos: QoS::ExactlyOnce,
+                no_local: true,
+                retain_as_published: true,
+                retain_handling: RetainHandling::DoNotSend,
+            };
+            assert_eq!(s.topics.len(), 1);
+            assert_eq!(s.topics[0].topic_path.as_str(), "a/b");
+            assert_eq!(s.topics[0].options, options);
+        }
+        other => panic!("Failed decode: {:?}", other),
+    }
+
+    // Reserved bits and retain handling 3 are invalid.
+    for byte in &[0b01000000, 0b00110000] {
+        let data: &[u8] = &[0b10000010, 9, 0, 10, 0, 0, 3, b'a', b'/', b'b', *byte];
+        assert_eq!(
+            Err(Error::InvalidSubscriptionOptions(*byte)),
+            decode_slice(data)
+        );
+    }
+}
+
+#[test]
+fn test_suback() {
+    let data: &[u8] = &[0b10010000, 5, 0, 10, 0, 0x02, 0x87];
+    match decode_slice(data) {
+        Ok(Some(Packet::Suback(s))) => {
+            assert_eq!(s.pid.get(), 10);
+            assert_eq!(
+                &s.reason_codes[..],
+                &[ReasonCode::GrantedQoS2, ReasonCode::NotAuthorized]
+            );
+        }
+        other => panic!("Failed decode: {:?}", other),
+    }
+}
+
+#[test]
+fn test_auth() {
+    let data: &[u8] = &[0b11110000, 8, 0x18, 6, 0x15, 0, 3, b'f', b'o', b'o'];
+    match decode_slice(data) {
+        Ok(Some(Packet::Auth(a))) => {
+            assert_eq!(a.reason_code, ReasonCode::ContinueAuthentication);
+            assert_eq!(
+                a.properties.get(0x15),
+                Some(Property::AuthenticationMethod("foo"))
+            );
+        }
+        other => panic!("Failed decode: {:?}", other),
+    }
+    // Flags must be zero.
+    assert_eq!(Err(Error::InvalidHeader), decode_slice(&[0b11110001, 0]));
+}
+
+#[test]
+fn test_properties() {
+    let data: &[u8] = &[
+        0b00110000, 35, 0x00, 0x01, b't', // topic
+        29, // properties
+        0x02, 0, 0, 0x0e, 0x10, // message expiry interval = 3600
+        0x09, 0, 2, 0xAB, 0xCD, // correlation data
+        0x0B, 0x80, 0x01, // subscription identifier = 128
+        0x0B, 0x02, // subscription identifier = 2
+        0x26, 0, 1, b'a', 0, 1, b'b', // user property a=b
+        0x26, 0, 1, b'a', 0, 1, b'c', // user property a=c
+        b'x', b'y',
+    ];
+    let expected = [
+        Property::MessageExpiryInterval(3600),
+        Property::CorrelationData(&[0xAB, 0xCD]),
+        Property::SubscriptionIdentifier(128),
+        Property::SubscriptionIdentifier(2),
+        Property::UserProperty("a", "b"),
+        Property::UserProperty("a", "c"),
+    ];
+    match decode_slice(data) {
+        Ok(Some(Packet::Publish(p))) => {
+            assert!(p.properties.iter().eq(expected.iter().copied()));
+            assert_eq!(p.properties, Properties::from(&expected[..]));
+            assert_eq!(p.payload, b"xy");
+        }
+        other => panic!("Failed decode: {:?}", other),
+    }
+}
+
+#[test]
+fn test_properties_invalid() {
+    // Unknown property identifier.
+    let data: &[u8] = &[0b11100000, 4, 0x00, 2, 0x7F, 0];
+    assert_eq!(Err(Error::InvalidProperty(0x7F)), decode_slice(data));
+    // Invalid value: maximum QoS can't be 2.
+    let data: &[u8] = &[0b00100000, 5, 0, 0, 2, 0x24, 2];
+    assert_eq!(Err(Error::InvalidProperty(0x24)), decode_slice(data));
+    // Value truncated by the properties length.
+    let data: &[u8] = &[0b11100000, 4, 0x00, 2, 0x11, 0];
+    assert_eq!(Err(Error::InvalidLength), decode_slice(data));
+    // Topic alias is only allowed in Publish.
+    let data: &[u8] = &[0b11100000, 5, 0x00, 3, 0x23, 0, 1];
+    assert_eq!(Err(Error::DisallowedProperty(0x23)), decode_slice(data));
+    // Only user properties and subscription identifiers (in Publish) can be repeated.
+    let data: &[u8] = &[0b11100000, 8, 0x00, 6, 0x1F, 0, 0, 0x1F, 0, 0];
+    assert_eq!(Err(Error::DuplicateProperty(0x1F)), decode_slice(data));
+    let data: &[u8] = &[0b10000010, 11, 0, 10, 4, 0x0B, 1, 0x0B, 2, 0, 1, b'a', 0];
+    assert_eq!(Err(Error::DuplicateProperty(0x0B)), decode_slice(data));
+}
+
+#[test]
+fn test_reason_codes() {
+    // Unknown reason code.
+    let data: &[u8] = &[0b01000000, 3, 0, 10, 0x05];
+    assert_eq!(Err(Error::InvalidReasonCode(0x05)), decode_slice(data));
+    // Known reason code, but not allowed in Pubrel.
+    let data: &[u8] = &[0b01100010, 3, 0, 10, 0x10];
+    assert_eq!(Err(Error::InvalidReasonCode(0x10)), decode_slice(data));
+    // Granted QoS is only for Suback.
+    let data: &[u8] = &[0b10110000, 4, 0, 10, 0, 0x01];
+    assert_eq!(Err(Error::InvalidReasonCode(0x01)), decode_slice(data));
+    let data: &[u8] = &[0b00100000, 3, 0, 0x01, 0];
+    assert_eq!(Err(Error::InvalidReasonCode(0x01)), decode_slice(data));
+
+    for byte in 0..=255 {
+        if let Ok(code) = ReasonCode::from_u8(byte) {
+            assert_eq!(code.to_u8(), byte);
+            assert_eq!(code.is_error(), byte >= 0x80);
+        }
+    }
+}
+
+#[test]
+fn test_remaining_len_mismatch() {
+    use crate::PacketType::*;
+    let cases: &[(&[u8], crate::PacketType, usize)] = &[
+        (&[0b11000000, 1, 0], Pingreq, 1),
+        (&[0b11010000, 2, 0, 0], Pingresp, 2),
+        // Trailing bytes after the properties
+        (&[0b00100000, 4, 0, 0, 0, 0], Connack, 4),
+        (&[0b01000000, 5, 0, 1, 0x10, 0, 0], Puback, 5),
+        (&[0b11100000, 3, 0x04, 0, 0], Disconnect, 3),
+        (&[0b11110000, 3, 0x18, 0, 0], Auth, 3),
+    ];
+    for (buf, typ, len) in cases {
+        let expected = Err(Error::InvalidRemainingLength(*typ, *len));
+        assert_eq!(expected, decode_slice(buf), "{:?}", buf);
+    }
+    // The decoded length includes the fixed header.
+    let buf = [0b01000000, 4, 0, 1, 0x10, 0, 0b11010000, 0];
+    assert_eq!(6, decode_slice_with_len(&buf).unwrap().unwrap().0);
+}
@@ -0,0 +1,48 @@
+use super::Packet;
+use crate::{encoder::*, Error, PacketType};
+
+/// Encode an MQTT 5 [Packet] enum into a slice.
+///
+/// Returns the number of bytes written, or `Error::WriteZero` if the slice is too small.
+///
+/// ```
+/// # use mqttrs::*;
+/// let reason_code = v5::ReasonCode::DisconnectWithWillMessage;
+/// let packet = v5::Packet::Disconnect(v5::Disconnect::new(reason_code));
+/// let mut buf = [0u8; 16];
+/// let len = v5::encode_slice(&packet, &mut buf).expect("failed encoding");
+/// assert_eq!(&buf[..len], &[0b11100000, 1, 0x04]);
+/// ```
+///
+/// [Packet]: enum.Packet.html
+pub fn encode_slice(packet: &Packet, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut offset = 0;
+
+    match packet {
+        Packet::Connect(connect) => connect.to_buffer(buf, &mut offset),
+        Packet::Connack(connack) => connack.to_buffer(buf, &mut offset),
+        Packet::Publish(publish) => publish.to_buffer(buf, &mut offset),
+        Packet::Puback(ack) => ack.to_buffer(PacketType::Puback, buf, &mut offset),
+        Packet::Pubrec(ack) => ack.to_buffer(PacketType::Pubrec, buf, &mut offset),
+        Packet::Pubrel(ack) => ack.to_buffer(PacketType::Pubrel, buf, &mut offset),
+        Packet::Pubcomp(ack) => ack.to_buffer(PacketType::Pubcomp, buf, &mut offset),
+        Packet::Subscribe(subscribe) => subscribe.to_buffer(buf, &mut offset),
+        Packet::Suback(suback) => suback.to_buffer(buf, &mut offset),
+        Packet::Unsubscribe(unsub) => unsub.to_buffer(buf, &mut offset),
+        Packet::Unsuback(unsuback) => unsuback.to_buffer(buf, &mut offset),
+        Packet::Pingreq => {
+            check_remaining(buf, &mut offset, 2)?;
+            write_u8(buf, &mut offset, 0b11000000)?;
+            write_u8(buf, &mut offset, 0)?;
+            Ok(2)
+        }
+        Packet::Pingresp => {
+            check_remaining(buf, &mut offset, 2)?;
+            write_u8(buf, &mut offset, 0b11010000)?;
+            write_u8(buf, &mut offset, 0)?;
+            Ok(2)
+        }
+        Packet::Disconnect(disconnect) => disconnect.to_buffer(buf, &mut offset),
+        Packet::Auth(auth) => auth.to_buffer(buf, &mut offset),
+    }
+}
@@ -0,0 +1,288 @@
+use crate::subscribe::{LimitedString, LimitedVec};
+use crate::v5::*;
+use crate::{Error, Pid, QoS, QosPid};
+use core::convert::TryFrom;
+#[cfg(not(feature = "std"))]
+use core::str::FromStr;
+
+macro_rules! assert_roundtrip {
+    ($pkt:expr, $written_exp:expr) => {
+        let pkt: Packet = $pkt;
+        let mut slice = [0u8; 512];
+        let written = encode_slice(&pkt, &mut slice).unwrap();
+        assert_eq!(written, $written_exp);
+        assert_eq!(Ok(Some((written, pkt))), decode_slice_with_len(&slice[..written]));
+    };
+}
+
+#[test]
+fn test_connect() {
+    let pkt = Connect {
+        keep_alive: 120,
+        client_id: "imvj",
+        clean_start: true,
+        last_will: Some(LastWill {
+            topic: "/a",
+            message: b"offline",
+            qos: QoS::ExactlyOnce,
+            retain: true,
+            properties: Properties::from(&[Property::PayloadFormatIndicator(1)][..]),
+        }),
+        username: None,
+        password: Some(b"secret"),
+        properties: Properties::from(&[Property::SessionExpiryInterval(60)][..]),
+    };
+    assert_roundtrip!(pkt.into(), 48);
+}
+
+#[test]
+fn test_connect_write_zero() {
+    let pkt = Connect {
+        keep_alive: 120,
+        client_id: "imvj",
+        clean_start: true,
+        last_will: None,
+        username: None,
+        password: None,
+        properties: Properties::new(),
+    }
+    .into();
+    let mut slice = [0u8; 18];
+    assert_eq!(Err(Error::WriteZero), encode_slice(&pkt, &mut slice));
+    let mut slice = [0u8; 19];
+    assert_eq!(Ok(19), encode_slice(&pkt, &mut slice));
+}
+
+#[test]
+fn test_connack() {
+    let pkt = Connack {
+        session_present: true,
+        reason_code: ReasonCode::Success,
+        properties: Properties::from(&[Property::MaximumQoS(QoS::AtLeastOnce)][..]),
+    };
+    assert_roundtrip!(pkt.into(), 7);
+}
+
+#[test]
+fn test_publish() {
+    let pkt = Publish {
+        dup: false,
+        qospid: QosPid::from_u8u16(1, 10),
+        retain: true,
+        topic_name: "asdf",
+        properties: Properties::from(&[Property::TopicAlias(5)][..]),
+        payload: b"hello",
+    };
+    assert_roundtrip!(pkt.into(), 19);
+}
+
+#[test]
+fn test_acks() {
+    let ack = Ack {
+        pid: Pid::try_from(19).unwrap(),
+        reason_code: ReasonCode::NoMatchingSubscribers,
+        properties: Properties::from(&[Property::ReasonString("none")][..]),
+    };
+    assert_roundtrip!(Packet::Puback(ack.clone()), 13);
+    assert_roundtrip!(Packet::Pubrec(ack), 13);
+    let ack = Ack {
+        pid: Pid::try_from(19).unwrap(),
+        reason_code: ReasonCode::PacketIdentifierNotFound,
+        properties: Properties::new(),
+    };
+    assert_roundtrip!(Packet::Pubrel(ack.clone()), 5);
+    assert_roundtrip!(Packet::Pubcomp(ack), 5);
+}
+
+#[test]
+fn test_short_forms() {
+    let mut slice = [0u8; 16];
+    // Success without properties: only the pid is written.
+    let pkt = Packet::Puback(Ack::new(Pid::try_from(19).unwrap()));
+    assert_eq!(Ok(4), encode_slice(&pkt, &mut slice));
+    assert_eq!(&slice[..4], &[0b01000000, 2, 0, 19]);
+    assert_eq!(Ok(Some(pkt)), decode_slice(&slice[..4]));
+    // Failure without properties: the property length is omitted.
+    let pkt = Packet::Pubrec(Ack {
+        pid: Pid::try_from(19).unwrap(),
+        reason_code: ReasonCode::QuotaExceeded,
+        properties: Properties::new(),
+    });
+    assert_eq!(Ok(5), encode_slice(&pkt, &mut slice));
+    assert_eq!(&slice[..5], &[0b01010000, 3, 0, 19, 0x97]);
+    assert_eq!(Ok(Some(pkt)), decode_slice(&slice[..5]));
+    // Success with properties: everything is written.
+    let pkt = Packet::Pubcomp(Ack {
+        pid: Pid::try_from(19).unwrap(),
+        reason_code: ReasonCode::Success,
+        properties: Properties::from(&[Property::UserProperty("", "")][..]),
+    });
+    assert_eq!(Ok(11), encode_slice(&pkt, &mut slice));
+    assert_eq!(&slice[..6], &[0b01110000, 9, 0, 19, 0x00, 5]);
+    assert_eq!(Ok(Some(pkt)), decode_slice(&slice[..11]));
+
+    let pkt = Packet::Disconnect(Disconnect::new(ReasonCode::Success));
+    assert_eq!(Ok(2), encode_slice(&pkt, &mut slice));
+    assert_eq!(&slice[..2], &[0b11100000, 0]);
+    assert_eq!(Ok(Some(pkt)), decode_slice(&slice[..2]));
+    let pkt = Packet::Auth(Auth {
+        reason_code: ReasonCode::Success,
+        properties: Properties::new(),
+    });
+    assert_eq!(Ok(2), encode_slice(&pkt, &mut slice));
+    assert_eq!(&slice[..2], &[0b11110000, 0]);
+    assert_eq!(Ok(Some(pkt)), decode_slice(&slice[..2]));
+}
+
+#[test]
+fn test_reason_codes() {
+    let mut slice = [0u8; 16];
+    let pkt = Packet::Pubrel(Ack {
+        pid: Pid::try_from(19).unwrap(),
+        reason_code: ReasonCode::NoMatchingSubscribers,
+        properties: Properties::new(),
+    });
+    assert_eq!(Err(Error::InvalidReasonCode(0x10)), encode_slice(&pkt, &mut slice));
+    let pkt = Packet::Disconnect(Disconnect::new(ReasonCode::GrantedQoS1));
+    assert_eq!(Err(Error::InvalidReasonCode(0x01)), encode_slice(&pkt, &mut slice));
+}
+
+#[test]
+fn test_subscribe() {
+    #[cfg(feature = "std")]
+    let topic_path = LimitedString::from("a/+");
+    #[cfg(not(feature = "std"))]
+    let topic_path = LimitedString::from_str("a/+").unwrap();
+    let topic = SubscribeTopic {
+        topic_path,
+        options: SubscriptionOptions {
+            qos: QoS::AtLeastOnce,
+            no_local: false,
+            retain_as_published: true,
+            retain_handling: RetainHandling::SendOnNewSubscribe,
+        },
+    };
+    let topics: LimitedVec<SubscribeTopic> = core::iter::once(topic).collect();
+    assert_roundtrip!(Subscribe::new(Pid::try_from(345).unwrap(), topics).into(), 11);
+}
+
+#[test]
+fn test_suback_unsuback() {
+    let pid = Pid::try_from(12).unwrap();
+    let codes: LimitedVec<ReasonCode> = IntoIterator::into_iter([
+        ReasonCode::Success,
+        ReasonCode::GrantedQoS1,
+        ReasonCode::UnspecifiedError,
+    ])
+    .collect();
+    assert_roundtrip!(Suback::new(pid, codes).into(), 8);
+    let codes: LimitedVec<ReasonCode> = IntoIterator::into_iter([
+        ReasonCode::NoSubscriptionExisted,
+        ReasonCode::NotAuthorized,
+    ])
+    .collect();
+    assert_roundtrip!(Unsuback::new(pid, codes).into(), 7);
+}
+
+#[test]
+fn test_unsubscribe() {
+    #[cfg(feature = "std")]
+    let topics: LimitedVec<LimitedString> = core::iter::once(LimitedString::from("a/b")).collect();
+    #[cfg(not(feature = "std"))]
+    let topics: LimitedVec<LimitedString> =
+        core::iter::once(LimitedString::from_str("a/b").unwrap()).collect();
+    assert_roundtrip!(Unsubscribe::new(Pid::try_from(12).unwrap(), topics).into(), 10);
+}
+
+#[test]
+fn test_ping_disconnect_auth() {
+    assert_roundtrip!(Packet::Pingreq, 2);
+    assert_roundtrip!(Packet::Pingresp, 2);
+    let disconnect = Disconnect {
+        reason_code: ReasonCode::SessionTakenOver,
+        properties: Properties::new(),
+    };
+    assert_roundtrip!(disconnect.into(), 3);
+    let auth = Auth {
+        reason_code: ReasonCode::ContinueAuthentication,
+        properties: Properties::from(&[Property::AuthenticationMethod("foo")][..]),
+    };
+    assert_roundtrip!(auth.into(), 10);
+}
+
+#[test]
+fn test_properties() {
+    let props = [
+        Property::SessionExpiryInterval(u32::MAX),
+        Property::ReceiveMaximum(20),
+        Property::MaximumPacketSize(1024),
+        Property::TopicAliasMaximum(0),
+        Property::RequestResponseInformation(true),
+        Property::RequestProblemInformation(false),
+        Property::UserProperty("k", "v"),
+        Property::AuthenticationMethod("SCRAM-SHA-1"),
+        Property::AuthenticationData(b"data"),
+    ];
+    let pkt = Connect {
+        keep_alive: 0,
+        client_id: "",
+        clean_start: true,
+        last_will: None,
+        username: None,
+        password: None,
+        properties: Properties::from(&props[..]),
+    };
+    assert_roundtrip!(pkt.into(), 63);
+
+    // A valid raw block is copied as-is.
+    let raw = Properties::from_raw(&[0x1F, 0, 2, b'o', b'k']).unwrap();
+    assert_eq!(raw.iter().next(), Some(Property::ReasonString("ok")));
+    let pkt = Disconnect {
+        reason_code: ReasonCode::Success,
+        properties: raw,
+    };
+    assert_roundtrip!(pkt.into(), 9);
+    assert_eq!(
+        Err(Error::InvalidLength),
+        Properties::from_raw(&[0x1F, 0, 3, b'o', b'k']).map(|_| ())
+    );
+}
+
+#[test]
+fn test_properties_invalid() {
+    let mut slice = [0u8; 64];
+    let disconnect = |props| -> Packet {
+        Disconnect {
+            reason_code: ReasonCode::Success,
+            properties: Properties::from(props),
+        }
+        .into()
+    };
+    let pkt = disconnect(&[Property::TopicAlias(1)][..]);
+    assert_eq!(Err(Error::DisallowedProperty(0x23)), encode_slice(&pkt, &mut slice));
+    let pkt = disconnect(&[Property::ReasonString("a"), Property::ReasonString("b")][..]);
+    assert_eq!(Err(Error::DuplicateProperty(0x1F)), encode_slice(&pkt, &mut slice));
+    let pkt = Connack {
+        session_present: false,
+        reason_code: ReasonCode::Success,
+        properties: Properties::from(&[Property::ReceiveMaximum(0)][..]),
+    };
+    assert_eq!(Err(Error::InvalidProperty(0x21)), encode_slice(&pkt.into(), &mut slice));
+    // Will properties have their own rules.
+    let pkt = Connect {
+        keep_alive: 0,
+        client_id: "",
+        clean_start: true,
+        last_will: Some(LastWill {
+            topic: "t",
+            message: b"",
+            qos: QoS::AtMostOnce,
+            retain: false,
+            properties: Properties::from(&[Property::SessionExpiryInterval(1)][..]),
+        }),
+        username: None,
+        password: None,
+        properties: Properties::from(&[Property::SessionExpiryInterval(1)][..]),
+    };
+    assert_eq!(Err(Error::DisallowedProperty(0x11)), encode_slice(&pkt.into(), &mut slice));
+}
@@ -0,0 +1,67 @@
+//! [MQTT 5] packet model.
+//!
+//! MQTT 5 keeps the fixed header of MQTT 3.1.1, but adds a properties section to most packets,
+//! reason codes to all acknowledgements, and a new [AUTH] packet. Since the layout of nearly every
+//! packet differs, MQTT 5 packets get their own [Packet] enum, living alongside the 3.1.1
+//! [`mqttrs::Packet`]. Primitive types like [`Pid`], [`QoS`] and [`QosPid`] are shared.
+//!
+//! Use [decode_slice_versioned()] if you don't know the protocol version in advance, for example
+//! on the server side before the client's `Connect` packet has been received.
+//!
+//! ```
+//! use mqttrs::{v5, QosPid};
+//!
+//! let mut buf = [0u8; 1024];
+//!
+//! let pkt = v5::Packet::Publish(v5::Publish { dup: false,
+//!                                             qospid: QosPid::AtMostOnce,
+//!                                             retain: false,
+//!                                             topic_name: "to/pic",
+//!                                             properties: v5::Properties::new(),
+//!                                             payload: b"payload" });
+//! let len = v5::encode_slice(&pkt, &mut buf).unwrap();
+//! assert_eq!(Ok(Some(pkt)), v5::decode_slice(&buf[..len]));
+//! ```
+//!
+//! [MQTT 5]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html
+//! [AUTH]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901217
+//! [Packet]: enum.Packet.html
+//! [`mqttrs::Packet`]: ../type.Packet.html
+//! [`Pid`]: ../struct.Pid.html
+//! [`QoS`]: ../enum.QoS.html
+//! [`QosPid`]: ../enum.QosPid.html
+//! [decode_slice_versioned()]: ../fn.decode_slice_versioned.html
+
+mod ack;
+mod auth;
+mod connect;
+mod decoder;
+mod encoder;
+mod packet;
+mod property;
+mod publish;
+mod reason;
+mod subscribe;
+
+#[cfg(test)]
+mod auth_test;
+#[cfg(test)]
+mod decoder_test;
+#[cfg(test)]
+mod encoder_test;
+
+pub use self::{
+    ack::{Ack, Auth, Disconnect},
+    auth::{AuthEvent, AuthProperties, AuthState, EnhancedAuth},
+    connect::{Connack, Connect, LastWill},
+    decoder::{decode_slice, decode_slice_with_len},
+    encoder::encode_slice,
+    packet::Packet,
+    property::{Properties, PropertiesIter, Property},
+    publish::Publish,
+    reason::ReasonCode,
+    subscribe::{
+        RetainHandling, Suback, Subscribe, SubscribeTopic, SubscriptionOptions, Unsuback,
+        Unsubscribe,
+    },
+};
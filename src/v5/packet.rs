@@ -0,0 +1,95 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use super::*;
+use crate::PacketType;
+
+/// Base enum for all MQTT 5 packet types.
+///
+/// This is the MQTT 5 counterpart of [`mqttrs::Packet`], as an output of [`decode_slice()`] and
+/// an input of [`encode_slice()`].
+///
+/// ```
+/// # use mqttrs::*;
+/// # use core::convert::TryFrom;
+/// let pkt = v5::Packet::Puback(v5::Ack { pid: Pid::try_from(42).unwrap(),
+///                                        reason_code: v5::ReasonCode::NoMatchingSubscribers,
+///                                        properties: v5::Properties::new() });
+/// let pkt = v5::Packet::Connack(v5::Connack { session_present: false,
+///                                             reason_code: v5::ReasonCode::Success,
+///                                             properties: v5::Properties::new() });
+/// ```
+///
+/// [`mqttrs::Packet`]: ../type.Packet.html
+/// [`decode_slice()`]: fn.decode_slice.html
+/// [`encode_slice()`]: fn.encode_slice.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet<'a> {
+    /// [MQTT 3.1](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901033)
+    Connect(Connect<'a>),
+    /// [MQTT 3.2](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901074)
+    Connack(Connack<'a>),
+    /// [MQTT 3.3](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901100)
+    Publish(Publish<'a>),
+    /// [MQTT 3.4](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901121)
+    Puback(Ack<'a>),
+    /// [MQTT 3.5](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901131)
+    Pubrec(Ack<'a>),
+    /// [MQTT 3.6](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901141)
+    Pubrel(Ack<'a>),
+    /// [MQTT 3.7](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901151)
+    Pubcomp(Ack<'a>),
+    /// [MQTT 3.8](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901161)
+    Subscribe(Subscribe<'a>),
+    /// [MQTT 3.9](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901171)
+    Suback(Suback<'a>),
+    /// [MQTT 3.10](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901179)
+    Unsubscribe(Unsubscribe<'a>),
+    /// [MQTT 3.11](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901187)
+    Unsuback(Unsuback<'a>),
+    /// [MQTT 3.12](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901195)
+    Pingreq,
+    /// [MQTT 3.13](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901200)
+    Pingresp,
+    /// [MQTT 3.14](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901205)
+    Disconnect(Disconnect<'a>),
+    /// [MQTT 3.15](https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901217)
+    Auth(Auth<'a>),
+}
+
+impl<'a> Packet<'a> {
+    /// Return the packet type variant.
+    pub fn get_type(&self) -> PacketType {
+        match self {
+            Packet::Connect(_) => PacketType::Connect,
+            Packet::Connack(_) => PacketType::Connack,
+            Packet::Publish(_) => PacketType::Publish,
+            Packet::Puback(_) => PacketType::Puback,
+            Packet::Pubrec(_) => PacketType::Pubrec,
+            Packet::Pubrel(_) => PacketType::Pubrel,
+            Packet::Pubcomp(_) => PacketType::Pubcomp,
+            Packet::Subscribe(_) => PacketType::Subscribe,
+            Packet::Suback(_) => PacketType::Suback,
+            Packet::Unsubscribe(_) => PacketType::Unsubscribe,
+            Packet::Unsuback(_) => PacketType::Unsuback,
+            Packet::Pingreq => PacketType::Pingreq,
+            Packet::Pingresp => PacketType::Pingresp,
+            Packet::Disconnect(_) => PacketType::Disconnect,
+            Packet::Auth(_) => PacketType::Auth,
+        }
+    }
+}
+
+macro_rules! packet_from {
+    ($($t:ident),+) => {
+        $(
+            impl<'a> From<$t<'a>> for Packet<'a> {
+                fn from(p: $t<'a>) -> Self {
+                    Packet::$t(p)
+                }
+            }
+        )+
+    }
+}
+
+packet_from!(Connect, Connack, Publish, Subscribe, Suback, Unsubscribe, Unsuback, Disconnect, Auth);
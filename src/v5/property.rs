@@ -0,0 +1,587 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use crate::{decoder::*, encoder::*, Error, PacketType, QoS};
+use core::fmt;
+
+/// MQTT 5 property ([MQTT 2.2.2.2]).
+///
+/// Each variant is named after the property it represents, and holds its decoded value. String
+/// and binary values are borrowed from the packet buffer.
+///
+/// [MQTT 2.2.2.2]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901029
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property<'a> {
+    /// 0x01, 0 for unspecified bytes, 1 for UTF-8 encoded payload.
+    PayloadFormatIndicator(u8),
+    /// 0x02, in seconds.
+    MessageExpiryInterval(u32),
+    /// 0x03
+    ContentType(&'a str),
+    /// 0x08
+    ResponseTopic(&'a str),
+    /// 0x09
+    CorrelationData(&'a [u8]),
+    /// 0x0B, between 1 and 268,435,455.
+    SubscriptionIdentifier(u32),
+    /// 0x11, in seconds.
+    SessionExpiryInterval(u32),
+    /// 0x12
+    AssignedClientIdentifier(&'a str),
+    /// 0x13, in seconds.
+    ServerKeepAlive(u16),
+    /// 0x15
+    AuthenticationMethod(&'a str),
+    /// 0x16
+    AuthenticationData(&'a [u8]),
+    /// 0x17
+    RequestProblemInformation(bool),
+    /// 0x18, in seconds.
+    WillDelayInterval(u32),
+    /// 0x19
+    RequestResponseInformation(bool),
+    /// 0x1A
+    ResponseInformation(&'a str),
+    /// 0x1C
+    ServerReference(&'a str),
+    /// 0x1F
+    ReasonString(&'a str),
+    /// 0x21, can't be 0.
+    ReceiveMaximum(u16),
+    /// 0x22
+    TopicAliasMaximum(u16),
+    /// 0x23, can't be 0.
+    TopicAlias(u16),
+    /// 0x24, can't be `QoS::ExactlyOnce`, which is the default.
+    MaximumQoS(QoS),
+    /// 0x25
+    RetainAvailable(bool),
+    /// 0x26, name and value. The only property that can appear multiple times in any packet.
+    UserProperty(&'a str, &'a str),
+    /// 0x27, can't be 0.
+    MaximumPacketSize(u32),
+    /// 0x28
+    WildcardSubscriptionAvailable(bool),
+    /// 0x29
+    SubscriptionIdentifierAvailable(bool),
+    /// 0x2A
+    SharedSubscriptionAvailable(bool),
+}
+
+/// Where a properties section is located, which determines the properties it may contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Location {
+    Packet(PacketType),
+    Will,
+}
+
+impl<'a> Property<'a> {
+    /// Get the property identifier.
+    pub fn id(&self) -> u8 {
+        match self {
+            Property::PayloadFormatIndicator(_) => 0x01,
+            Property::MessageExpiryInterval(_) => 0x02,
+            Property::ContentType(_) => 0x03,
+            Property::ResponseTopic(_) => 0x08,
+            Property::CorrelationData(_) => 0x09,
+            Property::SubscriptionIdentifier(_) => 0x0B,
+            Property::SessionExpiryInterval(_) => 0x11,
+            Property::AssignedClientIdentifier(_) => 0x12,
+            Property::ServerKeepAlive(_) => 0x13,
+            Property::AuthenticationMethod(_) => 0x15,
+            Property::AuthenticationData(_) => 0x16,
+            Property::RequestProblemInformation(_) => 0x17,
+            Property::WillDelayInterval(_) => 0x18,
+            Property::RequestResponseInformation(_) => 0x19,
+            Property::ResponseInformation(_) => 0x1A,
+            Property::ServerReference(_) => 0x1C,
+            Property::ReasonString(_) => 0x1F,
+            Property::ReceiveMaximum(_) => 0x21,
+            Property::TopicAliasMaximum(_) => 0x22,
+            Property::TopicAlias(_) => 0x23,
+            Property::MaximumQoS(_) => 0x24,
+            Property::RetainAvailable(_) => 0x25,
+            Property::UserProperty(_, _) => 0x26,
+            Property::MaximumPacketSize(_) => 0x27,
+            Property::WildcardSubscriptionAvailable(_) => 0x28,
+            Property::SubscriptionIdentifierAvailable(_) => 0x29,
+            Property::SharedSubscriptionAvailable(_) => 0x2A,
+        }
+    }
+
+    /// Whether this property may be found at the given location.
+    pub(crate) fn allowed_in(&self, location: Location) -> bool {
+        use PacketType::*;
+        let packet = match location {
+            Location::Will => {
+                return matches!(
+                    self,
+                    Property::PayloadFormatIndicator(_)
+                        | Property::MessageExpiryInterval(_)
+                        | Property::ContentType(_)
+                        | Property::ResponseTopic(_)
+                        | Property::CorrelationData(_)
+                        | Property::WillDelayInterval(_)
+                        | Property::UserProperty(_, _)
+                )
+            }
+            Location::Packet(p) => p,
+        };
+        match self {
+            Property::PayloadFormatIndicator(_)
+            | Property::MessageExpiryInterval(_)
+            | Property::ContentType(_)
+            | Property::ResponseTopic(_)
+            | Property::CorrelationData(_)
+            | Property::TopicAlias(_) => packet == Publish,
+            Property::SubscriptionIdentifier(_) => matches!(packet, Publish | Subscribe),
+            Property::SessionExpiryInterval(_) => matches!(packet, Connect | Connack | Disconnect),
+            Property::AssignedClientIdentifier(_)
+            | Property::ServerKeepAlive(_)
+            | Property::ResponseInformation(_)
+            | Property::MaximumQoS(_)
+            | Property::RetainAvailable(_)
+            | Property::WildcardSubscriptionAvailable(_)
+            | Property::SubscriptionIdentifierAvailable(_)
+            | Property::SharedSubscriptionAvailable(_) => packet == Connack,
+            Property::AuthenticationMethod(_) | Property::AuthenticationData(_) => {
+                matches!(packet, Connect | Connack | Auth)
+            }
+            Property::RequestProblemInformation(_) | Property::RequestResponseInformation(_) => {
+                packet == Connect
+            }
+            Property::WillDelayInterval(_) => false,
+            Property::ServerReference(_) => matches!(packet, Connack | Disconnect),
+            Property::ReasonString(_) => !matches!(
+                packet,
+                Connect | Publish | Subscribe | Unsubscribe | Pingreq | Pingresp
+            ),
+            Property::ReceiveMaximum(_)
+            | Property::TopicAliasMaximum(_)
+            | Property::MaximumPacketSize(_) => matches!(packet, Connect | Connack),
+            Property::UserProperty(_, _) => !matches!(packet, Pingreq | Pingresp),
+        }
+    }
+
+    /// Whether this property may appear more than once in the same properties section.
+    fn repeatable(&self, location: Location) -> bool {
+        match self {
+            Property::UserProperty(_, _) => true,
+            // Publish packets forwarded by the server can match multiple subscriptions.
+            Property::SubscriptionIdentifier(_) => {
+                location == Location::Packet(PacketType::Publish)
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn from_buffer(buf: &'a [u8], offset: &mut usize) -> Result<Self, Error> {
+        let id = read_varint(buf, offset)?;
+        let invalid = Error::InvalidProperty(id as u8);
+        let prop = match id {
+            0x01 => Property::PayloadFormatIndicator(match read_u8(buf, offset)? {
+                b @ 0..=1 => b,
+                _ => return Err(invalid),
+            }),
+            0x02 => Property::MessageExpiryInterval(read_u32(buf, offset)?),
+            0x03 => Property::ContentType(read_str(buf, offset)?),
+            0x08 => Property::ResponseTopic(read_str(buf, offset)?),
+            0x09 => Property::CorrelationData(read_bytes(buf, offset)?),
+            0x0B => match read_varint(buf, offset)? {
+                0 => return Err(invalid),
+                n => Property::SubscriptionIdentifier(n as u32),
+            },
+            0x11 => Property::SessionExpiryInterval(read_u32(buf, offset)?),
+            0x12 => Property::AssignedClientIdentifier(read_str(buf, offset)?),
+            0x13 => Property::ServerKeepAlive(read_u16(buf, offset)?),
+            0x15 => Property::AuthenticationMethod(read_str(buf, offset)?),
+            0x16 => Property::AuthenticationData(read_bytes(buf, offset)?),
+            0x17 => Property::RequestProblemInformation(read_bool(buf, offset, invalid)?),
+            0x18 => Property::WillDelayInterval(read_u32(buf, offset)?),
+            0x19 => Property::RequestResponseInformation(read_bool(buf, offset, invalid)?),
+            0x1A => Property::ResponseInformation(read_str(buf, offset)?),
+            0x1C => Property::ServerReference(read_str(buf, offset)?),
+            0x1F => Property::ReasonString(read_str(buf, offset)?),
+            0x21 => match read_u16(buf, offset)? {
+                0 => return Err(invalid),
+                n => Property::ReceiveMaximum(n),
+            },
+            0x22 => Property::TopicAliasMaximum(read_u16(buf, offset)?),
+            0x23 => match read_u16(buf, offset)? {
+                0 => return Err(invalid),
+                n => Property::TopicAlias(n),
+            },
+            0x24 => match read_u8(buf, offset)? {
+                0 => Property::MaximumQoS(QoS::AtMostOnce),
+                1 => Property::MaximumQoS(QoS::AtLeastOnce),
+                _ => return Err(invalid),
+            },
+            0x25 => Property::RetainAvailable(read_bool(buf, offset, invalid)?),
+            0x26 => Property::UserProperty(read_str(buf, offset)?, read_str(buf, offset)?),
+            0x27 => match read_u32(buf, offset)? {
+                0 => return Err(invalid),
+                n => Property::MaximumPacketSize(n),
+            },
+            0x28 => Property::WildcardSubscriptionAvailable(read_bool(buf, offset, invalid)?),
+            0x29 => Property::SubscriptionIdentifierAvailable(read_bool(buf, offset, invalid)?),
+            0x2A => Property::SharedSubscriptionAvailable(read_bool(buf, offset, invalid)?),
+            _ => return Err(invalid),
+        };
+        Ok(prop)
+    }
+
+    /// Number of bytes written by `to_buffer()`.
+    pub(crate) fn len(&self) -> usize {
+        1 + match self {
+            Property::PayloadFormatIndicator(_)
+            | Property::RequestProblemInformation(_)
+            | Property::RequestResponseInformation(_)
+            | Property::MaximumQoS(_)
+            | Property::RetainAvailable(_)
+            | Property::WildcardSubscriptionAvailable(_)
+            | Property::SubscriptionIdentifierAvailable(_)
+            | Property::SharedSubscriptionAvailable(_) => 1,
+            Property::ServerKeepAlive(_)
+            | Property::ReceiveMaximum(_)
+            | Property::TopicAliasMaximum(_)
+            | Property::TopicAlias(_) => 2,
+            Property::MessageExpiryInterval(_)
+            | Property::SessionExpiryInterval(_)
+            | Property::WillDelayInterval(_)
+            | Property::MaximumPacketSize(_) => 4,
+            Property::SubscriptionIdentifier(n) => varint_len(*n as usize),
+            Property::ContentType(s)
+            | Property::ResponseTopic(s)
+            | Property::AssignedClientIdentifier(s)
+            | Property::AuthenticationMethod(s)
+            | Property::ResponseInformation(s)
+            | Property::ServerReference(s)
+            | Property::ReasonString(s) => 2 + s.len(),
+            Property::CorrelationData(b) | Property::AuthenticationData(b) => 2 + b.len(),
+            Property::UserProperty(k, v) => 2 + k.len() + 2 + v.len(),
+        }
+    }
+
+    pub(crate) fn to_buffer(self, buf: &mut [u8], offset: &mut usize) -> Result<(), Error> {
+        write_u8(buf, offset, self.id())?;
+        match self {
+            Property::PayloadFormatIndicator(b) => write_u8(buf, offset, b),
+            Property::RequestProblemInformation(b)
+            | Property::RequestResponseInformation(b)
+            | Property::RetainAvailable(b)
+            | Property::WildcardSubscriptionAvailable(b)
+            | Property::SubscriptionIdentifierAvailable(b)
+            | Property::SharedSubscriptionAvailable(b) => write_u8(buf, offset, b as u8),
+            Property::MaximumQoS(qos) => write_u8(buf, offset, qos.to_u8()),
+            Property::ServerKeepAlive(n)
+            | Property::ReceiveMaximum(n)
+            | Property::TopicAliasMaximum(n)
+            | Property::TopicAlias(n) => write_u16(buf, offset, n),
+            Property::MessageExpiryInterval(n)
+            | Property::SessionExpiryInterval(n)
+            | Property::WillDelayInterval(n)
+            | Property::MaximumPacketSize(n) => write_u32(buf, offset, n),
+            Property::SubscriptionIdentifier(n) => write_varint(buf, offset, n as usize),
+            Property::ContentType(s)
+            | Property::ResponseTopic(s)
+            | Property::AssignedClientIdentifier(s)
+            | Property::AuthenticationMethod(s)
+            | Property::ResponseInformation(s)
+            | Property::ServerReference(s)
+            | Property::ReasonString(s) => write_string(buf, offset, s),
+            Property::CorrelationData(b) | Property::AuthenticationData(b) => {
+                write_bytes(buf, offset, b)
+            }
+            Property::UserProperty(k, v) => {
+                write_string(buf, offset, k)?;
+                write_string(buf, offset, v)
+            }
+        }
+    }
+
+    /// Check values that the type system doesn't rule out, mirroring `from_buffer()`.
+    fn check_value(&self) -> Result<(), Error> {
+        let valid = match *self {
+            Property::PayloadFormatIndicator(b) => b <= 1,
+            Property::SubscriptionIdentifier(n) => n > 0 && n <= 268_435_455,
+            Property::ReceiveMaximum(n) | Property::TopicAlias(n) => n > 0,
+            Property::MaximumPacketSize(n) => n > 0,
+            Property::MaximumQoS(qos) => qos != QoS::ExactlyOnce,
+            _ => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidProperty(self.id()))
+        }
+    }
+}
+
+fn read_bool(buf: &[u8], offset: &mut usize, invalid: Error) -> Result<bool, Error> {
+    match read_u8(buf, offset)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(invalid),
+    }
+}
+
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Clone, Copy)]
+enum Repr<'a> {
+    /// Encoded properties block, borrowed from the decoded buffer.
+    Raw(&'a [u8]),
+    /// Properties supplied by the user, to be encoded.
+    List(&'a [Property<'a>]),
+}
+
+/// Properties section of an MQTT 5 packet ([MQTT 2.2.2]).
+///
+/// Decoded properties borrow the still-encoded block from the input buffer, and are only parsed
+/// into [Property] values while iterating. To encode properties, build a `Properties` from a
+/// slice. Neither direction allocates.
+///
+/// ```
+/// # use mqttrs::v5::*;
+/// let list = [Property::ContentType("text/plain"), Property::UserProperty("k", "v")];
+/// let props = Properties::from(&list[..]);
+///
+/// let mut content_type = None;
+/// for prop in props {
+///     if let Property::ContentType(s) = prop {
+///         content_type = Some(s);
+///     }
+/// }
+/// assert_eq!(content_type, Some("text/plain"));
+/// ```
+///
+/// When decoding, each property is checked against the packet it is found in: unknown or invalid
+/// properties return `Error::InvalidProperty`, properties that aren't allowed in that packet
+/// return `Error::DisallowedProperty`, and properties appearing more than once (apart from
+/// `UserProperty` and some `SubscriptionIdentifier`) return `Error::DuplicateProperty`. The same
+/// checks are done when encoding.
+///
+/// [MQTT 2.2.2]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901027
+/// [Property]: enum.Property.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Clone, Copy)]
+pub struct Properties<'a> {
+    repr: Repr<'a>,
+}
+
+impl<'a> Properties<'a> {
+    /// Returns an empty properties section.
+    pub fn new() -> Self {
+        Properties {
+            repr: Repr::List(&[]),
+        }
+    }
+
+    /// Wrap an already-encoded properties block (without the length prefix).
+    ///
+    /// Returns an error if the block can't be parsed. Whether the properties are allowed in a
+    /// particular packet will be checked when encoding.
+    pub fn from_raw(raw: &'a [u8]) -> Result<Self, Error> {
+        let mut offset = 0;
+        while offset < raw.len() {
+            Property::from_buffer(raw, &mut offset)?;
+        }
+        Ok(Properties {
+            repr: Repr::Raw(raw),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self.repr {
+            Repr::Raw(raw) => raw.is_empty(),
+            Repr::List(list) => list.is_empty(),
+        }
+    }
+
+    /// Iterate over the properties, in the order they were decoded or supplied.
+    pub fn iter(&self) -> PropertiesIter<'a> {
+        PropertiesIter {
+            repr: self.repr,
+            offset: 0,
+        }
+    }
+
+    /// Get the first property with the given identifier.
+    pub fn get(&self, id: u8) -> Option<Property<'a>> {
+        self.iter().find(|p| p.id() == id)
+    }
+
+    /// Get the `AuthenticationMethod` property, used for [enhanced authentication].
+    ///
+    /// [enhanced authentication]: struct.EnhancedAuth.html
+    pub fn authentication_method(&self) -> Option<&'a str> {
+        match self.get(0x15) {
+            Some(Property::AuthenticationMethod(method)) => Some(method),
+            _ => None,
+        }
+    }
+
+    /// Get the `AuthenticationData` property, used for [enhanced authentication].
+    ///
+    /// [enhanced authentication]: struct.EnhancedAuth.html
+    pub fn authentication_data(&self) -> Option<&'a [u8]> {
+        match self.get(0x16) {
+            Some(Property::AuthenticationData(data)) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes of the properties, without the length prefix.
+    fn inner_len(&self) -> usize {
+        match self.repr {
+            Repr::Raw(raw) => raw.len(),
+            Repr::List(list) => list.iter().map(|p| p.len()).sum(),
+        }
+    }
+
+    /// Number of bytes written by `to_buffer()`, including the length prefix.
+    pub(crate) fn len(&self) -> usize {
+        let len = self.inner_len();
+        varint_len(len) + len
+    }
+
+    /// Check that each property is valid, allowed at this location, and not duplicated.
+    pub(crate) fn validate(&self, location: Location) -> Result<(), Error> {
+        // All identifiers are < 64, so a bitmask is enough to find duplicates.
+        let mut seen: u64 = 0;
+        for prop in self.iter() {
+            let id = prop.id();
+            prop.check_value()?;
+            if !prop.allowed_in(location) {
+                return Err(Error::DisallowedProperty(id));
+            }
+            if seen & (1 << id) != 0 && !prop.repeatable(location) {
+                return Err(Error::DuplicateProperty(id));
+            }
+            seen |= 1 << id;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn from_buffer(
+        buf: &'a [u8],
+        offset: &mut usize,
+        location: Location,
+    ) -> Result<Self, Error> {
+        let len = read_varint(buf, offset)?;
+        if len > buf[*offset..].len() {
+            return Err(Error::InvalidLength);
+        }
+        let props = Properties::from_raw(&buf[*offset..*offset + len])?;
+        props.validate(location)?;
+        *offset += len;
+        Ok(props)
+    }
+
+    pub(crate) fn to_buffer(
+        self,
+        location: Location,
+        buf: &mut [u8],
+        offset: &mut usize,
+    ) -> Result<(), Error> {
+        self.validate(location)?;
+        write_varint(buf, offset, self.inner_len())?;
+        match self.repr {
+            Repr::Raw(raw) => {
+                for &byte in raw {
+                    write_u8(buf, offset, byte)?;
+                }
+            }
+            Repr::List(list) => {
+                for prop in list {
+                    prop.to_buffer(buf, offset)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Default for Properties<'a> {
+    fn default() -> Self {
+        Properties::new()
+    }
+}
+
+impl<'a> From<&'a [Property<'a>]> for Properties<'a> {
+    fn from(list: &'a [Property<'a>]) -> Self {
+        Properties {
+            repr: Repr::List(list),
+        }
+    }
+}
+
+impl<'a> PartialEq for Properties<'a> {
+    /// Properties are equal if they contain the same values in the same order, regardless of
+    /// whether they were decoded or supplied as a list.
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<'a> fmt::Debug for Properties<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for Properties<'a> {
+    type Item = Property<'a>;
+    type IntoIter = PropertiesIter<'a>;
+
+    fn into_iter(self) -> PropertiesIter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &Properties<'a> {
+    type Item = Property<'a>;
+    type IntoIter = PropertiesIter<'a>;
+
+    fn into_iter(self) -> PropertiesIter<'a> {
+        self.iter()
+    }
+}
+
+/// Borrowed iterator over [Properties], returned by [`Properties::iter()`].
+///
+/// Encoded properties are parsed lazily, without allocating.
+///
+/// [Properties]: struct.Properties.html
+/// [`Properties::iter()`]: struct.Properties.html#method.iter
+#[derive(Debug, Clone)]
+pub struct PropertiesIter<'a> {
+    repr: Repr<'a>,
+    offset: usize,
+}
+
+impl<'a> Iterator for PropertiesIter<'a> {
+    type Item = Property<'a>;
+
+    fn next(&mut self) -> Option<Property<'a>> {
+        match self.repr {
+            // The block was checked when creating the `Properties`, so this can't fail.
+            Repr::Raw(raw) if self.offset < raw.len() => {
+                Property::from_buffer(raw, &mut self.offset).ok()
+            }
+            Repr::List(list) if self.offset < list.len() => {
+                self.offset += 1;
+                Some(list[self.offset - 1])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Repr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Repr::Raw(raw) => f.debug_tuple("Raw").field(raw).finish(),
+            Repr::List(list) => f.debug_tuple("List").field(list).finish(),
+        }
+    }
+}
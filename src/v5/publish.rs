@@ -0,0 +1,102 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use super::{property::Location, Properties};
+use crate::{decoder::*, encoder::*, Error, PacketType, Pid, QoS, QosPid};
+
+/// Publish packet ([MQTT 3.3]).
+///
+/// The `topic_name` may be empty if the properties contain a topic alias.
+///
+/// [MQTT 3.3]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901100
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Publish<'a> {
+    pub dup: bool,
+    pub qospid: QosPid,
+    pub retain: bool,
+    pub topic_name: &'a str,
+    pub properties: Properties<'a>,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Publish<'a> {
+    pub(crate) fn from_buffer(
+        header: &Header,
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let payload_end = *offset + remaining_len;
+        let topic_name = read_str(buf, offset)?;
+
+        let qospid = match header.qos {
+            QoS::AtMostOnce => QosPid::AtMostOnce,
+            QoS::AtLeastOnce => QosPid::AtLeastOnce(Pid::from_buffer(buf, offset)?),
+            QoS::ExactlyOnce => QosPid::ExactlyOnce(Pid::from_buffer(buf, offset)?),
+        };
+
+        let location = Location::Packet(PacketType::Publish);
+        let properties = Properties::from_buffer(buf, offset, location)?;
+
+        let payload = buf.get(*offset..payload_end).ok_or(Error::InvalidLength)?;
+        *offset = payload_end;
+
+        Ok(Publish {
+            dup: header.dup,
+            qospid,
+            retain: header.retain,
+            topic_name,
+            properties,
+            payload,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        // Header
+        let mut header: u8 = match self.qospid {
+            QosPid::AtMostOnce => 0b00110000,
+            QosPid::AtLeastOnce(_) => 0b00110010,
+            QosPid::ExactlyOnce(_) => 0b00110100,
+        };
+        if self.dup {
+            header |= 0b00001000_u8;
+        };
+        if self.retain {
+            header |= 0b00000001_u8;
+        };
+        check_remaining(buf, offset, 1)?;
+        write_u8(buf, offset, header)?;
+
+        // Length: topic (2+len) + pid (0/2) + properties + payload (len)
+        let length = self.topic_name.len()
+            + match self.qospid {
+                QosPid::AtMostOnce => 2,
+                _ => 4,
+            }
+            + self.properties.len()
+            + self.payload.len();
+
+        let write_len = write_length(buf, offset, length)? + 1;
+
+        // Topic
+        write_string(buf, offset, self.topic_name)?;
+
+        // Pid
+        match self.qospid {
+            QosPid::AtMostOnce => (),
+            QosPid::AtLeastOnce(pid) => pid.to_buffer(buf, offset)?,
+            QosPid::ExactlyOnce(pid) => pid.to_buffer(buf, offset)?,
+        }
+
+        // Properties
+        self.properties
+            .to_buffer(Location::Packet(PacketType::Publish), buf, offset)?;
+
+        // Payload
+        for &byte in self.payload {
+            write_u8(buf, offset, byte)?;
+        }
+
+        Ok(write_len)
+    }
+}
@@ -0,0 +1,379 @@
+#[cfg(feature = "defmt")]
+use defmt::Format;
+use super::{property::Location, Properties, ReasonCode};
+use crate::{
+    decoder::*,
+    encoder::*,
+    subscribe::{limited_push, limited_string, LimitedString, LimitedVec},
+    Error, PacketType, Pid, QoS,
+};
+#[cfg(feature = "derive")]
+use serde::{Deserialize, Serialize};
+
+/// When the server should send retained messages on subscribe ([MQTT 3.8.3.1]).
+///
+/// [MQTT 3.8.3.1]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901169
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "derive", derive(Serialize, Deserialize))]
+pub enum RetainHandling {
+    /// Send retained messages at the time of the subscribe.
+    SendOnSubscribe,
+    /// Send retained messages at subscribe only if the subscription does not currently exist.
+    SendOnNewSubscribe,
+    /// Do not send retained messages at the time of the subscribe.
+    DoNotSend,
+}
+
+/// Subscription options of a [SubscribeTopic] ([MQTT 3.8.3.1]).
+///
+/// [SubscribeTopic]: struct.SubscribeTopic.html
+/// [MQTT 3.8.3.1]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901169
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "derive", derive(Serialize, Deserialize))]
+pub struct SubscriptionOptions {
+    pub qos: QoS,
+    pub no_local: bool,
+    pub retain_as_published: bool,
+    pub retain_handling: RetainHandling,
+}
+
+impl SubscriptionOptions {
+    pub(crate) fn from_u8(byte: u8) -> Result<Self, Error> {
+        let retain_handling = match (byte >> 4) & 0b11 {
+            0 => RetainHandling::SendOnSubscribe,
+            1 => RetainHandling::SendOnNewSubscribe,
+            2 => RetainHandling::DoNotSend,
+            _ => return Err(Error::InvalidSubscriptionOptions(byte)),
+        };
+        if byte & 0b11000000 != 0 {
+            return Err(Error::InvalidSubscriptionOptions(byte));
+        }
+        Ok(SubscriptionOptions {
+            qos: QoS::from_u8(byte & 0b11)?,
+            no_local: byte & 0b100 != 0,
+            retain_as_published: byte & 0b1000 != 0,
+            retain_handling,
+        })
+    }
+
+    pub(crate) fn to_u8(self) -> u8 {
+        let mut byte = self.qos.to_u8();
+        if self.no_local {
+            byte |= 0b100;
+        }
+        if self.retain_as_published {
+            byte |= 0b1000;
+        }
+        byte | match self.retain_handling {
+            RetainHandling::SendOnSubscribe => 0,
+            RetainHandling::SendOnNewSubscribe => 0b010000,
+            RetainHandling::DoNotSend => 0b100000,
+        }
+    }
+}
+
+impl From<QoS> for SubscriptionOptions {
+    /// Options matching the MQTT 3.1.1 behaviour, with the given maximum `QoS`.
+    fn from(qos: QoS) -> Self {
+        SubscriptionOptions {
+            qos,
+            no_local: false,
+            retain_as_published: false,
+            retain_handling: RetainHandling::SendOnSubscribe,
+        }
+    }
+}
+
+/// Subscribe topic.
+///
+/// [Subscribe] packets contain a `Vec` of those.
+///
+/// [Subscribe]: struct.Subscribe.html
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "derive", derive(Serialize, Deserialize))]
+pub struct SubscribeTopic {
+    pub topic_path: LimitedString,
+    pub options: SubscriptionOptions,
+}
+
+impl SubscribeTopic {
+    pub(crate) fn from_buffer(buf: &[u8], offset: &mut usize) -> Result<Self, Error> {
+        let topic_path = limited_string(read_str(buf, offset)?)?;
+        let options = SubscriptionOptions::from_u8(read_u8(buf, offset)?)?;
+        Ok(SubscribeTopic {
+            topic_path,
+            options,
+        })
+    }
+}
+
+/// Subscribe packet ([MQTT 3.8]).
+///
+/// [MQTT 3.8]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901161
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscribe<'a> {
+    pub pid: Pid,
+    pub properties: Properties<'a>,
+    pub topics: LimitedVec<SubscribeTopic>,
+}
+
+/// Suback packet ([MQTT 3.9]).
+///
+/// Contains one reason code per topic of the matching [Subscribe] packet.
+///
+/// [Subscribe]: struct.Subscribe.html
+/// [MQTT 3.9]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901171
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suback<'a> {
+    pub pid: Pid,
+    pub properties: Properties<'a>,
+    pub reason_codes: LimitedVec<ReasonCode>,
+}
+
+/// Unsubscribe packet ([MQTT 3.10]).
+///
+/// [MQTT 3.10]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901179
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsubscribe<'a> {
+    pub pid: Pid,
+    pub properties: Properties<'a>,
+    pub topics: LimitedVec<LimitedString>,
+}
+
+/// Unsuback packet ([MQTT 3.11]).
+///
+/// Contains one reason code per topic of the matching [Unsubscribe] packet.
+///
+/// [Unsubscribe]: struct.Unsubscribe.html
+/// [MQTT 3.11]: https://docs.oasis-open.org/mqtt/mqtt/v5.0/os/mqtt-v5.0-os.html#_Toc3901187
+#[cfg_attr(feature = "defmt",derive(Format))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsuback<'a> {
+    pub pid: Pid,
+    pub properties: Properties<'a>,
+    pub reason_codes: LimitedVec<ReasonCode>,
+}
+
+impl<'a> Subscribe<'a> {
+    pub fn new(pid: Pid, topics: LimitedVec<SubscribeTopic>) -> Self {
+        Subscribe {
+            pid,
+            properties: Properties::new(),
+            topics,
+        }
+    }
+
+    pub(crate) fn from_buffer(
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let payload_end = *offset + remaining_len;
+        let pid = Pid::from_buffer(buf, offset)?;
+        let location = Location::Packet(PacketType::Subscribe);
+        let properties = Properties::from_buffer(buf, offset, location)?;
+
+        let mut topics = LimitedVec::new();
+        while *offset < payload_end {
+            limited_push(&mut topics, SubscribeTopic::from_buffer(buf, offset)?)?;
+        }
+
+        Ok(Subscribe {
+            pid,
+            properties,
+            topics,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let header: u8 = 0b10000010;
+        check_remaining(buf, offset, 1)?;
+        write_u8(buf, offset, header)?;
+
+        // Length: pid(2) + properties + topic.for_each(2+len + options(1))
+        let mut length = 2 + self.properties.len();
+        for topic in &self.topics {
+            length += topic.topic_path.len() + 2 + 1;
+        }
+        let write_len = write_length(buf, offset, length)? + 1;
+
+        self.pid.to_buffer(buf, offset)?;
+        self.properties
+            .to_buffer(Location::Packet(PacketType::Subscribe), buf, offset)?;
+        for topic in &self.topics {
+            write_string(buf, offset, topic.topic_path.as_str())?;
+            write_u8(buf, offset, topic.options.to_u8())?;
+        }
+
+        Ok(write_len)
+    }
+}
+
+impl<'a> Unsubscribe<'a> {
+    pub fn new(pid: Pid, topics: LimitedVec<LimitedString>) -> Self {
+        Unsubscribe {
+            pid,
+            properties: Properties::new(),
+            topics,
+        }
+    }
+
+    pub(crate) fn from_buffer(
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let payload_end = *offset + remaining_len;
+        let pid = Pid::from_buffer(buf, offset)?;
+        let location = Location::Packet(PacketType::Unsubscribe);
+        let properties = Properties::from_buffer(buf, offset, location)?;
+
+        let mut topics = LimitedVec::new();
+        while *offset < payload_end {
+            limited_push(&mut topics, limited_string(read_str(buf, offset)?)?)?;
+        }
+
+        Ok(Unsubscribe {
+            pid,
+            properties,
+            topics,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        let header: u8 = 0b10100010;
+        let mut length = 2 + self.properties.len();
+        for topic in &self.topics {
+            length += 2 + topic.len();
+        }
+        check_remaining(buf, offset, 1)?;
+        write_u8(buf, offset, header)?;
+
+        let write_len = write_length(buf, offset, length)? + 1;
+        self.pid.to_buffer(buf, offset)?;
+        self.properties
+            .to_buffer(Location::Packet(PacketType::Unsubscribe), buf, offset)?;
+        for topic in &self.topics {
+            write_string(buf, offset, topic)?;
+        }
+        Ok(write_len)
+    }
+}
+
+impl<'a> Suback<'a> {
+    pub fn new(pid: Pid, reason_codes: LimitedVec<ReasonCode>) -> Self {
+        Suback {
+            pid,
+            properties: Properties::new(),
+            reason_codes,
+        }
+    }
+
+    pub(crate) fn from_buffer(
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let (pid, properties, reason_codes) =
+            read_reason_codes(PacketType::Suback, remaining_len, buf, offset)?;
+        Ok(Suback {
+            pid,
+            properties,
+            reason_codes,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        write_reason_codes(
+            PacketType::Suback,
+            0b10010000,
+            self.pid,
+            &self.properties,
+            &self.reason_codes,
+            buf,
+            offset,
+        )
+    }
+}
+
+impl<'a> Unsuback<'a> {
+    pub fn new(pid: Pid, reason_codes: LimitedVec<ReasonCode>) -> Self {
+        Unsuback {
+            pid,
+            properties: Properties::new(),
+            reason_codes,
+        }
+    }
+
+    pub(crate) fn from_buffer(
+        remaining_len: usize,
+        buf: &'a [u8],
+        offset: &mut usize,
+    ) -> Result<Self, Error> {
+        let (pid, properties, reason_codes) =
+            read_reason_codes(PacketType::Unsuback, remaining_len, buf, offset)?;
+        Ok(Unsuback {
+            pid,
+            properties,
+            reason_codes,
+        })
+    }
+
+    pub(crate) fn to_buffer(&self, buf: &mut [u8], offset: &mut usize) -> Result<usize, Error> {
+        write_reason_codes(
+            PacketType::Unsuback,
+            0b10110000,
+            self.pid,
+            &self.properties,
+            &self.reason_codes,
+            buf,
+            offset,
+        )
+    }
+}
+
+/// Read the shared Suback/Unsuback layout: pid, properties, and one reason code per topic.
+fn read_reason_codes<'a>(
+    typ: PacketType,
+    remaining_len: usize,
+    buf: &'a [u8],
+    offset: &mut usize,
+) -> Result<(Pid, Properties<'a>, LimitedVec<ReasonCode>), Error> {
+    let payload_end = *offset + remaining_len;
+    let pid = Pid::from_buffer(buf, offset)?;
+    let properties = Properties::from_buffer(buf, offset, Location::Packet(typ))?;
+
+    let mut reason_codes = LimitedVec::new();
+    while *offset < payload_end {
+        limited_push(&mut reason_codes, ReasonCode::from_buffer(typ, buf, offset)?)?;
+    }
+    Ok((pid, properties, reason_codes))
+}
+
+fn write_reason_codes(
+    typ: PacketType,
+    header: u8,
+    pid: Pid,
+    properties: &Properties,
+    reason_codes: &[ReasonCode],
+    buf: &mut [u8],
+    offset: &mut usize,
+) -> Result<usize, Error> {
+    let length = 2 + properties.len() + reason_codes.len();
+    check_remaining(buf, offset, 1)?;
+    write_u8(buf, offset, header)?;
+
+    let write_len = write_length(buf, offset, length)? + 1;
+    pid.to_buffer(buf, offset)?;
+    properties.to_buffer(Location::Packet(typ), buf, offset)?;
+    for rc in reason_codes {
+        rc.to_buffer(typ, buf, offset)?;
+    }
+    Ok(write_len)
+}